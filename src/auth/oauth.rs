@@ -257,7 +257,6 @@ impl OAuthFlow {
         &self,
         registration_endpoint: &Option<String>,
         auth_server: &str,
-        auth_store: &mut AuthStore,
     ) -> Result<(TcpListener, String, StoredClient)> {
         let registration_endpoint = registration_endpoint
             .as_ref()
@@ -279,8 +278,7 @@ impl OAuthFlow {
             client_secret: response.client_secret,
             redirect_uri: Some(redirect_uri.clone()),
         };
-        auth_store.set_client(auth_server.to_string(), client.clone());
-        auth_store.save()?;
+        AuthStore::update(|store| store.set_client(auth_server.to_string(), client.clone()))?;
 
         Ok((listener, redirect_uri, client))
     }
@@ -336,7 +334,6 @@ impl OAuthFlow {
     }
 
     /// Refresh an access token
-    #[allow(dead_code)]
     pub async fn refresh_token(
         &self,
         token_endpoint: &str,
@@ -382,6 +379,66 @@ impl OAuthFlow {
             .context("Failed to parse token response")
     }
 
+    /// Refresh this server's stored token, replacing `stale_access_token`.
+    ///
+    /// Runs under the auth store lock. If another relay process already replaced
+    /// the stale token while we waited, its token is returned instead, so a
+    /// rotated refresh token is never used twice.
+    pub async fn refresh_stored_token(&self, stale_access_token: &str) -> Result<StoredToken> {
+        let _lock = tokio::task::spawn_blocking(AuthStore::lock).await??;
+        let mut auth_store = AuthStore::load()?;
+
+        let stored = auth_store
+            .get_token(&self.server_name)
+            .cloned()
+            .ok_or_else(|| anyhow!("No stored token for '{}'", self.server_name))?;
+        if stored.access_token != stale_access_token {
+            return Ok(stored);
+        }
+
+        let refresh_token = stored.refresh_token.as_deref().ok_or_else(|| {
+            anyhow!(
+                "Stored token for '{}' cannot be refreshed",
+                self.server_name
+            )
+        })?;
+        let (auth_server, token_endpoint) = stored
+            .auth_server
+            .as_deref()
+            .zip(stored.token_endpoint.as_deref())
+            .ok_or_else(|| {
+                anyhow!(
+                    "Stored token for '{}' has no refresh endpoint",
+                    self.server_name
+                )
+            })?;
+        let client = auth_store
+            .get_client(auth_server)
+            .cloned()
+            .ok_or_else(|| anyhow!("No client registration for {}", auth_server))?;
+
+        tracing::debug!("Refreshing OAuth token for '{}'", self.server_name);
+        let token_response = self
+            .refresh_token(
+                token_endpoint,
+                refresh_token,
+                &client.client_id,
+                client.client_secret.as_deref(),
+            )
+            .await?;
+
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+        let refreshed = refreshed_token(&stored, token_response, now);
+
+        auth_store.set_token(self.server_name.clone(), refreshed.clone());
+        auth_store.save()?;
+
+        Ok(refreshed)
+    }
+
     /// Run the full OAuth flow
     pub async fn authenticate(&self, resource_metadata_url: &str) -> Result<StoredToken> {
         println!("Fetching resource metadata...");
@@ -421,7 +478,6 @@ impl OAuthFlow {
                                     .register_new_client(
                                         &auth_metadata.registration_endpoint,
                                         auth_server,
-                                        &mut auth_store,
                                     )
                                     .await?;
                                 (listener, uri, client)
@@ -431,11 +487,7 @@ impl OAuthFlow {
                         // Invalid stored redirect_uri - re-register
                         auth_store.remove_client(auth_server);
                         let (listener, uri, client) = self
-                            .register_new_client(
-                                &auth_metadata.registration_endpoint,
-                                auth_server,
-                                &mut auth_store,
-                            )
+                            .register_new_client(&auth_metadata.registration_endpoint, auth_server)
                             .await?;
                         (listener, uri, client)
                     }
@@ -443,22 +495,14 @@ impl OAuthFlow {
                     // No stored redirect_uri (legacy client) - re-register
                     auth_store.remove_client(auth_server);
                     let (listener, uri, client) = self
-                        .register_new_client(
-                            &auth_metadata.registration_endpoint,
-                            auth_server,
-                            &mut auth_store,
-                        )
+                        .register_new_client(&auth_metadata.registration_endpoint, auth_server)
                         .await?;
                     (listener, uri, client)
                 }
             } else {
                 // No stored client - register new one
                 let (listener, uri, client) = self
-                    .register_new_client(
-                        &auth_metadata.registration_endpoint,
-                        auth_server,
-                        &mut auth_store,
-                    )
+                    .register_new_client(&auth_metadata.registration_endpoint, auth_server)
                     .await?;
                 (listener, uri, client)
             };
@@ -537,11 +581,12 @@ impl OAuthFlow {
             refresh_token: token_response.refresh_token,
             expires_at: token_response.expires_in.map(|e| now + e),
             token_type: token_response.token_type,
+            auth_server: Some(auth_server.to_string()),
+            token_endpoint: Some(auth_metadata.token_endpoint.clone()),
         };
 
         // Store token
-        auth_store.set_token(self.server_name.clone(), stored_token.clone());
-        auth_store.save()?;
+        AuthStore::update(|store| store.set_token(self.server_name.clone(), stored_token.clone()))?;
 
         Ok(stored_token)
     }
@@ -604,7 +649,6 @@ impl OAuthFlow {
                                     .register_new_client(
                                         &auth_metadata.registration_endpoint,
                                         auth_server,
-                                        &mut auth_store,
                                     )
                                     .await?;
                                 (listener, uri, client)
@@ -614,11 +658,7 @@ impl OAuthFlow {
                         // Invalid stored redirect_uri - re-register
                         auth_store.remove_client(auth_server);
                         let (listener, uri, client) = self
-                            .register_new_client(
-                                &auth_metadata.registration_endpoint,
-                                auth_server,
-                                &mut auth_store,
-                            )
+                            .register_new_client(&auth_metadata.registration_endpoint, auth_server)
                             .await?;
                         (listener, uri, client)
                     }
@@ -626,22 +666,14 @@ impl OAuthFlow {
                     // No stored redirect_uri (legacy client) - re-register
                     auth_store.remove_client(auth_server);
                     let (listener, uri, client) = self
-                        .register_new_client(
-                            &auth_metadata.registration_endpoint,
-                            auth_server,
-                            &mut auth_store,
-                        )
+                        .register_new_client(&auth_metadata.registration_endpoint, auth_server)
                         .await?;
                     (listener, uri, client)
                 }
             } else {
                 // No stored client - register new one
                 let (listener, uri, client) = self
-                    .register_new_client(
-                        &auth_metadata.registration_endpoint,
-                        auth_server,
-                        &mut auth_store,
-                    )
+                    .register_new_client(&auth_metadata.registration_endpoint, auth_server)
                     .await?;
                 (listener, uri, client)
             };
@@ -713,16 +745,30 @@ impl OAuthFlow {
             refresh_token: token_response.refresh_token,
             expires_at: token_response.expires_in.map(|e| now + e),
             token_type: token_response.token_type,
+            auth_server: Some(auth_server.to_string()),
+            token_endpoint: Some(auth_metadata.token_endpoint.clone()),
         };
 
         // Store token
-        auth_store.set_token(self.server_name.clone(), stored_token.clone());
-        auth_store.save()?;
+        AuthStore::update(|store| store.set_token(self.server_name.clone(), stored_token.clone()))?;
 
         Ok(stored_token)
     }
 }
 
+/// Build the stored token from a refresh response. Servers that don't rotate
+/// refresh tokens omit it from the response, so the old one is kept.
+fn refreshed_token(old: &StoredToken, response: TokenResponse, now: u64) -> StoredToken {
+    StoredToken {
+        access_token: response.access_token,
+        refresh_token: response.refresh_token.or_else(|| old.refresh_token.clone()),
+        expires_at: response.expires_in.map(|e| now + e),
+        token_type: response.token_type,
+        auth_server: old.auth_server.clone(),
+        token_endpoint: old.token_endpoint.clone(),
+    }
+}
+
 fn parse_callback(request_line: &str, expected_state: &str) -> Result<String> {
     // Parse: GET /callback?code=xxx&state=yyy HTTP/1.1
     let path = request_line
@@ -770,3 +816,52 @@ fn parse_callback(request_line: &str, expected_state: &str) -> Result<String> {
 
     code.ok_or_else(|| anyhow!("Missing authorization code in callback"))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stored_token() -> StoredToken {
+        StoredToken {
+            access_token: "old-access".to_string(),
+            refresh_token: Some("old-refresh".to_string()),
+            expires_at: Some(100),
+            token_type: "Bearer".to_string(),
+            auth_server: Some("https://auth.example.com".to_string()),
+            token_endpoint: Some("https://auth.example.com/token".to_string()),
+        }
+    }
+
+    #[test]
+    fn test_refreshed_token_keeps_unrotated_refresh_token() {
+        let response = TokenResponse {
+            access_token: "new-access".to_string(),
+            token_type: "Bearer".to_string(),
+            expires_in: Some(3600),
+            refresh_token: None,
+        };
+
+        let token = refreshed_token(&stored_token(), response, 1000);
+        assert_eq!(token.access_token, "new-access");
+        assert_eq!(token.refresh_token.as_deref(), Some("old-refresh"));
+        assert_eq!(token.expires_at, Some(4600));
+        assert_eq!(
+            token.token_endpoint.as_deref(),
+            Some("https://auth.example.com/token")
+        );
+    }
+
+    #[test]
+    fn test_refreshed_token_uses_rotated_refresh_token() {
+        let response = TokenResponse {
+            access_token: "new-access".to_string(),
+            token_type: "Bearer".to_string(),
+            expires_in: None,
+            refresh_token: Some("new-refresh".to_string()),
+        };
+
+        let token = refreshed_token(&stored_token(), response, 1000);
+        assert_eq!(token.refresh_token.as_deref(), Some("new-refresh"));
+        assert_eq!(token.expires_at, None);
+    }
+}
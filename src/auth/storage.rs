@@ -1,7 +1,10 @@
 use anyhow::{Context, Result};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
-use std::path::PathBuf;
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+use std::path::{Path, PathBuf};
 
 /// Stored OAuth tokens for a server
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -10,6 +13,26 @@ pub struct StoredToken {
     pub refresh_token: Option<String>,
     pub expires_at: Option<u64>,
     pub token_type: String,
+    /// Authorization server that issued the token (key into `AuthStore::clients`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub auth_server: Option<String>,
+    /// Token endpoint used to refresh the token
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub token_endpoint: Option<String>,
+}
+
+impl StoredToken {
+    /// A token that can only be replaced by hand (no refresh information)
+    pub fn manual(access_token: String) -> Self {
+        Self {
+            access_token,
+            refresh_token: None,
+            expires_at: None,
+            token_type: "Bearer".to_string(),
+            auth_server: None,
+            token_endpoint: None,
+        }
+    }
 }
 
 /// OAuth client registration for a server
@@ -22,6 +45,17 @@ pub struct StoredClient {
     pub redirect_uri: Option<String>,
 }
 
+/// The Authorization header value for a stored token. Tokens that already
+/// name their scheme (`Bearer`, `token`, `Basic`) are sent as is; bare ones
+/// as Bearer tokens.
+pub fn authorization_value(token: &str) -> String {
+    if token.starts_with("Bearer ") || token.starts_with("token ") || token.starts_with("Basic ") {
+        token.to_string()
+    } else {
+        format!("Bearer {}", token)
+    }
+}
+
 /// All auth data for servers
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
 pub struct AuthStore {
@@ -31,13 +65,46 @@ pub struct AuthStore {
     pub clients: HashMap<String, StoredClient>,
 }
 
+/// Exclusive lock on the auth store, released on drop
+pub struct AuthStoreLock {
+    _file: File,
+}
+
 impl AuthStore {
-    fn path() -> Result<PathBuf> {
+    fn config_dir() -> Result<PathBuf> {
         let home = std::env::var("HOME").context("HOME environment variable not set")?;
         let config_dir = PathBuf::from(home).join(".config").join("relay");
         std::fs::create_dir_all(&config_dir)
             .with_context(|| format!("Failed to create config directory: {:?}", config_dir))?;
-        Ok(config_dir.join("auth.json"))
+        Ok(config_dir)
+    }
+
+    fn path() -> Result<PathBuf> {
+        Ok(Self::config_dir()?.join("auth.json"))
+    }
+
+    /// Take an exclusive lock shared by every relay process. Hold it across a
+    /// load/modify/save cycle so concurrent processes don't clobber each other.
+    pub fn lock() -> Result<AuthStoreLock> {
+        let path = Self::config_dir()?.join("auth.json.lock");
+        let file = OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(&path)
+            .with_context(|| format!("Failed to open auth lock file {:?}", path))?;
+        file.lock()
+            .with_context(|| format!("Failed to lock {:?}", path))?;
+        Ok(AuthStoreLock { _file: file })
+    }
+
+    /// Apply a change to the latest on-disk auth store under the lock
+    pub fn update(f: impl FnOnce(&mut AuthStore)) -> Result<AuthStore> {
+        let _lock = Self::lock()?;
+        let mut store = Self::load()?;
+        f(&mut store);
+        store.save()?;
+        Ok(store)
     }
 
     pub fn load() -> Result<Self> {
@@ -53,10 +120,27 @@ impl AuthStore {
     }
 
     pub fn save(&self) -> Result<()> {
-        let path = Self::path()?;
+        self.save_to(&Self::path()?)
+    }
+
+    fn save_to(&self, path: &Path) -> Result<()> {
         let contents =
             serde_json::to_string_pretty(self).context("Failed to serialize auth store")?;
-        std::fs::write(&path, contents)
+        // Write to a temporary file and rename so readers never see a partial
+        // file. It holds tokens, so only the user may read it.
+        let tmp_path = path.with_extension("json.tmp");
+        let mut file = OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .mode(0o600)
+            .open(&tmp_path)
+            .with_context(|| format!("Failed to write auth store to {:?}", tmp_path))?;
+        // The mode only applies to new files, not one left by an earlier save
+        file.set_permissions(std::fs::Permissions::from_mode(0o600))?;
+        file.write_all(contents.as_bytes())
+            .with_context(|| format!("Failed to write auth store to {:?}", tmp_path))?;
+        std::fs::rename(&tmp_path, path)
             .with_context(|| format!("Failed to write auth store to {:?}", path))?;
         Ok(())
     }
@@ -85,7 +169,6 @@ impl AuthStore {
         self.clients.remove(auth_server);
     }
 
-    #[allow(dead_code)]
     pub fn is_token_expired(&self, server_name: &str) -> bool {
         if let Some(token) = self.tokens.get(server_name) {
             if let Some(expires_at) = token.expires_at {
@@ -100,3 +183,36 @@ impl AuthStore {
         false
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_save_is_private() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("auth.json");
+        // Left world-readable by an earlier relay
+        std::fs::write(&path, "{}").unwrap();
+        std::fs::write(path.with_extension("json.tmp"), "").unwrap();
+
+        let mut store = AuthStore::default();
+        store.set_token(
+            "mock".to_string(),
+            StoredToken {
+                access_token: "secret".to_string(),
+                refresh_token: None,
+                expires_at: None,
+                token_type: "Bearer".to_string(),
+                auth_server: None,
+                token_endpoint: None,
+            },
+        );
+        store.save_to(&path).unwrap();
+
+        let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+        assert!(std::fs::read_to_string(&path).unwrap().contains("secret"));
+    }
+}
@@ -1,69 +1,577 @@
 use serde_json::{json, Value};
+use std::collections::VecDeque;
 use std::io::{BufRead, BufReader, Write};
+use std::sync::mpsc::{self, Receiver};
+use std::time::{Duration, Instant};
 
-/// A simple mock MCP server for testing
-pub fn run_mock_server() {
-    let stdin = std::io::stdin();
-    let mut stdout = std::io::stdout();
-    let reader = BufReader::new(stdin);
+/// How often the `sleep` tool reports progress and checks for cancellation
+const SLEEP_TICK: Duration = Duration::from_millis(100);
 
-    for line in reader.lines() {
-        let line = line.unwrap();
-        let request: Value = serde_json::from_str(&line).unwrap();
+fn send(stdout: &mut impl Write, message: &Value) {
+    writeln!(stdout, "{}", serde_json::to_string(message).unwrap()).unwrap();
+    stdout.flush().unwrap();
+}
 
-        let method = request["method"].as_str().unwrap_or("");
-        let id = &request["id"];
+/// Build the response to a single client request
+fn handle_request(request: &Value) -> Value {
+    let method = request["method"].as_str().unwrap_or("");
+    let id = &request["id"];
 
-        let response = match method {
-            "initialize" => json!({
+    match method {
+        "initialize" => json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "result": {
+                "protocolVersion": "2024-11-05",
+                "capabilities": { "tools": {}, "resources": {}, "prompts": {}, "logging": {} },
+                "serverInfo": { "name": "mock-server", "version": "1.0.0" }
+            }
+        }),
+        "tools/list" => json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "result": {
+                "tools": [
+                    {
+                        "name": "echo",
+                        "description": "Echo back a message",
+                        "inputSchema": {
+                            "type": "object",
+                            "properties": {
+                                "message": { "type": "string", "description": "Message to echo" }
+                            },
+                            "required": ["message"]
+                        }
+                    },
+                    {
+                        "name": "ask",
+                        "description": "Ask the client's model a question",
+                        "inputSchema": {
+                            "type": "object",
+                            "properties": {
+                                "question": { "type": "string", "description": "What to ask" }
+                            },
+                            "required": ["question"]
+                        }
+                    },
+                    {
+                        "name": "confirm",
+                        "description": "Ask the user which project to use",
+                        "inputSchema": { "type": "object", "properties": {} }
+                    },
+                    {
+                        "name": "roots",
+                        "description": "List the client's roots",
+                        "inputSchema": { "type": "object", "properties": {} }
+                    },
+                    {
+                        "name": "sleep",
+                        "description": "Wait before answering",
+                        "inputSchema": {
+                            "type": "object",
+                            "properties": {
+                                "seconds": { "type": "number", "description": "How long to wait" }
+                            },
+                            "required": ["seconds"]
+                        }
+                    }
+                ]
+            }
+        }),
+        "tools/call" => {
+            let args = &request["params"]["arguments"];
+            let message = args["message"].as_str().unwrap_or("(no message)");
+            json!({
                 "jsonrpc": "2.0",
                 "id": id,
                 "result": {
-                    "protocolVersion": "2024-11-05",
-                    "capabilities": { "tools": {} },
-                    "serverInfo": { "name": "mock-server", "version": "1.0.0" }
+                    "content": [{ "type": "text", "text": format!("Echo: {}", message) }],
+                    "isError": false
                 }
-            }),
-            "tools/list" => json!({
+            })
+        }
+        "resources/list" => json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "result": {
+                "resources": [
+                    {
+                        "uri": "mock://greeting",
+                        "name": "greeting",
+                        "description": "A friendly greeting",
+                        "mimeType": "text/plain"
+                    },
+                    {
+                        "uri": "mock://logo.png",
+                        "name": "logo",
+                        "mimeType": "image/png"
+                    }
+                ]
+            }
+        }),
+        "resources/templates/list" => json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "result": {
+                "resourceTemplates": [
+                    {
+                        "uriTemplate": "mock://greeting/{name}",
+                        "name": "personal-greeting",
+                        "description": "A greeting for someone specific"
+                    }
+                ]
+            }
+        }),
+        "resources/read" => {
+            let uri = request["params"]["uri"].as_str().unwrap_or("");
+            let contents = match uri {
+                "mock://greeting" => json!([
+                    { "uri": uri, "mimeType": "text/plain", "text": "Hello from mock" }
+                ]),
+                // "relay" encoded as base64
+                "mock://logo.png" => json!([
+                    { "uri": uri, "mimeType": "image/png", "blob": "cmVsYXk=" }
+                ]),
+                _ => match uri.strip_prefix("mock://greeting/") {
+                    Some(name) => json!([
+                        { "uri": uri, "mimeType": "text/plain", "text": format!("Hello, {}!", name) }
+                    ]),
+                    None => json!(null),
+                },
+            };
+            if contents.is_null() {
+                json!({
+                    "jsonrpc": "2.0",
+                    "id": id,
+                    "error": { "code": -32002, "message": "Resource not found" }
+                })
+            } else {
+                json!({
+                    "jsonrpc": "2.0",
+                    "id": id,
+                    "result": { "contents": contents }
+                })
+            }
+        }
+        "prompts/list" => json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "result": {
+                "prompts": [
+                    {
+                        "name": "greet",
+                        "description": "Greet someone",
+                        "arguments": [
+                            { "name": "name", "description": "Who to greet", "required": true },
+                            { "name": "style", "description": "Greeting style" }
+                        ]
+                    }
+                ]
+            }
+        }),
+        "prompts/get" => {
+            let args = &request["params"]["arguments"];
+            let name = args["name"].as_str().unwrap_or("stranger");
+            let style = args["style"].as_str().unwrap_or("friendly");
+            json!({
                 "jsonrpc": "2.0",
                 "id": id,
                 "result": {
-                    "tools": [
+                    "description": "A greeting prompt",
+                    "messages": [
                         {
-                            "name": "echo",
-                            "description": "Echo back a message",
-                            "inputSchema": {
-                                "type": "object",
-                                "properties": {
-                                    "message": { "type": "string", "description": "Message to echo" }
-                                },
-                                "required": ["message"]
-                            }
+                            "role": "user",
+                            "content": { "type": "text", "text": format!("Write a {} greeting for {}", style, name) }
+                        },
+                        {
+                            "role": "assistant",
+                            "content": { "type": "text", "text": format!("Hello, {}!", name) }
                         }
                     ]
                 }
-            }),
-            "tools/call" => {
-                let args = &request["params"]["arguments"];
-                let message = args["message"].as_str().unwrap_or("(no message)");
-                json!({
+            })
+        }
+        "logging/setLevel" => json!({ "jsonrpc": "2.0", "id": id, "result": {} }),
+        _ => json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "error": { "code": -32601, "message": "Method not found" }
+        }),
+    }
+}
+
+/// Run the `sleep` tool: wait in ticks, reporting progress if the client sent
+/// a token, and give up without answering if the client cancels the call.
+/// Anything else that arrives meanwhile is queued for later.
+fn run_sleep(
+    request: &Value,
+    stdout: &mut impl Write,
+    incoming: &Receiver<String>,
+    queued: &mut VecDeque<String>,
+) -> Option<Value> {
+    let seconds = request["params"]["arguments"]["seconds"]
+        .as_f64()
+        .unwrap_or(0.0);
+    let token = &request["params"]["_meta"]["progressToken"];
+    let deadline = Instant::now() + Duration::from_secs_f64(seconds);
+
+    loop {
+        let now = Instant::now();
+        if now >= deadline {
+            break;
+        }
+        if !token.is_null() {
+            let remaining = (deadline - now).as_secs_f64();
+            send(
+                stdout,
+                &json!({
                     "jsonrpc": "2.0",
-                    "id": id,
-                    "result": {
-                        "content": [{ "type": "text", "text": format!("Echo: {}", message) }],
-                        "isError": false
+                    "method": "notifications/progress",
+                    "params": {
+                        "progressToken": token,
+                        "progress": seconds - remaining,
+                        "total": seconds,
+                        "message": "Sleeping"
                     }
-                })
+                }),
+            );
+        }
+        if let Ok(line) = incoming.recv_timeout(SLEEP_TICK.min(deadline - now)) {
+            let message: Value = serde_json::from_str(&line).unwrap();
+            if message["method"] == "notifications/cancelled"
+                && message["params"]["requestId"] == request["id"]
+            {
+                eprintln!(
+                    "mock-server: cancelled request {} ({})",
+                    request["id"], message["params"]["reason"]
+                );
+                return None;
             }
-            _ => json!({
+            queued.push_back(line);
+        }
+    }
+
+    Some(json!({
+        "jsonrpc": "2.0",
+        "id": request["id"],
+        "result": {
+            "content": [{ "type": "text", "text": format!("Slept {}s", seconds) }],
+            "isError": false
+        }
+    }))
+}
+
+/// Run the `ask` tool: put the question to the client's model with
+/// `sampling/createMessage` and answer with what it said
+fn run_ask(
+    request: &Value,
+    stdout: &mut impl Write,
+    incoming: &Receiver<String>,
+    queued: &mut VecDeque<String>,
+    sampling: bool,
+) -> Value {
+    let text = if !sampling {
+        "Client does not support sampling".to_string()
+    } else {
+        let question = &request["params"]["arguments"]["question"];
+        let sample_id = format!("mock-sample-{}", request["id"]);
+        send(
+            stdout,
+            &json!({
                 "jsonrpc": "2.0",
-                "id": id,
-                "error": { "code": -32601, "message": "Method not found" }
+                "id": sample_id,
+                "method": "sampling/createMessage",
+                "params": {
+                    "messages": [{ "role": "user", "content": { "type": "text", "text": question } }],
+                    "systemPrompt": "Answer briefly",
+                    "maxTokens": 100
+                }
             }),
+        );
+
+        let reply = await_reply(&sample_id, incoming, queued);
+        match reply["result"]["content"]["text"].as_str() {
+            Some(answer) => format!("Model said: {}", answer),
+            None => format!("Sampling failed: {}", reply["error"]["message"]),
+        }
+    };
+
+    json!({
+        "jsonrpc": "2.0",
+        "id": request["id"],
+        "result": {
+            "content": [{ "type": "text", "text": text }],
+            "isError": !text.starts_with("Model said")
+        }
+    })
+}
+
+/// Run the `roots` tool: ask the client for its roots and answer with their URIs
+fn run_roots(
+    request: &Value,
+    stdout: &mut impl Write,
+    incoming: &Receiver<String>,
+    queued: &mut VecDeque<String>,
+) -> Value {
+    let roots_id = format!("mock-roots-{}", request["id"]);
+    send(
+        stdout,
+        &json!({ "jsonrpc": "2.0", "id": roots_id, "method": "roots/list" }),
+    );
+    let reply = await_reply(&roots_id, incoming, queued);
+    let text = match reply["result"]["roots"].as_array() {
+        Some(roots) => roots
+            .iter()
+            .filter_map(|root| root["uri"].as_str())
+            .collect::<Vec<_>>()
+            .join("\n"),
+        None => format!("roots/list failed: {}", reply["error"]["message"]),
+    };
+
+    json!({
+        "jsonrpc": "2.0",
+        "id": request["id"],
+        "result": { "content": [{ "type": "text", "text": text }] }
+    })
+}
+
+/// Run the `confirm` tool: ask the user for a project with
+/// `elicitation/create` and answer with their response
+fn run_confirm(
+    request: &Value,
+    stdout: &mut impl Write,
+    incoming: &Receiver<String>,
+    queued: &mut VecDeque<String>,
+    elicitation: bool,
+) -> Value {
+    if !elicitation {
+        return json!({
+            "jsonrpc": "2.0",
+            "id": request["id"],
+            "result": {
+                "content": [{ "type": "text", "text": "Client does not support elicitation" }]
+            }
+        });
+    }
+    let elicit_id = format!("mock-elicit-{}", request["id"]);
+    send(
+        stdout,
+        &json!({
+            "jsonrpc": "2.0",
+            "id": elicit_id,
+            "method": "elicitation/create",
+            "params": {
+                "message": "Which project?",
+                "requestedSchema": {
+                    "type": "object",
+                    "properties": {
+                        "project": { "type": "string", "description": "Project name" },
+                        "count": { "type": "integer" },
+                        "dryRun": { "type": "boolean", "default": true }
+                    },
+                    "required": ["project"]
+                }
+            }
+        }),
+    );
+    let reply = await_reply(&elicit_id, incoming, queued);
+    let text = match reply["result"]["action"].as_str() {
+        Some(action) => format!("User chose {}: {}", action, reply["result"]["content"]),
+        None => format!("Elicitation failed: {}", reply["error"]["message"]),
+    };
+
+    json!({
+        "jsonrpc": "2.0",
+        "id": request["id"],
+        "result": { "content": [{ "type": "text", "text": text }] }
+    })
+}
+
+/// MCP log levels, least severe first
+const LOG_LEVELS: [&str; 8] = [
+    "debug",
+    "info",
+    "notice",
+    "warning",
+    "error",
+    "critical",
+    "alert",
+    "emergency",
+];
+
+/// Send a log record if the client asked for records at its level
+fn log(stdout: &mut impl Write, threshold: Option<usize>, level: &str, data: Value) {
+    let rank = LOG_LEVELS.iter().position(|known| *known == level);
+    if threshold.is_some_and(|threshold| rank >= Some(threshold)) {
+        send(
+            stdout,
+            &json!({
+                "jsonrpc": "2.0",
+                "method": "notifications/message",
+                "params": { "level": level, "logger": "mock", "data": data }
+            }),
+        );
+    }
+}
+
+/// Wait for the client's reply to one of our requests, queueing anything else
+fn await_reply(id: &str, incoming: &Receiver<String>, queued: &mut VecDeque<String>) -> Value {
+    for line in incoming.iter() {
+        let message: Value = serde_json::from_str(&line).unwrap();
+        if message["id"] == id && message.get("method").is_none() {
+            return message;
+        }
+        queued.push_back(line);
+    }
+    Value::Null
+}
+
+/// Read stdin on a thread so long-running tools can watch for cancellation
+fn read_lines() -> Receiver<String> {
+    let (sender, receiver) = mpsc::channel();
+    std::thread::spawn(move || {
+        for line in BufReader::new(std::io::stdin()).lines() {
+            let Ok(line) = line else { break };
+            if sender.send(line).is_err() {
+                break;
+            }
+        }
+    });
+    receiver
+}
+
+/// A simple mock MCP server for testing.
+///
+/// With `--interleave`, every response is preceded by a log notification, a
+/// progress notification and a `ping` request that the client must answer.
+/// With `--list-changed`, the server advertises `tools.listChanged` and sends
+/// `notifications/tools/list_changed` before answering each tool call.
+/// With `--no-templates`, `resources/templates/list` is an unknown method.
+/// The `sleep` tool reports progress and can be cancelled, the `ask` tool
+/// samples the client's model, the `confirm` tool elicits input from the
+/// user and the `roots` tool lists the client's roots. Once the client sets a
+/// log level, the server sends log records at that level and above.
+pub fn run_mock_server() {
+    let interleave = std::env::args().any(|arg| arg == "--interleave");
+    let list_changed = std::env::args().any(|arg| arg == "--list-changed");
+    let no_templates = std::env::args().any(|arg| arg == "--no-templates");
+    let mut stdout = std::io::stdout();
+    let incoming = read_lines();
+    let mut queued: VecDeque<String> = VecDeque::new();
+    let mut pings = 0;
+    let mut sampling = false;
+    let mut elicitation = false;
+    let mut log_level = None;
+    eprintln!("mock-server: ready");
+
+    loop {
+        let line = match queued.pop_front() {
+            Some(line) => line,
+            None => match incoming.recv() {
+                Ok(line) => line,
+                Err(_) => break,
+            },
         };
+        let request: Value = serde_json::from_str(&line).unwrap();
 
-        writeln!(stdout, "{}", serde_json::to_string(&response).unwrap()).unwrap();
-        stdout.flush().unwrap();
+        if request["method"] == "notifications/roots/list_changed" {
+            eprintln!("mock-server: roots changed");
+        }
+        // Ignore client notifications and responses
+        if request.get("method").is_none() || request.get("id").is_none() {
+            continue;
+        }
+
+        if interleave {
+            send(
+                &mut stdout,
+                &json!({
+                    "jsonrpc": "2.0",
+                    "method": "notifications/message",
+                    "params": { "level": "info", "data": format!("handling {}", request["method"]) }
+                }),
+            );
+            send(
+                &mut stdout,
+                &json!({
+                    "jsonrpc": "2.0",
+                    "method": "notifications/progress",
+                    "params": { "progressToken": request["id"], "progress": 50, "total": 100 }
+                }),
+            );
+
+            pings += 1;
+            let ping_id = format!("mock-ping-{}", pings);
+            send(
+                &mut stdout,
+                &json!({ "jsonrpc": "2.0", "id": ping_id, "method": "ping" }),
+            );
+
+            await_reply(&ping_id, &incoming, &mut queued);
+        }
+
+        if request["method"] == "initialize" {
+            sampling = !request["params"]["capabilities"]["sampling"].is_null();
+            elicitation = !request["params"]["capabilities"]["elicitation"].is_null();
+        }
+        let tool = &request["params"]["name"];
+        let mut response = if request["method"] == "tools/call" && tool == "sleep" {
+            match run_sleep(&request, &mut stdout, &incoming, &mut queued) {
+                Some(response) => response,
+                None => continue,
+            }
+        } else if request["method"] == "tools/call" && tool == "ask" {
+            run_ask(&request, &mut stdout, &incoming, &mut queued, sampling)
+        } else if request["method"] == "tools/call" && tool == "confirm" {
+            run_confirm(&request, &mut stdout, &incoming, &mut queued, elicitation)
+        } else if request["method"] == "tools/call" && tool == "roots" {
+            run_roots(&request, &mut stdout, &incoming, &mut queued)
+        } else {
+            handle_request(&request)
+        };
+        if request["method"] == "logging/setLevel" {
+            let level = request["params"]["level"].as_str().unwrap_or("");
+            log_level = LOG_LEVELS.iter().position(|known| *known == level);
+            send(&mut stdout, &response);
+            log(
+                &mut stdout,
+                log_level,
+                "info",
+                json!(format!("log level set to {}", level)),
+            );
+            log(
+                &mut stdout,
+                log_level,
+                "debug",
+                json!({ "detail": "debug records enabled" }),
+            );
+            continue;
+        }
+        if request["method"] == "tools/call" {
+            log(
+                &mut stdout,
+                log_level,
+                "warning",
+                json!(format!("tool called: {}", tool.as_str().unwrap_or(""))),
+            );
+        }
+        if no_templates && request["method"] == "resources/templates/list" {
+            response = json!({
+                "jsonrpc": "2.0",
+                "id": request["id"],
+                "error": { "code": -32601, "message": "Method not found" }
+            });
+        }
+        if list_changed && request["method"] == "initialize" {
+            response["result"]["capabilities"]["tools"]["listChanged"] = json!(true);
+        }
+        if list_changed && request["method"] == "tools/call" {
+            send(
+                &mut stdout,
+                &json!({ "jsonrpc": "2.0", "method": "notifications/tools/list_changed" }),
+            );
+        }
+        send(&mut stdout, &response);
     }
 }
 
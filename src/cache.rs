@@ -0,0 +1,266 @@
+use crate::config::{ConnectionSettings, ServerConfig};
+use crate::mcp::Tool;
+use crate::paths::{cache_dir, server_file_name};
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// How long a cached tool list is trusted without hearing from the server
+pub const TOOLS_TTL: Duration = Duration::from_secs(60 * 60);
+
+/// One server's cached `tools/list` result
+#[derive(Debug, Serialize, Deserialize)]
+struct CacheEntry {
+    config_hash: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    server_version: Option<String>,
+    /// Seconds since the Unix epoch
+    fetched_at: u64,
+    tools: Vec<Tool>,
+}
+
+/// Tool lists cached on disk per server, under `~/.cache/relay/tools`.
+///
+/// An entry is only used while the server's config and reported version
+/// match the ones it was fetched with, and for at most the TTL.
+#[derive(Clone)]
+pub struct ToolCache {
+    dir: PathBuf,
+    ttl: Duration,
+    refresh: bool,
+}
+
+impl ToolCache {
+    pub fn new() -> Result<Self> {
+        Ok(Self::with_dir(cache_dir()?.join("tools")))
+    }
+
+    pub fn with_dir(dir: PathBuf) -> Self {
+        Self {
+            dir,
+            ttl: TOOLS_TTL,
+            refresh: false,
+        }
+    }
+
+    #[cfg(test)]
+    fn with_ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = ttl;
+        self
+    }
+
+    /// Ignore cached entries, still storing freshly fetched ones
+    pub fn with_refresh(mut self, refresh: bool) -> Self {
+        self.refresh = refresh;
+        self
+    }
+
+    fn entry_path(&self, server_name: &str) -> PathBuf {
+        self.dir.join(server_file_name(server_name, "json"))
+    }
+
+    /// The cached tools for a server, if still valid for this config and version
+    pub fn get(
+        &self,
+        server_name: &str,
+        config: &ServerConfig,
+        server_version: Option<&str>,
+    ) -> Option<Vec<Tool>> {
+        if self.refresh {
+            return None;
+        }
+        let contents = std::fs::read_to_string(self.entry_path(server_name)).ok()?;
+        let entry: CacheEntry = match serde_json::from_str(&contents) {
+            Ok(entry) => entry,
+            Err(e) => {
+                tracing::debug!(
+                    "Ignoring unreadable tool cache for '{}': {}",
+                    server_name,
+                    e
+                );
+                return None;
+            }
+        };
+
+        let age = now().saturating_sub(entry.fetched_at);
+        if entry.config_hash != config_hash(config)
+            || entry.server_version.as_deref() != server_version
+            || age >= self.ttl.as_secs()
+        {
+            return None;
+        }
+        tracing::debug!("Using cached tools for '{}' ({}s old)", server_name, age);
+        Some(entry.tools)
+    }
+
+    pub fn put(
+        &self,
+        server_name: &str,
+        config: &ServerConfig,
+        server_version: Option<&str>,
+        tools: &[Tool],
+    ) -> Result<()> {
+        let entry = CacheEntry {
+            config_hash: config_hash(config),
+            server_version: server_version.map(str::to_string),
+            fetched_at: now(),
+            tools: tools.to_vec(),
+        };
+        std::fs::create_dir_all(&self.dir)
+            .with_context(|| format!("Failed to create cache directory {:?}", self.dir))?;
+
+        // Write then rename so concurrent readers never see a partial file
+        let path = self.entry_path(server_name);
+        let temp = path.with_extension(format!("json.{}", std::process::id()));
+        std::fs::write(&temp, serde_json::to_string(&entry)?)
+            .with_context(|| format!("Failed to write {:?}", temp))?;
+        std::fs::rename(&temp, &path).with_context(|| format!("Failed to write {:?}", path))?;
+        Ok(())
+    }
+
+    /// Drop a server's cached tools, e.g. when it reports that they changed
+    pub fn invalidate(&self, server_name: &str) {
+        let path = self.entry_path(server_name);
+        match std::fs::remove_file(&path) {
+            Ok(()) => tracing::debug!("Invalidated cached tools for '{}'", server_name),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => tracing::debug!("Failed to remove {:?}: {}", path, e),
+        }
+    }
+
+    /// Remove every cached tool list, returning how many there were
+    pub fn clear(&self) -> Result<usize> {
+        let entries = match std::fs::read_dir(&self.dir) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(0),
+            Err(e) => return Err(e).with_context(|| format!("Failed to read {:?}", self.dir)),
+        };
+
+        let mut removed = 0;
+        for entry in entries {
+            let path = entry?.path();
+            if path.extension().is_some_and(|ext| ext == "json") {
+                std::fs::remove_file(&path)
+                    .with_context(|| format!("Failed to remove {:?}", path))?;
+                removed += 1;
+            }
+        }
+        Ok(removed)
+    }
+}
+
+/// Fingerprint of everything about a server that could change its tools.
+/// Timeouts, retries and roots can't, so they are left out.
+fn config_hash(config: &ServerConfig) -> String {
+    let config = ServerConfig {
+        connection: ConnectionSettings::default(),
+        roots: Vec::new(),
+        ..config.clone()
+    };
+    // Values serialize maps with sorted keys, so equal configs hash equally
+    let canonical = serde_json::to_value(&config)
+        .map(|value| value.to_string())
+        .unwrap_or_default();
+    Sha256::digest(canonical.as_bytes())
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs())
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::TransportConfig;
+
+    fn server(command: &str) -> ServerConfig {
+        ServerConfig {
+            transport: TransportConfig::Stdio {
+                command: command.to_string(),
+                args: vec![],
+                cwd: None,
+                inherit_env: true,
+                log_stderr: true,
+            },
+            env: [
+                ("A".to_string(), "1".to_string()),
+                ("B".to_string(), "2".to_string()),
+            ]
+            .into_iter()
+            .collect(),
+            connection: ConnectionSettings::default(),
+            roots: Vec::new(),
+        }
+    }
+
+    fn tools() -> Vec<Tool> {
+        vec![Tool {
+            name: "echo".to_string(),
+            description: None,
+            input_schema: None,
+        }]
+    }
+
+    #[test]
+    fn test_keyed_by_config_and_version() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = ToolCache::with_dir(dir.path().to_path_buf());
+        let config = server("mock");
+        cache.put("mock", &config, Some("1.0"), &tools()).unwrap();
+
+        let hit = cache.get("mock", &config, Some("1.0")).unwrap();
+        assert_eq!(hit[0].name, "echo");
+        assert!(cache.get("mock", &config, Some("1.1")).is_none());
+        assert!(cache.get("mock", &config, None).is_none());
+        assert!(cache.get("mock", &server("other"), Some("1.0")).is_none());
+        assert!(cache.get("other", &config, Some("1.0")).is_none());
+
+        // Connection settings don't affect the tools
+        let mut tuned = config.clone();
+        tuned.connection.timeout = Some(Duration::from_secs(5));
+        assert!(cache.get("mock", &tuned, Some("1.0")).is_some());
+
+        assert!(cache
+            .clone()
+            .with_refresh(true)
+            .get("mock", &config, Some("1.0"))
+            .is_none());
+    }
+
+    #[test]
+    fn test_expires_after_ttl() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = ToolCache::with_dir(dir.path().to_path_buf()).with_ttl(Duration::ZERO);
+        let config = server("mock");
+        cache.put("mock", &config, None, &tools()).unwrap();
+        assert!(cache.get("mock", &config, None).is_none());
+    }
+
+    #[test]
+    fn test_invalidate_and_clear() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = ToolCache::with_dir(dir.path().join("tools"));
+        assert_eq!(cache.clear().unwrap(), 0);
+
+        let config = server("mock");
+        cache.put("mock", &config, None, &tools()).unwrap();
+        cache.put("team/search", &config, None, &tools()).unwrap();
+        assert!(dir.path().join("tools/team_search.json").exists());
+
+        cache.invalidate("mock");
+        cache.invalidate("mock");
+        assert!(cache.get("mock", &config, None).is_none());
+        assert!(cache.get("team/search", &config, None).is_some());
+
+        assert_eq!(cache.clear().unwrap(), 1);
+        assert!(cache.get("team/search", &config, None).is_none());
+    }
+}
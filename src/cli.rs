@@ -1,4 +1,9 @@
+use crate::config::parse_duration;
+use crate::mcp::LoggingLevel;
 use clap::{Parser, Subcommand};
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::time::Duration;
 
 #[derive(Parser)]
 #[command(name = "relay")]
@@ -12,6 +17,16 @@ pub struct Cli {
     #[arg(long, global = true, default_value = "human")]
     pub format: OutputFormat,
 
+    #[command(flatten)]
+    pub connection: ConnectionArgs,
+
+    /// Fetch tool lists from the server instead of the cache
+    #[arg(long, global = true)]
+    pub no_cache: bool,
+
+    #[command(flatten)]
+    pub session: SessionArgs,
+
     #[command(subcommand)]
     pub command: Commands,
 }
@@ -32,14 +47,10 @@ pub enum Commands {
         /// Transport type
         #[arg(long, value_enum)]
         transport: Transport,
-        /// Command to spawn (for stdio transport)
-        #[arg(long)]
-        cmd: Option<String>,
-        /// URL (for http transport)
-        #[arg(long)]
-        url: Option<String>,
+        #[command(flatten)]
+        target: TransportArgs,
         /// Environment variables (KEY=value format)
-        #[arg(long, value_parser = parse_env_var)]
+        #[arg(long, value_parser = parse_key_value)]
         env: Vec<(String, String)>,
     },
     /// List registered servers
@@ -49,6 +60,8 @@ pub enum Commands {
         /// Server name
         name: String,
     },
+    /// Use the servers in this project's .relay.yaml
+    Trust,
     /// Ping a server to check connectivity
     Ping {
         /// Server name
@@ -81,6 +94,82 @@ pub enum Commands {
         #[arg(trailing_var_arg = true, allow_hyphen_values = true)]
         args: Vec<String>,
     },
+    /// List resources and resource templates from a server
+    Resources {
+        /// Server name (uses default if not specified)
+        server: Option<String>,
+    },
+    /// Read a resource by URI, expanding URI templates
+    Read {
+        /// Resource URI or URI template
+        uri: String,
+        /// Server name (uses default if not specified)
+        #[arg(long, short)]
+        server: Option<String>,
+        /// Template variables (KEY=value format)
+        #[arg(long = "var", value_parser = parse_key_value)]
+        vars: Vec<(String, String)>,
+        /// Write the contents to a file (decodes binary blobs)
+        #[arg(long, short)]
+        output: Option<PathBuf>,
+    },
+    /// List prompts from a server
+    Prompts {
+        /// Server name (uses default if not specified)
+        server: Option<String>,
+    },
+    /// Render a prompt
+    Prompt {
+        /// Prompt name
+        name: String,
+        /// Server name (uses default if not specified)
+        #[arg(long, short)]
+        server: Option<String>,
+        /// Prompt arguments as flags (collected dynamically)
+        #[arg(trailing_var_arg = true, allow_hyphen_values = true)]
+        args: Vec<String>,
+    },
+    /// Import servers from a Claude Desktop, Cursor or VS Code MCP config file
+    Import {
+        /// Path to claude_desktop_config.json, .cursor/mcp.json or .vscode/mcp.json
+        path: PathBuf,
+        /// Replace existing servers with the same name
+        #[arg(long)]
+        overwrite: bool,
+    },
+    /// Print the configured servers as another client's MCP config
+    Export {
+        /// Config format to export
+        #[arg(long = "as", value_enum)]
+        target: ClientFormat,
+    },
+    /// Serve all configured servers as a single MCP server
+    Serve {
+        /// Serve streamable HTTP on this address instead of stdio
+        #[arg(long)]
+        http: Option<SocketAddr>,
+    },
+    /// Keep server sessions warm in a background process that other commands use
+    Daemon {
+        #[command(subcommand)]
+        command: DaemonCommand,
+    },
+    /// Show the end of a stdio server's stderr log
+    Logs {
+        /// Server name
+        server: String,
+        /// Number of lines to show
+        #[arg(long, short = 'n', default_value_t = 50)]
+        lines: usize,
+        /// Keep a session open and stream the server's log records
+        #[arg(long, short = 'f')]
+        follow: bool,
+    },
+    /// Manage cached tool lists
+    Cache {
+        #[command(subcommand)]
+        command: CacheCommand,
+    },
     /// Update relay to the latest version
     Update,
     /// Authenticate with an MCP server
@@ -98,13 +187,143 @@ pub enum Commands {
     },
 }
 
+#[derive(Subcommand)]
+pub enum CacheCommand {
+    /// Remove every cached tool list
+    Clear,
+}
+
+#[derive(Subcommand)]
+pub enum DaemonCommand {
+    /// Start the daemon in the background
+    Start {
+        /// Close sessions unused for this long, e.g. 10m
+        #[arg(long, default_value = "10m", value_parser = parse_duration)]
+        idle_timeout: Duration,
+    },
+    /// Run the daemon in the foreground
+    Run {
+        /// Close sessions unused for this long, e.g. 10m
+        #[arg(long, default_value = "10m", value_parser = parse_duration)]
+        idle_timeout: Duration,
+    },
+    /// Show whether the daemon is running and its open sessions
+    Status,
+    /// Stop the daemon, closing every session
+    Stop,
+}
+
+/// Overrides for every server's timeout and retry settings
+#[derive(clap::Args)]
+pub struct ConnectionArgs {
+    /// Timeout for each request to a server, e.g. 30s or 500ms
+    #[arg(long, global = true, value_parser = parse_duration)]
+    pub timeout: Option<Duration>,
+    /// Timeout for connecting to HTTP servers
+    #[arg(long, global = true, value_parser = parse_duration)]
+    pub connect_timeout: Option<Duration>,
+    /// Retries for connection errors and 5xx/429 responses
+    #[arg(long, global = true)]
+    pub retries: Option<u32>,
+    /// Also retry tool calls (only safe for idempotent tools)
+    #[arg(long, global = true)]
+    pub retry_tool_calls: bool,
+}
+
+/// How relay answers and listens to servers during a session
+#[derive(clap::Args)]
+pub struct SessionArgs {
+    /// Run the sampling command for servers' requests without asking
+    #[arg(long, global = true)]
+    pub auto_approve_sampling: bool,
+    /// Answer a server's elicitation request field (KEY=value format, repeatable)
+    /// instead of asking
+    #[arg(long = "elicit", global = true, value_parser = parse_key_value)]
+    pub elicit: Vec<(String, String)>,
+    /// Ask servers to send log records at this level and above (debug, info,
+    /// notice, warning, error, critical, alert, emergency)
+    #[arg(long, global = true)]
+    pub server_log_level: Option<LoggingLevel>,
+}
+
+/// Options for spawning a stdio server
+#[derive(clap::Args)]
+pub struct StdioArgs {
+    /// Command to spawn (for stdio transport); shell-style quoting is honored
+    #[arg(long)]
+    pub cmd: Option<String>,
+    /// Argument to pass to the command verbatim (repeatable); --cmd is then the program
+    #[arg(long = "arg", allow_hyphen_values = true)]
+    pub args: Vec<String>,
+    /// Working directory for the command
+    #[arg(long)]
+    pub cwd: Option<PathBuf>,
+    /// Don't inherit relay's environment beyond PATH, HOME, USER, LANG and TMPDIR
+    #[arg(long)]
+    pub clear_env: bool,
+    /// Append the server's stderr to a log file for `relay logs`
+    #[arg(long)]
+    pub log_stderr: bool,
+}
+
+/// Where to find the server, per transport
+#[derive(clap::Args)]
+pub struct TransportArgs {
+    #[command(flatten)]
+    pub stdio: StdioArgs,
+    #[command(flatten)]
+    pub http: HttpArgs,
+    #[command(flatten)]
+    pub socket: SocketArgs,
+}
+
+/// Options for connecting to an HTTP server
+#[derive(clap::Args)]
+pub struct HttpArgs {
+    /// URL (for http and sse transports); ws:// and wss:// connect over WebSocket
+    #[arg(long)]
+    pub url: Option<String>,
+    /// Extra request header (KEY=value format, repeatable); values may use ${env:VAR}
+    #[arg(long = "header", value_parser = parse_key_value)]
+    pub headers: Vec<(String, String)>,
+    /// Keep a GET stream open for server-initiated messages (http transport)
+    #[arg(long)]
+    pub listen: bool,
+}
+
+/// Options for connecting to a server that is already listening locally
+#[derive(clap::Args)]
+pub struct SocketArgs {
+    /// Socket path (for unix transport)
+    #[arg(long)]
+    pub socket: Option<PathBuf>,
+    /// host:port (for tcp transport)
+    #[arg(long)]
+    pub address: Option<String>,
+}
+
 #[derive(Clone, Copy, clap::ValueEnum)]
 pub enum Transport {
+    /// Spawn a local process and talk over stdin/stdout
     Stdio,
+    /// Streamable HTTP, falling back to legacy SSE if the server rejects it
     Http,
+    /// Legacy HTTP+SSE
+    Sse,
+    /// Newline-delimited JSON-RPC over a Unix domain socket
+    Unix,
+    /// Newline-delimited JSON-RPC over TCP
+    Tcp,
+}
+
+#[derive(Clone, Copy, clap::ValueEnum)]
+pub enum ClientFormat {
+    Claude,
+    Cursor,
+    Vscode,
 }
 
-fn parse_env_var(s: &str) -> Result<(String, String), String> {
+fn parse_key_value(s: &str) -> Result<(String, String), String> {
     let pos = s
         .find('=')
         .ok_or_else(|| format!("invalid KEY=value: no `=` found in `{s}`"))?;
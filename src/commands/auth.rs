@@ -1,5 +1,6 @@
 use crate::auth::{AuthStore, OAuthFlow, StoredToken};
 use crate::cli::OutputFormat;
+use crate::commands::http_client;
 use crate::config::ConfigStore;
 use anyhow::{anyhow, Result};
 use owo_colors::OwoColorize;
@@ -12,17 +13,9 @@ pub async fn authenticate(
 ) -> Result<()> {
     // Handle manual token
     if let Some(token) = manual_token {
-        let mut auth_store = AuthStore::load()?;
-        auth_store.set_token(
-            name.to_string(),
-            StoredToken {
-                access_token: token,
-                refresh_token: None,
-                expires_at: None,
-                token_type: "Bearer".to_string(),
-            },
-        );
-        auth_store.save()?;
+        AuthStore::update(|store| {
+            store.set_token(name.to_string(), StoredToken::manual(token));
+        })?;
 
         match format {
             OutputFormat::Human => {
@@ -40,9 +33,10 @@ pub async fn authenticate(
         .get(name)
         .ok_or_else(|| anyhow!("Server '{}' not found", name))?;
 
-    let url = match &server_config.transport {
-        crate::config::TransportConfig::Http { url } => url.clone(),
-        crate::config::TransportConfig::Stdio { .. } => {
+    let (url, headers) = match &server_config.transport {
+        crate::config::TransportConfig::Http { url, headers, .. }
+        | crate::config::TransportConfig::Sse { url, headers } => (url.clone(), headers),
+        _ => {
             return Err(anyhow!(
                 "OAuth authentication is only supported for HTTP servers"
             ));
@@ -50,7 +44,7 @@ pub async fn authenticate(
     };
 
     // First, probe the server to get the resource metadata URL
-    let client = reqwest::Client::new();
+    let client = http_client(headers, server_config.connection.connect_timeout)?;
     let response = client
         .get(&url)
         .header("Accept", "application/json, text/event-stream")
@@ -163,6 +157,7 @@ pub async fn authenticate(
 }
 
 pub fn logout(name: &str, format: OutputFormat) -> Result<()> {
+    let _lock = AuthStore::lock()?;
     let mut auth_store = AuthStore::load()?;
 
     if auth_store.get_token(name).is_none() {
@@ -0,0 +1,20 @@
+use crate::cache::ToolCache;
+use crate::cli::OutputFormat;
+use anyhow::Result;
+use owo_colors::OwoColorize;
+
+pub fn clear_cache(cache: &ToolCache, format: OutputFormat) -> Result<()> {
+    let removed = cache.clear()?;
+
+    match format {
+        OutputFormat::Human => {
+            println!("{} Cleared {} cached tool list(s)", "✓".green(), removed);
+        }
+        OutputFormat::Json => {
+            let output = serde_json::json!({ "cleared": removed });
+            println!("{}", serde_json::to_string_pretty(&output)?);
+        }
+    }
+
+    Ok(())
+}
@@ -1,8 +1,41 @@
-use crate::auth::AuthStore;
-use crate::config::{interpolate_env_map, Config, ConfigStore, TransportConfig};
-use crate::mcp::transport::{HttpTransport, SseTransport, StdioTransport, Transport};
+use crate::auth::{AuthStore, OAuthFlow};
+use crate::commands::{can_prompt, open_server_log, root_paths, SessionHandler};
+use crate::config::{
+    interpolate_env_map, stdio_argv, Config, ConfigStore, ServerConfig, TransportConfig,
+};
+use crate::daemon::{daemon_running, DaemonTransport};
+use crate::mcp::transport::{
+    is_websocket_url, HttpTransport, RetryPolicy, RetryingTransport, SocketTransport, SseTransport,
+    StderrCapture, StdioTransport, TokenRefresher, Transport, TransportError, WebSocketTransport,
+};
 use crate::mcp::McpClient;
 use anyhow::{Context, Result};
+use async_trait::async_trait;
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::process::Command;
+
+/// Variables a stdio server keeps with `inherit_env: false`, so bare commands
+/// still resolve and programs can find their home directory and locale
+const BASE_ENV: &[&str] = &["PATH", "HOME", "USER", "LANG", "TMPDIR"];
+
+/// Refreshes a server's OAuth token via the auth store when the server rejects it
+struct StoredTokenRefresher {
+    flow: OAuthFlow,
+}
+
+#[async_trait]
+impl TokenRefresher for StoredTokenRefresher {
+    async fn refresh(&self, stale_token: &str) -> Result<String> {
+        Ok(self
+            .flow
+            .refresh_stored_token(stale_token)
+            .await?
+            .access_token)
+    }
+}
 
 /// Resolve server name, using default if not specified
 pub fn resolve_server_name(config: &Config, server: Option<String>) -> Result<String> {
@@ -14,7 +47,9 @@ pub fn resolve_server_name(config: &Config, server: Option<String>) -> Result<St
     }
 }
 
-/// Create a connected MCP client for a server
+/// Create a connected MCP client for a server, going through `relay daemon`
+/// when one is running, unless this invocation answers requests the daemon's
+/// sessions can't
 pub async fn connect(store: &ConfigStore, server_name: &str) -> Result<McpClient> {
     let config = store.load()?;
 
@@ -23,35 +58,325 @@ pub async fn connect(store: &ConfigStore, server_name: &str) -> Result<McpClient
         .get(server_name)
         .with_context(|| format!("Server '{}' not found", server_name))?;
 
-    let env = interpolate_env_map(&server_config.env);
+    let socket = store.daemon_socket();
+    if answers_for_itself(store, &config) {
+        tracing::debug!(
+            "Connecting to '{}' directly to answer its requests",
+            server_name
+        );
+    } else if daemon_running(&socket).await {
+        tracing::debug!("Connecting to '{}' through the daemon", server_name);
+        // The daemon's own session answers the server's requests
+        let handler = Arc::new(SessionHandler::new(server_name).with_roots(server_config));
+        let transport =
+            DaemonTransport::new(socket, server_name.to_string(), resolve_env(server_config))
+                .with_log_level(store.server_log_level())
+                .with_handler(handler.clone());
+        return initialize(Box::new(transport), server_config, &handler).await;
+    }
+
+    connect_direct(store, server_name, server_config).await
+}
+
+/// Whether this invocation can answer server requests the daemon's sessions
+/// can't: `--elicit` values, or sampling approved on this terminal or by
+/// this invocation's `--auto-approve-sampling`
+fn answers_for_itself(store: &ConfigStore, config: &Config) -> bool {
+    if !store.elicit_answers().is_empty() {
+        return true;
+    }
+    config.sampling.as_ref().is_some_and(|sampling| {
+        store.auto_approve_sampling() || (!sampling.auto_approve && can_prompt())
+    })
+}
 
-    // Load auth tokens
-    let auth_store = AuthStore::load().ok();
-    let access_token = auth_store
-        .as_ref()
-        .and_then(|s| s.get_token(server_name))
-        .map(|t| t.access_token.clone());
+/// Connect to a server from this process
+pub async fn connect_direct(
+    store: &ConfigStore,
+    server_name: &str,
+    server_config: &ServerConfig,
+) -> Result<McpClient> {
+    let handler = SessionHandler::new(server_name)
+        .with_sampling(store.load()?.sampling)
+        .with_elicit_answers(store.elicit_answers().clone())
+        .with_log_level(store.server_log_level())
+        .with_roots(server_config);
+    connect_with(store, server_name, server_config, handler).await
+}
+
+/// Connect from this process, answering the server's requests with `handler`
+pub async fn connect_with(
+    store: &ConfigStore,
+    server_name: &str,
+    server_config: &ServerConfig,
+    handler: SessionHandler,
+) -> Result<McpClient> {
+    let handler = Arc::new(handler);
+    let transport = open_transport(server_name, server_config, handler.clone()).await?;
+    match (
+        &server_config.transport,
+        initialize(transport, server_config, &handler).await,
+    ) {
+        // Servers that reject a streamable HTTP initialize may speak the
+        // legacy HTTP+SSE transport instead
+        (TransportConfig::Http { url, headers, .. }, Err(e))
+            if !is_websocket_url(url) && rejects_streamable_http(&e) =>
+        {
+            tracing::debug!(
+                "'{}' rejected streamable HTTP ({:#}), trying legacy SSE",
+                server_name,
+                e
+            );
+            let legacy = ServerConfig {
+                transport: TransportConfig::Sse {
+                    url: url.clone(),
+                    headers: headers.clone(),
+                },
+                ..server_config.clone()
+            };
+            let transport = open_transport(server_name, &legacy, handler.clone()).await?;
+            let client = initialize(transport, &legacy, &handler).await?;
+
+            if let Err(e) = remember_transport(store, server_name, server_config, legacy) {
+                tracing::debug!("Failed to cache detected transport: {:#}", e);
+            }
+            Ok(client)
+        }
+        (_, result) => result,
+    }
+}
+
+/// Interpolate `${env:VAR}` references with this process's environment and
+/// resolve roots against its working directory, neither of which the daemon
+/// shares
+fn resolve_env(server_config: &ServerConfig) -> ServerConfig {
+    let mut resolved = server_config.clone();
+    resolved.env = interpolate_env_map(&resolved.env);
+    resolved.roots = root_paths(server_config);
+    if let TransportConfig::Http { headers, .. } | TransportConfig::Sse { headers, .. } =
+        &mut resolved.transport
+    {
+        *headers = interpolate_env_map(headers);
+    }
+    resolved
+}
+
+/// Create the transport for a server's configured transport type
+async fn open_transport(
+    server_name: &str,
+    server_config: &ServerConfig,
+    handler: Arc<SessionHandler>,
+) -> Result<Box<dyn Transport>> {
+    let connect_timeout = server_config.connection.connect_timeout;
 
     let transport: Box<dyn Transport> = match &server_config.transport {
-        TransportConfig::Stdio { command } => Box::new(StdioTransport::spawn(command, env).await?),
-        TransportConfig::Http { url } => {
-            // Use SSE transport for URLs ending with /sse
-            if url.ends_with("/sse") {
-                Box::new(
-                    SseTransport::new(url.clone(), server_name.to_string())
-                        .with_token(access_token),
-                )
-            } else {
-                Box::new(
-                    HttpTransport::new(url.clone(), server_name.to_string())
-                        .with_token(access_token),
-                )
+        TransportConfig::Stdio {
+            command,
+            args,
+            cwd,
+            inherit_env,
+            log_stderr,
+        } => {
+            let argv = stdio_argv(command, args)?;
+            let mut cmd = Command::new(&argv[0]);
+            cmd.args(&argv[1..]);
+            if let Some(cwd) = cwd {
+                cmd.current_dir(cwd);
             }
+            if !inherit_env {
+                cmd.env_clear();
+                for name in BASE_ENV {
+                    if let Some(value) = std::env::var_os(name) {
+                        cmd.env(name, value);
+                    }
+                }
+            }
+            cmd.envs(interpolate_env_map(&server_config.env));
+            let log_file = if *log_stderr {
+                open_server_log(server_name)
+                    .inspect_err(|e| tracing::debug!("Not logging stderr: {:#}", e))
+                    .ok()
+            } else {
+                None
+            };
+            let stderr = StderrCapture {
+                server_name: server_name.to_string(),
+                log_file,
+            };
+            Box::new(StdioTransport::spawn(cmd, handler, stderr).await?)
+        }
+        TransportConfig::Http { url, headers, .. } if is_websocket_url(url) => {
+            let (access_token, refresher) = load_access_token(server_name, url).await;
+            Box::new(
+                WebSocketTransport::new(url.clone(), server_name.to_string())
+                    .with_headers(header_map(headers)?)
+                    .with_token(access_token)
+                    .with_token_refresher(refresher)
+                    .with_connect_timeout(connect_timeout)
+                    .with_handler(handler),
+            )
+        }
+        TransportConfig::Http {
+            url,
+            headers,
+            listen,
+        } => {
+            let (access_token, refresher) = load_access_token(server_name, url).await;
+            Box::new(
+                HttpTransport::builder(url.clone(), server_name.to_string())
+                    .with_client(http_client(headers, connect_timeout)?)
+                    .with_token(access_token)
+                    .with_token_refresher(refresher)
+                    .with_listen(*listen)
+                    .with_handler(handler)
+                    .build(),
+            )
+        }
+        TransportConfig::Unix { path } => {
+            Box::new(SocketTransport::connect_unix(path, connect_timeout, handler).await?)
+        }
+        TransportConfig::Tcp { address } => {
+            Box::new(SocketTransport::connect_tcp(address, connect_timeout, handler).await?)
+        }
+        TransportConfig::Sse { url, headers } => {
+            let (access_token, refresher) = load_access_token(server_name, url).await;
+            Box::new(
+                SseTransport::new(url.clone(), server_name.to_string())
+                    .with_client(http_client(headers, connect_timeout)?)
+                    .with_token(access_token)
+                    .with_token_refresher(refresher)
+                    .with_handler(handler),
+            )
         }
     };
 
-    let mut client = McpClient::new(transport);
+    Ok(transport)
+}
+
+/// Apply the server's timeout and retry settings and initialize the session,
+/// advertising what the handler can answer
+async fn initialize(
+    transport: Box<dyn Transport>,
+    server_config: &ServerConfig,
+    handler: &SessionHandler,
+) -> Result<McpClient> {
+    let connection = &server_config.connection;
+    let defaults = RetryPolicy::default();
+    let policy = RetryPolicy {
+        retries: connection.retries.unwrap_or(defaults.retries),
+        timeout: connection.timeout,
+        retry_tool_calls: connection.retry_tool_calls,
+        ..defaults
+    };
+
+    let mut client = McpClient::new(Box::new(RetryingTransport::new(transport, policy)))
+        .with_capabilities(handler.capabilities())
+        .with_progress(handler.progress())
+        .with_roots(handler.roots());
     client.initialize().await?;
 
+    if let Some(level) = handler.log_level() {
+        if client
+            .capabilities()
+            .is_some_and(|caps| caps.logging.is_some())
+        {
+            if let Err(e) = client.set_log_level(level).await {
+                tracing::debug!("Failed to set the server's log level: {:#}", e);
+            }
+        } else {
+            tracing::debug!("Server doesn't support logging; not setting its level");
+        }
+    }
     Ok(client)
 }
+
+/// Whether an initialize failure means the server doesn't speak streamable
+/// HTTP: a 400, 404 or 405, as the MCP backwards compatibility guidance has
+/// it. Other errors, such as a 403 for a bad key, are the server's answer.
+fn rejects_streamable_http(error: &anyhow::Error) -> bool {
+    matches!(
+        error.downcast_ref::<TransportError>(),
+        Some(TransportError::HttpStatus { status, .. }) if matches!(status.as_u16(), 400 | 404 | 405)
+    )
+}
+
+/// Save a detected transport so later connections skip detection. Servers
+/// from project config files are left alone.
+fn remember_transport(
+    store: &ConfigStore,
+    server_name: &str,
+    configured: &ServerConfig,
+    detected: ServerConfig,
+) -> Result<()> {
+    let mut config = store.load_user()?;
+    let Some(server) = config.servers.get_mut(server_name) else {
+        return Ok(());
+    };
+    if server.transport != configured.transport {
+        return Ok(());
+    }
+    server.transport = detected.transport;
+    store.save(&config)
+}
+
+/// Build an HTTP client that sends a server's configured headers on every request
+pub fn http_client(
+    headers: &HashMap<String, String>,
+    connect_timeout: Option<Duration>,
+) -> Result<reqwest::Client> {
+    let mut builder = reqwest::Client::builder().default_headers(header_map(headers)?);
+    if let Some(timeout) = connect_timeout {
+        builder = builder.connect_timeout(timeout);
+    }
+    builder.build().context("Failed to build HTTP client")
+}
+
+/// Interpolate a server's configured headers into a header map
+fn header_map(headers: &HashMap<String, String>) -> Result<HeaderMap> {
+    let mut header_map = HeaderMap::new();
+    for (name, value) in interpolate_env_map(headers) {
+        let name = HeaderName::from_bytes(name.as_bytes())
+            .with_context(|| format!("Invalid header name: {}", name))?;
+        let value = HeaderValue::from_str(&value)
+            .with_context(|| format!("Invalid value for header {}", name))?;
+        header_map.insert(name, value);
+    }
+    Ok(header_map)
+}
+
+/// Load the stored access token for a server, refreshing it first if it has
+/// expired. Also returns a refresher when the token can be renewed mid-session.
+async fn load_access_token(
+    server_name: &str,
+    url: &str,
+) -> (Option<String>, Option<Arc<dyn TokenRefresher>>) {
+    let Ok(auth_store) = AuthStore::load() else {
+        return (None, None);
+    };
+    let Some(token) = auth_store.get_token(server_name).cloned() else {
+        return (None, None);
+    };
+
+    if token.refresh_token.is_none() {
+        return (Some(token.access_token), None);
+    }
+
+    let flow = OAuthFlow::new(server_name.to_string(), url.to_string());
+    let access_token = if auth_store.is_token_expired(server_name) {
+        match flow.refresh_stored_token(&token.access_token).await {
+            Ok(refreshed) => refreshed.access_token,
+            Err(e) => {
+                // Fall back to the old token; the server will ask for re-auth if needed
+                tracing::debug!("Failed to refresh token for '{}': {:#}", server_name, e);
+                token.access_token
+            }
+        }
+    } else {
+        token.access_token
+    };
+
+    (
+        Some(access_token),
+        Some(Arc::new(StoredTokenRefresher { flow })),
+    )
+}
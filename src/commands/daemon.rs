@@ -0,0 +1,184 @@
+use crate::cli::OutputFormat;
+use crate::config::{format_duration, ConfigStore};
+use crate::daemon::{daemon_request, daemon_running, Daemon, DaemonReply, DaemonRequest};
+use anyhow::{bail, Context, Result};
+use owo_colors::OwoColorize;
+use std::os::unix::process::CommandExt;
+use std::process::{Command, Stdio};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// How long to wait for the daemon to come up or go away
+const DAEMON_WAIT: Duration = Duration::from_secs(10);
+
+/// Run the daemon in this process until it is stopped
+pub async fn daemon_run(store: &ConfigStore, idle_timeout: Duration) -> Result<()> {
+    let socket = store.daemon_socket();
+    let daemon = Arc::new(Daemon::new(store.clone(), idle_timeout));
+    tracing::info!("Daemon listening on {}", socket.display());
+    daemon.run(&socket).await
+}
+
+/// Start the daemon as a detached background process
+pub async fn daemon_start(
+    store: &ConfigStore,
+    idle_timeout: Duration,
+    format: OutputFormat,
+) -> Result<()> {
+    let socket = store.daemon_socket();
+    if daemon_running(&socket).await {
+        bail!("Daemon is already running on {}", socket.display());
+    }
+
+    let exe = std::env::current_exe().context("Failed to locate the relay executable")?;
+    let mut command = Command::new(exe);
+    command
+        .args(["daemon", "run", "--idle-timeout"])
+        .arg(format_duration(idle_timeout));
+    // The daemon has no terminal to ask for approval on
+    if store.auto_approve_sampling() {
+        command.arg("--auto-approve-sampling");
+    }
+    let mut child = command
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        // Keep the terminal's Ctrl-C from reaching the daemon
+        .process_group(0)
+        .spawn()
+        .context("Failed to start daemon")?;
+
+    let started = Instant::now();
+    while !daemon_running(&socket).await {
+        if let Some(status) = child.try_wait()? {
+            bail!("Daemon exited during startup ({})", status);
+        }
+        if started.elapsed() > DAEMON_WAIT {
+            bail!("Daemon did not start listening on {}", socket.display());
+        }
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+
+    match format {
+        OutputFormat::Human => {
+            println!(
+                "{} Daemon started {}",
+                "✓".green(),
+                format!("(pid {})", child.id()).dimmed()
+            );
+        }
+        OutputFormat::Json => {
+            let output = serde_json::json!({ "started": true, "pid": child.id() });
+            println!("{}", serde_json::to_string_pretty(&output)?);
+        }
+    }
+
+    Ok(())
+}
+
+/// Show whether the daemon is running and the sessions it holds
+pub async fn daemon_status(store: &ConfigStore, format: OutputFormat) -> Result<()> {
+    let socket = store.daemon_socket();
+    let status = if daemon_running(&socket).await {
+        match daemon_request(&socket, &DaemonRequest::Status).await? {
+            DaemonReply::Status(status) => Some(status),
+            reply => bail!("Unexpected reply from daemon: {:?}", reply),
+        }
+    } else {
+        None
+    };
+
+    match format {
+        OutputFormat::Human => {
+            let Some(status) = status else {
+                println!("{}", "Daemon is not running".dimmed());
+                return Ok(());
+            };
+
+            println!(
+                "{} Daemon running {}",
+                "✓".green(),
+                format!(
+                    "(pid {}, up {}, idle timeout {})",
+                    status.pid,
+                    format_duration(Duration::from_secs(status.uptime_secs)),
+                    format_duration(Duration::from_secs(status.idle_timeout_secs))
+                )
+                .dimmed()
+            );
+
+            if status.sessions.is_empty() {
+                println!("  {}", "No open sessions".dimmed());
+            }
+            for session in &status.sessions {
+                let info = match &session.server_info {
+                    Some(info) => {
+                        format!("{} v{}", info.name, info.version.as_deref().unwrap_or("?"))
+                    }
+                    None => "connecting".to_string(),
+                };
+                println!(
+                    "  {:<20} {} {}",
+                    session.server.cyan(),
+                    info,
+                    format!(
+                        "({} requests, idle {})",
+                        session.requests,
+                        format_duration(Duration::from_secs(session.idle_secs))
+                    )
+                    .dimmed()
+                );
+            }
+        }
+        OutputFormat::Json => {
+            let output = match status {
+                Some(status) => {
+                    let mut output = serde_json::to_value(status)?;
+                    output["running"] = true.into();
+                    output
+                }
+                None => serde_json::json!({ "running": false }),
+            };
+            println!("{}", serde_json::to_string_pretty(&output)?);
+        }
+    }
+
+    Ok(())
+}
+
+/// Ask the daemon to close its sessions and exit
+pub async fn daemon_stop(store: &ConfigStore, format: OutputFormat) -> Result<()> {
+    let socket = store.daemon_socket();
+    if !daemon_running(&socket).await {
+        bail!("Daemon is not running");
+    }
+
+    match daemon_request(&socket, &DaemonRequest::Stop).await? {
+        DaemonReply::Stopping => {}
+        reply => bail!("Unexpected reply from daemon: {:?}", reply),
+    }
+
+    // The socket goes away once every session is closed
+    let started = Instant::now();
+    while socket.exists() {
+        if started.elapsed() > DAEMON_WAIT {
+            bail!(
+                "Daemon did not shut down within {}",
+                format_duration(DAEMON_WAIT)
+            );
+        }
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+
+    match format {
+        OutputFormat::Human => {
+            println!("{} Daemon stopped", "✓".green());
+        }
+        OutputFormat::Json => {
+            let output = serde_json::json!({ "stopped": true });
+            println!("{}", serde_json::to_string_pretty(&output)?);
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,252 @@
+use super::sampling::{can_prompt, read_answer, PROMPT};
+use crate::mcp::{
+    ElicitAction, ElicitParams, ElicitResult, JsonRpcRequest, JsonRpcResponse, INVALID_PARAMS,
+};
+use crate::schema::{parse_schema, parse_value, FlagType, SchemaFlag};
+use owo_colors::OwoColorize;
+use serde_json::{Map, Value};
+use std::collections::HashMap;
+use std::io::Write;
+use tokio_util::sync::CancellationToken;
+
+/// Answers servers' `elicitation/create` requests, from `--elicit` values when
+/// given and otherwise by asking on the terminal
+pub struct Elicitor {
+    answers: HashMap<String, String>,
+}
+
+impl Elicitor {
+    pub fn new(answers: HashMap<String, String>) -> Self {
+        Self { answers }
+    }
+
+    /// Whether requests are answered from `--elicit` values
+    pub fn has_answers(&self) -> bool {
+        !self.answers.is_empty()
+    }
+
+    /// Answer a request, cancelling the form if `cancel` fires
+    pub async fn elicit(
+        &self,
+        server_name: &str,
+        request: JsonRpcRequest,
+        cancel: &CancellationToken,
+    ) -> JsonRpcResponse {
+        let params: ElicitParams =
+            match serde_json::from_value(request.params.unwrap_or(Value::Null)) {
+                Ok(params) => params,
+                Err(e) => {
+                    return JsonRpcResponse::failure(
+                        request.id,
+                        INVALID_PARAMS,
+                        format!("Invalid elicitation request: {}", e),
+                    )
+                }
+            };
+        let flags = match parse_schema(&params.requested_schema) {
+            Ok(flags) => flags,
+            Err(e) => {
+                return JsonRpcResponse::failure(
+                    request.id,
+                    INVALID_PARAMS,
+                    format!("Invalid requested schema: {}", e),
+                )
+            }
+        };
+
+        let result = if !self.answers.is_empty() {
+            answer_from(&flags, &self.answers)
+        } else if can_prompt() {
+            ask(server_name, &params.message, &flags, cancel).await
+        } else {
+            tracing::debug!(
+                "Declining elicitation request from '{}': no terminal to ask on",
+                server_name
+            );
+            respond(ElicitAction::Decline)
+        };
+        JsonRpcResponse::success(request.id, serde_json::to_value(result).unwrap_or_default())
+    }
+}
+
+fn respond(action: ElicitAction) -> ElicitResult {
+    ElicitResult {
+        action,
+        content: None,
+    }
+}
+
+/// Fill the form from `--elicit` values, declining if none of them are for
+/// this form, a required field is missing or a value doesn't fit its type
+fn answer_from(flags: &[SchemaFlag], answers: &HashMap<String, String>) -> ElicitResult {
+    if !flags.iter().any(|flag| answers.contains_key(&flag.name)) {
+        tracing::debug!("Declining elicitation: no --elicit values for its fields");
+        return respond(ElicitAction::Decline);
+    }
+    let mut content = Map::new();
+    for flag in flags {
+        let value = match (answers.get(&flag.name), &flag.default) {
+            (Some(answer), _) => match parse_value(answer, &flag.flag_type) {
+                Ok(value) => value,
+                Err(e) => {
+                    tracing::debug!("Declining elicitation: {}: {:#}", flag.name, e);
+                    return respond(ElicitAction::Decline);
+                }
+            },
+            (None, Some(default)) => default.clone(),
+            (None, None) if flag.required => {
+                tracing::debug!("Declining elicitation: no value for {}", flag.name);
+                return respond(ElicitAction::Decline);
+            }
+            (None, None) => continue,
+        };
+        content.insert(flag.name.clone(), value);
+    }
+    ElicitResult {
+        action: ElicitAction::Accept,
+        content: Some(content),
+    }
+}
+
+/// Show the request as a form on the terminal. Ending input cancels; saying
+/// no at the end declines.
+async fn ask(
+    server_name: &str,
+    message: &str,
+    flags: &[SchemaFlag],
+    cancel: &CancellationToken,
+) -> ElicitResult {
+    let _prompt = PROMPT.lock().await;
+    eprintln!(
+        "{} {} asks: {}",
+        "?".yellow().bold(),
+        server_name.cyan(),
+        message
+    );
+
+    let mut content = Map::new();
+    for flag in flags {
+        if !flag.description.is_empty() {
+            eprintln!("  {}", flag.description.dimmed());
+        }
+        loop {
+            eprint!("  {}{}: ", flag.name, field_hint(flag).dimmed());
+            std::io::stderr().flush().ok();
+            let Some(answer) = read_answer(cancel).await else {
+                eprintln!();
+                return respond(ElicitAction::Cancel);
+            };
+
+            if answer.is_empty() {
+                match &flag.default {
+                    Some(default) => {
+                        content.insert(flag.name.clone(), default.clone());
+                    }
+                    None if flag.required => {
+                        eprintln!("  {}", "A value is required".red());
+                        continue;
+                    }
+                    None => {}
+                }
+                break;
+            }
+            match parse_value(&answer, &flag.flag_type) {
+                Ok(value) => {
+                    content.insert(flag.name.clone(), value);
+                    break;
+                }
+                Err(e) => eprintln!("  {}", e.red()),
+            }
+        }
+    }
+
+    eprint!("Send? [Y/n] ");
+    std::io::stderr().flush().ok();
+    match read_answer(cancel).await {
+        None => {
+            eprintln!();
+            respond(ElicitAction::Cancel)
+        }
+        Some(answer) if matches!(answer.to_lowercase().as_str(), "n" | "no") => {
+            respond(ElicitAction::Decline)
+        }
+        Some(_) => ElicitResult {
+            action: ElicitAction::Accept,
+            content: Some(content),
+        },
+    }
+}
+
+/// What a field expects, e.g. ` (integer, default 3)`
+fn field_hint(flag: &SchemaFlag) -> String {
+    let kind = match &flag.flag_type {
+        FlagType::String => None,
+        FlagType::Integer => Some("integer".to_string()),
+        FlagType::Number => Some("number".to_string()),
+        FlagType::Boolean => Some("yes/no".to_string()),
+        FlagType::Array => Some("comma-separated".to_string()),
+        FlagType::Object => Some("JSON".to_string()),
+        FlagType::Enum(values) => Some(values.join("/")),
+    };
+    let default = flag
+        .default
+        .as_ref()
+        .map(|default| format!("default {}", default));
+    let parts: Vec<String> = kind.into_iter().chain(default).collect();
+    if parts.is_empty() {
+        String::new()
+    } else {
+        format!(" ({})", parts.join(", "))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_answer_from_values() {
+        let flags = parse_schema(&json!({
+            "type": "object",
+            "properties": {
+                "project": { "type": "string" },
+                "count": { "type": "integer" },
+                "dryRun": { "type": "boolean", "default": true },
+                "note": { "type": "string" }
+            },
+            "required": ["project"]
+        }))
+        .unwrap();
+        let answers = |pairs: &[(&str, &str)]| -> HashMap<String, String> {
+            pairs
+                .iter()
+                .map(|(key, value)| (key.to_string(), value.to_string()))
+                .collect()
+        };
+
+        let result = answer_from(&flags, &answers(&[("project", "relay"), ("count", "3")]));
+        assert_eq!(result.action, ElicitAction::Accept);
+        assert_eq!(
+            Value::Object(result.content.unwrap()),
+            json!({ "project": "relay", "count": 3, "dryRun": true })
+        );
+
+        let missing = answer_from(&flags, &answers(&[("count", "3")]));
+        assert_eq!(missing.action, ElicitAction::Decline);
+        assert!(missing.content.is_none());
+
+        let invalid = answer_from(&flags, &answers(&[("project", "relay"), ("count", "x")]));
+        assert_eq!(invalid.action, ElicitAction::Decline);
+
+        // Values meant for another form aren't an answer to this one
+        let optional = parse_schema(&json!({
+            "type": "object",
+            "properties": { "note": { "type": "string" } }
+        }))
+        .unwrap();
+        let unrelated = answer_from(&optional, &answers(&[("project", "relay")]));
+        assert_eq!(unrelated.action, ElicitAction::Decline);
+        assert!(unrelated.content.is_none());
+    }
+}
@@ -0,0 +1,225 @@
+use crate::cache::ToolCache;
+use crate::commands::{Elicitor, Sampler};
+use crate::config::{SamplingConfig, ServerConfig};
+use crate::logging::log_server_record;
+use crate::mcp::{
+    CancelledParams, ClientCapabilities, DefaultHandler, ElicitationCapability,
+    JsonRpcNotification, JsonRpcRequest, JsonRpcResponse, ListRootsResult, LoggingLevel,
+    MessageHandler, ProgressRouter, RequestId, Root, RootList, RootsCapability, SamplingCapability,
+};
+use async_trait::async_trait;
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use tokio::sync::broadcast;
+use tokio_util::sync::CancellationToken;
+
+/// Handles the messages a server sends on its own during a relay session
+pub struct SessionHandler {
+    server_name: String,
+    progress: ProgressRouter,
+    sampler: Option<Sampler>,
+    elicitor: Elicitor,
+    roots: RootList,
+    log_level: Option<LoggingLevel>,
+    /// Where log records go instead of being shown here
+    records: Option<broadcast::Sender<JsonRpcNotification>>,
+    /// Whether requests may be answered by asking the user
+    prompts: bool,
+    /// Server requests being answered, so the server can cancel them
+    answering: Mutex<HashMap<RequestId, CancellationToken>>,
+}
+
+impl SessionHandler {
+    pub fn new(server_name: &str) -> Self {
+        Self {
+            server_name: server_name.to_string(),
+            progress: ProgressRouter::default(),
+            sampler: None,
+            elicitor: Elicitor::new(HashMap::new()),
+            roots: RootList::default(),
+            log_level: None,
+            records: None,
+            prompts: true,
+            answering: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Answer `elicitation/create` with these values instead of asking
+    pub fn with_elicit_answers(mut self, answers: HashMap<String, String>) -> Self {
+        self.elicitor = Elicitor::new(answers);
+        self
+    }
+
+    /// Answer `roots/list` with the server's configured roots
+    pub fn with_roots(mut self, config: &ServerConfig) -> Self {
+        self.roots = RootList::new(session_roots(config));
+        self
+    }
+
+    /// Answer `sampling/createMessage` with the configured command
+    pub fn with_sampling(mut self, sampling: Option<SamplingConfig>) -> Self {
+        self.sampler = sampling.map(Sampler::new);
+        self
+    }
+
+    /// For sessions with no user to ask, such as the daemon's: elicitation
+    /// is only offered with `--elicit` values, and sampling only when
+    /// auto-approved
+    pub fn without_prompts(mut self) -> Self {
+        self.prompts = false;
+        self
+    }
+
+    /// Where progress notifications are delivered
+    pub fn progress(&self) -> ProgressRouter {
+        self.progress.clone()
+    }
+
+    /// Ask the server for log records at this level once initialized
+    pub fn with_log_level(mut self, level: Option<LoggingLevel>) -> Self {
+        self.log_level = level;
+        self
+    }
+
+    pub fn log_level(&self) -> Option<LoggingLevel> {
+        self.log_level
+    }
+
+    /// Pass the server's log records on to `records` instead of showing them
+    pub fn with_record_forwarding(
+        mut self,
+        records: broadcast::Sender<JsonRpcNotification>,
+    ) -> Self {
+        self.records = Some(records);
+        self
+    }
+
+    /// The roots `roots/list` is answered from
+    pub fn roots(&self) -> RootList {
+        self.roots.clone()
+    }
+
+    /// What to advertise in `initialize`
+    pub fn capabilities(&self) -> ClientCapabilities {
+        ClientCapabilities {
+            sampling: self.sampler().map(|_| SamplingCapability::default()),
+            roots: Some(RootsCapability { list_changed: true }),
+            elicitation: self.elicitor().map(|_| ElicitationCapability::default()),
+        }
+    }
+
+    fn sampler(&self) -> Option<&Sampler> {
+        self.sampler
+            .as_ref()
+            .filter(|sampler| self.prompts || sampler.auto_approves())
+    }
+
+    fn elicitor(&self) -> Option<&Elicitor> {
+        (self.prompts || self.elicitor.has_answers()).then_some(&self.elicitor)
+    }
+}
+
+/// The directories a server may work in: its configured roots, or else the
+/// current directory
+pub fn root_paths(config: &ServerConfig) -> Vec<PathBuf> {
+    let cwd = std::env::current_dir().unwrap_or_default();
+    if config.roots.is_empty() {
+        return vec![cwd];
+    }
+    config.roots.iter().map(|root| cwd.join(root)).collect()
+}
+
+/// The server's roots as `file://` URIs, named after their directories
+pub fn session_roots(config: &ServerConfig) -> Vec<Root> {
+    root_paths(config)
+        .into_iter()
+        .filter_map(|path| {
+            let uri = reqwest::Url::from_file_path(&path).ok()?;
+            Some(Root {
+                uri: uri.to_string(),
+                name: path
+                    .file_name()
+                    .map(|name| name.to_string_lossy().into_owned()),
+            })
+        })
+        .collect()
+}
+
+#[async_trait]
+impl MessageHandler for SessionHandler {
+    async fn on_notification(&self, notification: JsonRpcNotification) {
+        if self.progress.route(&notification) {
+            return;
+        }
+        if notification.method == "notifications/message" {
+            if let Some(records) = &self.records {
+                // Nobody may be listening, in which case the record is dropped
+                records.send(notification).ok();
+                return;
+            }
+            let record = notification
+                .params
+                .clone()
+                .and_then(|params| serde_json::from_value(params).ok());
+            if let Some(record) = record {
+                log_server_record(&self.server_name, &record);
+                return;
+            }
+        }
+        if notification.method == "notifications/cancelled" {
+            let params: Option<CancelledParams> = notification
+                .params
+                .clone()
+                .and_then(|params| serde_json::from_value(params).ok());
+            if let Some(params) = params {
+                if let Some(cancel) = self.answering.lock().unwrap().get(&params.request_id) {
+                    cancel.cancel();
+                }
+            }
+        }
+        if notification.method == "notifications/tools/list_changed" {
+            if let Ok(cache) = ToolCache::new() {
+                cache.invalidate(&self.server_name);
+            }
+        }
+        DefaultHandler.on_notification(notification).await;
+    }
+
+    async fn on_request(&self, request: JsonRpcRequest) -> JsonRpcResponse {
+        let id = request.id.clone();
+        let cancel = CancellationToken::new();
+        self.answering
+            .lock()
+            .unwrap()
+            .insert(id.clone(), cancel.clone());
+        let response = self.answer(request, &cancel).await;
+        self.answering.lock().unwrap().remove(&id);
+        response
+    }
+}
+
+impl SessionHandler {
+    async fn answer(&self, request: JsonRpcRequest, cancel: &CancellationToken) -> JsonRpcResponse {
+        match (request.method.as_str(), self.sampler(), self.elicitor()) {
+            ("sampling/createMessage", Some(sampler), _) => {
+                sampler
+                    .create_message(&self.server_name, request, cancel)
+                    .await
+            }
+            ("elicitation/create", _, Some(elicitor)) => {
+                elicitor.elicit(&self.server_name, request, cancel).await
+            }
+            ("roots/list", _, _) => {
+                let result = ListRootsResult {
+                    roots: self.roots.get(),
+                };
+                JsonRpcResponse::success(
+                    request.id,
+                    serde_json::to_value(result).unwrap_or_default(),
+                )
+            }
+            _ => DefaultHandler.on_request(request).await,
+        }
+    }
+}
@@ -0,0 +1,103 @@
+use crate::cli::{ClientFormat, OutputFormat};
+use crate::config::{parse_external_config, to_external_config, ConfigStore, ExternalFormat};
+use anyhow::{Context, Result};
+use owo_colors::OwoColorize;
+use std::path::Path;
+
+impl From<ClientFormat> for ExternalFormat {
+    fn from(format: ClientFormat) -> Self {
+        match format {
+            ClientFormat::Claude => ExternalFormat::Claude,
+            ClientFormat::Cursor => ExternalFormat::Cursor,
+            ClientFormat::Vscode => ExternalFormat::Vscode,
+        }
+    }
+}
+
+fn print_warnings(warnings: &[String]) {
+    for warning in warnings {
+        eprintln!("{} {}", "warning:".yellow().bold(), warning);
+    }
+}
+
+pub fn import_servers(
+    store: &ConfigStore,
+    path: &Path,
+    overwrite: bool,
+    format: OutputFormat,
+) -> Result<()> {
+    let contents =
+        std::fs::read_to_string(path).with_context(|| format!("Failed to read {:?}", path))?;
+    let (servers, warnings) = parse_external_config(&contents)
+        .with_context(|| format!("Failed to import {}", path.display()))?;
+    print_warnings(&warnings);
+
+    let mut config = store.load_user()?;
+    let mut imported = Vec::new();
+    let mut conflicts = Vec::new();
+
+    for (name, server) in servers {
+        if config.servers.contains_key(&name) && !overwrite {
+            conflicts.push(name);
+            continue;
+        }
+        config.servers.insert(name.clone(), server);
+        imported.push(name);
+    }
+
+    // Set the first imported server as default if there is none
+    if config.default_server.is_none() {
+        config.default_server = imported.first().cloned();
+    }
+
+    if !imported.is_empty() {
+        store.save(&config)?;
+    }
+
+    match format {
+        OutputFormat::Human => {
+            for name in &imported {
+                println!("{} Imported server: {}", "✓".green(), name.cyan());
+            }
+            for name in &conflicts {
+                println!(
+                    "{} Skipped {}: a server with that name already exists",
+                    "✗".red(),
+                    name.cyan()
+                );
+            }
+            if !conflicts.is_empty() {
+                println!(
+                    "{}",
+                    "Use --overwrite to replace existing servers.".dimmed()
+                );
+            }
+            if imported.is_empty() && conflicts.is_empty() {
+                println!("{}", "No servers found to import.".dimmed());
+            }
+        }
+        OutputFormat::Json => {
+            let output = serde_json::json!({
+                "imported": imported,
+                "conflicts": conflicts,
+                "warnings": warnings,
+            });
+            println!("{}", serde_json::to_string_pretty(&output)?);
+        }
+    }
+
+    Ok(())
+}
+
+/// Print the configured servers in another client's config format
+pub fn export_servers(store: &ConfigStore, target: ClientFormat) -> Result<()> {
+    let config = store.load()?;
+    let mut servers: Vec<_> = config.servers.iter().collect();
+    servers.sort_by_key(|(name, _)| name.as_str());
+
+    let (output, warnings) = to_external_config(target.into(), servers);
+    print_warnings(&warnings);
+    println!("{}", serde_json::to_string_pretty(&output)?);
+
+    Ok(())
+}
@@ -0,0 +1,121 @@
+use crate::cli::OutputFormat;
+use crate::commands::connect_direct;
+use crate::config::ConfigStore;
+use crate::paths::{server_file_name, state_dir};
+use anyhow::{bail, Context, Result};
+use owo_colors::OwoColorize;
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::PathBuf;
+use std::time::SystemTime;
+use tokio::signal::unix::{signal, SignalKind};
+
+/// Logs past this size are moved aside to `<server>.log.1` when a server starts
+const MAX_LOG_SIZE: u64 = 1024 * 1024;
+
+/// Where a stdio server's stderr is logged
+pub fn server_log_path(server_name: &str) -> Result<PathBuf> {
+    Ok(state_dir()?
+        .join("logs")
+        .join(server_file_name(server_name, "log")))
+}
+
+/// Open a server's log for appending, marking the start of a new run
+pub fn open_server_log(server_name: &str) -> Result<File> {
+    let path = server_log_path(server_name)?;
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)
+            .with_context(|| format!("Failed to create log directory: {:?}", parent))?;
+    }
+    if std::fs::metadata(&path).is_ok_and(|metadata| metadata.len() > MAX_LOG_SIZE) {
+        std::fs::rename(&path, path.with_extension("log.1")).ok();
+    }
+
+    // Servers may write tokens and other secrets to stderr
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .mode(0o600)
+        .open(&path)
+        .with_context(|| format!("Failed to open log {:?}", path))?;
+    writeln!(
+        file,
+        "--- started {} ---",
+        httpdate::fmt_http_date(SystemTime::now())
+    )?;
+    Ok(file)
+}
+
+/// Print the end of a stdio server's stderr log
+pub fn show_logs(server_name: &str, lines: usize, format: OutputFormat) -> Result<()> {
+    let path = server_log_path(server_name)?;
+    let contents = match std::fs::read(&path) {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => bail!(
+            "No logs for '{}' yet; they are written when relay starts it as a stdio server \
+             with `log_stderr: true`",
+            server_name
+        ),
+        Err(e) => return Err(e).with_context(|| format!("Failed to read {:?}", path)),
+    };
+    let contents = String::from_utf8_lossy(&contents);
+    let all: Vec<&str> = contents.lines().collect();
+    let tail = &all[all.len().saturating_sub(lines)..];
+
+    match format {
+        OutputFormat::Human => {
+            println!("{}", path.display().to_string().dimmed());
+            for line in tail {
+                println!("{}", line);
+            }
+        }
+        OutputFormat::Json => {
+            let output = serde_json::json!({
+                "server": server_name,
+                "path": path,
+                "lines": tail,
+            });
+            println!("{}", serde_json::to_string_pretty(&output)?);
+        }
+    }
+
+    Ok(())
+}
+
+/// Keep a session open, streaming the server's log records until Ctrl-C
+pub async fn follow_logs(
+    store: &ConfigStore,
+    server_name: &str,
+    format: OutputFormat,
+) -> Result<()> {
+    let config = store.load()?;
+    let server_config = config
+        .servers
+        .get(server_name)
+        .with_context(|| format!("Server '{}' not found", server_name))?;
+
+    // Listen before connecting: records can arrive during the handshake
+    let mut interrupt = signal(SignalKind::interrupt()).context("Failed to listen for Ctrl-C")?;
+    // Daemon sessions only pass on records sent during a request, so follow
+    // with a session of our own
+    let client = connect_direct(store, server_name, server_config).await?;
+    if matches!(format, OutputFormat::Human) {
+        let note = if client
+            .capabilities()
+            .is_some_and(|caps| caps.logging.is_some())
+        {
+            "(Ctrl-C to stop)"
+        } else {
+            "(it doesn't declare logging support; Ctrl-C to stop)"
+        };
+        eprintln!(
+            "Following logs for {} {}",
+            server_name.cyan(),
+            note.dimmed()
+        );
+    }
+
+    interrupt.recv().await;
+    client.close().await
+}
@@ -1,15 +1,35 @@
 mod auth;
+mod cache;
 mod connect;
+mod daemon;
+mod elicitation;
+mod handler;
+mod import;
+mod logs;
 mod ping;
+mod prompts;
+mod resources;
 mod run;
+mod sampling;
+mod serve;
 mod server;
 mod tools;
 mod update;
 
 pub use auth::*;
+pub use cache::*;
 pub use connect::*;
+pub use daemon::*;
+pub use elicitation::*;
+pub use handler::*;
+pub use import::*;
+pub use logs::*;
 pub use ping::*;
+pub use prompts::*;
+pub use resources::*;
 pub use run::*;
+pub use sampling::*;
+pub use serve::*;
 pub use server::*;
 pub use tools::*;
 pub use update::*;
@@ -8,7 +8,7 @@ use std::time::Instant;
 pub async fn ping_server(store: &ConfigStore, name: &str, format: OutputFormat) -> Result<()> {
     let start = Instant::now();
 
-    let mut client = connect(store, name).await?;
+    let client = connect(store, name).await?;
     let elapsed = start.elapsed();
 
     let server_info = client.server_info().cloned();
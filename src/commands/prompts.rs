@@ -0,0 +1,152 @@
+use crate::cli::OutputFormat;
+use crate::commands::{connect, print_content_item, resolve_server_name};
+use crate::config::ConfigStore;
+use crate::mcp::{PromptArgument, Role};
+use crate::schema::{parse_args, FlagType, SchemaFlag};
+use anyhow::{bail, Result};
+use owo_colors::OwoColorize;
+use serde_json::Value;
+use std::collections::HashMap;
+
+/// Convert declared prompt arguments into CLI flags (prompt arguments are always strings)
+fn prompt_flags(arguments: &[PromptArgument]) -> Vec<SchemaFlag> {
+    arguments
+        .iter()
+        .map(|arg| SchemaFlag {
+            name: arg.name.clone(),
+            description: arg.description.clone().unwrap_or_default(),
+            required: arg.required,
+            flag_type: FlagType::String,
+            default: None,
+        })
+        .collect()
+}
+
+pub async fn list_prompts(
+    store: &ConfigStore,
+    server: Option<String>,
+    format: OutputFormat,
+) -> Result<()> {
+    let config = store.load()?;
+    let server_name = resolve_server_name(&config, server)?;
+
+    let client = connect(store, &server_name).await?;
+    if client
+        .capabilities()
+        .is_none_or(|caps| caps.prompts.is_none())
+    {
+        client.close().await?;
+        bail!("Server '{}' does not support prompts", server_name);
+    }
+
+    let prompts = client.list_prompts().await?;
+    client.close().await?;
+
+    match format {
+        OutputFormat::Human => {
+            if prompts.is_empty() {
+                println!(
+                    "{}",
+                    format!("No prompts available from server '{}'", server_name).dimmed()
+                );
+                return Ok(());
+            }
+
+            println!("Prompts from {}:", server_name.cyan());
+            println!();
+
+            for prompt in &prompts {
+                println!("  {}", prompt.name.green().bold());
+                if let Some(desc) = &prompt.description {
+                    for line in textwrap::wrap(desc, 56) {
+                        println!("    {}", line.dimmed());
+                    }
+                }
+                for arg in &prompt.arguments {
+                    let flag = format!("--{}", arg.name);
+                    let required = if arg.required { " (required)" } else { "" };
+                    println!(
+                        "    {}{} {}",
+                        flag.yellow(),
+                        required.red(),
+                        arg.description.as_deref().unwrap_or("").dimmed()
+                    );
+                }
+                println!();
+            }
+
+            println!("{}", format!("Total: {} prompt(s)", prompts.len()).dimmed());
+        }
+        OutputFormat::Json => {
+            let output = serde_json::json!({
+                "server": server_name,
+                "prompts": prompts
+            });
+            println!("{}", serde_json::to_string_pretty(&output)?);
+        }
+    }
+
+    Ok(())
+}
+
+pub async fn get_prompt(
+    store: &ConfigStore,
+    server: Option<String>,
+    prompt_name: &str,
+    args: Vec<String>,
+    format: OutputFormat,
+) -> Result<()> {
+    let config = store.load()?;
+    let server_name = resolve_server_name(&config, server)?;
+
+    let client = connect(store, &server_name).await?;
+
+    // Find the prompt to get its declared arguments
+    let prompts = client.list_prompts().await?;
+    let prompt = prompts
+        .iter()
+        .find(|p| p.name == prompt_name)
+        .ok_or_else(|| {
+            anyhow::anyhow!(
+                "Prompt '{}' not found on server '{}'",
+                prompt_name,
+                server_name
+            )
+        })?;
+
+    let flags = prompt_flags(&prompt.arguments);
+    let arguments: HashMap<String, String> = parse_args(&args, &flags)?
+        .into_iter()
+        .map(|(name, value)| match value {
+            Value::String(s) => (name, s),
+            other => (name, other.to_string()),
+        })
+        .collect();
+
+    let result = client.get_prompt(prompt_name, arguments).await?;
+    client.close().await?;
+
+    match format {
+        OutputFormat::Human => {
+            if let Some(desc) = &result.description {
+                println!("{}", desc.dimmed());
+                println!();
+            }
+
+            for message in &result.messages {
+                let label = format!("[{}]", message.role);
+                match message.role {
+                    Role::User => println!("{}", label.cyan().bold()),
+                    Role::Assistant => println!("{}", label.green().bold()),
+                }
+                print_content_item(&message.content)?;
+                println!();
+            }
+        }
+        OutputFormat::Json => {
+            println!("{}", serde_json::to_string_pretty(&result)?);
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,192 @@
+use crate::cli::OutputFormat;
+use crate::commands::{connect, resolve_server_name};
+use crate::config::ConfigStore;
+use crate::mcp::{expand_uri_template, ResourceContents};
+use anyhow::{anyhow, bail, Context, Result};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use owo_colors::OwoColorize;
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+pub async fn list_resources(
+    store: &ConfigStore,
+    server: Option<String>,
+    format: OutputFormat,
+) -> Result<()> {
+    let config = store.load()?;
+    let server_name = resolve_server_name(&config, server)?;
+
+    let client = connect(store, &server_name).await?;
+    if client
+        .capabilities()
+        .is_none_or(|caps| caps.resources.is_none())
+    {
+        client.close().await?;
+        bail!("Server '{}' does not support resources", server_name);
+    }
+
+    let resources = client.list_resources().await?;
+    let templates = client.list_resource_templates().await?;
+    client.close().await?;
+
+    match format {
+        OutputFormat::Human => {
+            if resources.is_empty() && templates.is_empty() {
+                println!(
+                    "{}",
+                    format!("No resources available from server '{}'", server_name).dimmed()
+                );
+                return Ok(());
+            }
+
+            if !resources.is_empty() {
+                println!("Resources from {}:", server_name.cyan());
+                println!();
+
+                for resource in &resources {
+                    println!("  {}", resource.name.green().bold());
+                    println!("    {}", resource.uri.cyan());
+                    if let Some(desc) = &resource.description {
+                        for line in textwrap::wrap(desc, 56) {
+                            println!("    {}", line.dimmed());
+                        }
+                    }
+                    println!();
+                }
+            }
+
+            if !templates.is_empty() {
+                println!("Resource templates from {}:", server_name.cyan());
+                println!();
+
+                for template in &templates {
+                    println!("  {}", template.name.green().bold());
+                    println!("    {}", template.uri_template.cyan());
+                    if let Some(desc) = &template.description {
+                        for line in textwrap::wrap(desc, 56) {
+                            println!("    {}", line.dimmed());
+                        }
+                    }
+                    println!();
+                }
+            }
+
+            println!(
+                "{}",
+                format!(
+                    "Total: {} resource(s), {} template(s)",
+                    resources.len(),
+                    templates.len()
+                )
+                .dimmed()
+            );
+        }
+        OutputFormat::Json => {
+            let output = serde_json::json!({
+                "server": server_name,
+                "resources": resources,
+                "resourceTemplates": templates
+            });
+            println!("{}", serde_json::to_string_pretty(&output)?);
+        }
+    }
+
+    Ok(())
+}
+
+pub async fn read_resource(
+    store: &ConfigStore,
+    server: Option<String>,
+    uri: &str,
+    vars: Vec<(String, String)>,
+    output: Option<PathBuf>,
+    format: OutputFormat,
+) -> Result<()> {
+    let config = store.load()?;
+    let server_name = resolve_server_name(&config, server)?;
+
+    // Expand templated URIs like `file:///{path}` using --var values
+    let vars: HashMap<String, String> = vars.into_iter().collect();
+    let uri = if uri.contains('{') {
+        expand_uri_template(uri, &vars)
+            .map_err(|e| anyhow!("{} (use --var KEY=value to fill in the template)", e))?
+    } else {
+        uri.to_string()
+    };
+
+    let client = connect(store, &server_name).await?;
+    let result = client.read_resource(&uri).await?;
+    client.close().await?;
+
+    if let Some(path) = output {
+        let item = match result.contents.as_slice() {
+            [item] => item,
+            [] => bail!("Resource '{}' returned no contents", uri),
+            items => bail!(
+                "Resource '{}' returned {} items; --output requires exactly one",
+                uri,
+                items.len()
+            ),
+        };
+
+        let bytes = match item {
+            ResourceContents::Text { text, .. } => text.as_bytes().to_vec(),
+            ResourceContents::Blob { blob, .. } => STANDARD
+                .decode(blob)
+                .context("Failed to decode base64 blob contents")?,
+        };
+        std::fs::write(&path, &bytes)
+            .with_context(|| format!("Failed to write resource to {:?}", path))?;
+
+        match format {
+            OutputFormat::Human => {
+                println!(
+                    "{} Wrote {} to {}",
+                    "✓".green(),
+                    format!("{} bytes", bytes.len()).yellow(),
+                    path.display()
+                );
+            }
+            OutputFormat::Json => {
+                let output = serde_json::json!({
+                    "uri": uri,
+                    "output": path,
+                    "bytes": bytes.len()
+                });
+                println!("{}", serde_json::to_string_pretty(&output)?);
+            }
+        }
+
+        return Ok(());
+    }
+
+    match format {
+        OutputFormat::Human => {
+            for item in &result.contents {
+                match item {
+                    ResourceContents::Text { text, .. } => {
+                        println!("{}", text);
+                    }
+                    ResourceContents::Blob {
+                        uri,
+                        mime_type,
+                        blob,
+                    } => {
+                        println!(
+                            "{} {} {} {}",
+                            "[Blob]".magenta(),
+                            uri,
+                            mime_type.as_deref().unwrap_or("").dimmed(),
+                            format!("({} bytes base64, use --output to save)", blob.len()).dimmed()
+                        );
+                    }
+                }
+            }
+        }
+        OutputFormat::Json => {
+            println!("{}", serde_json::to_string_pretty(&result)?);
+        }
+    }
+
+    Ok(())
+}
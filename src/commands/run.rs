@@ -1,15 +1,20 @@
+use crate::cache::ToolCache;
 use crate::cli::OutputFormat;
-use crate::commands::{connect, resolve_server_name};
+use crate::commands::{connect, find_tool, resolve_server_name};
 use crate::config::ConfigStore;
-use crate::mcp::ContentItem;
+use crate::mcp::{ContentItem, ProgressParams};
 use crate::schema::{parse_args, parse_schema};
 use anyhow::{Context, Result};
 use owo_colors::OwoColorize;
-use serde_json::Value;
+use serde_json::{json, Value};
 use std::collections::HashMap;
+use std::io::IsTerminal;
+
+const PROGRESS_BAR_WIDTH: usize = 30;
 
 pub async fn run_tool(
     store: &ConfigStore,
+    cache: &ToolCache,
     server: Option<String>,
     tool_name: &str,
     input_json: Option<String>,
@@ -19,13 +24,10 @@ pub async fn run_tool(
     let config = store.load()?;
     let server_name = resolve_server_name(&config, server)?;
 
-    let mut client = connect(store, &server_name).await?;
+    let client = connect(store, &server_name).await?;
 
     // Find the tool to get its schema
-    let tools = client.list_tools().await?;
-    let tool = tools.iter().find(|t| t.name == tool_name).ok_or_else(|| {
-        anyhow::anyhow!("Tool '{}' not found on server '{}'", tool_name, server_name)
-    })?;
+    let tool = find_tool(cache, &config, &server_name, &client, tool_name).await?;
 
     // Build arguments
     let arguments: HashMap<String, Value> = if let Some(json_str) = input_json {
@@ -39,9 +41,24 @@ pub async fn run_tool(
         HashMap::new()
     };
 
-    // Call the tool
-    let result = client.call_tool(tool_name, arguments).await?;
+    // Call the tool, showing its progress and cancelling it on Ctrl-C
+    let mut progress = ProgressDisplay::new(format);
+    let interrupted = async {
+        if tokio::signal::ctrl_c().await.is_err() {
+            std::future::pending::<()>().await;
+        }
+    };
+    let result = client
+        .call_tool_with_progress(
+            tool_name,
+            arguments,
+            |update| progress.show(update),
+            interrupted,
+        )
+        .await;
+    progress.clear();
     client.close().await?;
+    let result = result?;
 
     // Render output
     match format {
@@ -51,26 +68,7 @@ pub async fn run_tool(
             }
 
             for item in &result.content {
-                match item {
-                    ContentItem::Text { text } => {
-                        println!("{}", text);
-                    }
-                    ContentItem::Image { data, mime_type } => {
-                        println!(
-                            "{} {} {}",
-                            "[Image]".magenta(),
-                            mime_type.dimmed(),
-                            format!("({} bytes)", data.len()).dimmed()
-                        );
-                    }
-                    ContentItem::Resource { resource } => {
-                        println!(
-                            "{}\n{}",
-                            "[Resource]".magenta(),
-                            serde_json::to_string_pretty(resource)?
-                        );
-                    }
-                }
+                print_content_item(item)?;
             }
 
             // Print structured content if present (MCP extension)
@@ -89,3 +87,135 @@ pub async fn run_tool(
 
     Ok(())
 }
+
+/// Shows a tool's progress reports on stderr, keeping stdout for the result:
+/// a bar redrawn in place on a terminal, or one JSON event per line
+struct ProgressDisplay {
+    format: OutputFormat,
+    terminal: bool,
+    drawn: bool,
+}
+
+impl ProgressDisplay {
+    fn new(format: OutputFormat) -> Self {
+        Self {
+            format,
+            terminal: std::io::stderr().is_terminal(),
+            drawn: false,
+        }
+    }
+
+    fn show(&mut self, update: &ProgressParams) {
+        match self.format {
+            OutputFormat::Human if self.terminal => {
+                eprint!("\r\x1b[2K{}", progress_bar(update));
+                self.drawn = true;
+            }
+            OutputFormat::Human => {}
+            OutputFormat::Json => {
+                let event = json!({
+                    "type": "progress",
+                    "progress": update.progress,
+                    "total": update.total,
+                    "message": update.message,
+                });
+                eprintln!("{}", event);
+            }
+        }
+    }
+
+    /// Erase the bar before the result is printed
+    fn clear(&mut self) {
+        if self.drawn {
+            eprint!("\r\x1b[2K");
+            self.drawn = false;
+        }
+    }
+}
+
+/// A progress bar when the total is known, otherwise just the count
+fn progress_bar(update: &ProgressParams) -> String {
+    let message = update.message.as_deref().unwrap_or_default();
+    match update.total.filter(|total| *total > 0.0) {
+        Some(total) => {
+            let fraction = (update.progress / total).clamp(0.0, 1.0);
+            let filled = (fraction * PROGRESS_BAR_WIDTH as f64).round() as usize;
+            format!(
+                "[{}{}] {:>3.0}% {}",
+                "#".repeat(filled),
+                "-".repeat(PROGRESS_BAR_WIDTH - filled),
+                fraction * 100.0,
+                message
+            )
+        }
+        None => format!("{} {}", update.progress, message),
+    }
+    .trim_end()
+    .to_string()
+}
+
+/// Render a single content item in human-readable form
+pub fn print_content_item(item: &ContentItem) -> Result<()> {
+    match item {
+        ContentItem::Text { text } => {
+            println!("{}", text);
+        }
+        ContentItem::Image { data, mime_type } => {
+            println!(
+                "{} {} {}",
+                "[Image]".magenta(),
+                mime_type.dimmed(),
+                format!("({} bytes)", data.len()).dimmed()
+            );
+        }
+        ContentItem::Audio { data, mime_type } => {
+            println!(
+                "{} {} {}",
+                "[Audio]".magenta(),
+                mime_type.dimmed(),
+                format!("({} bytes)", data.len()).dimmed()
+            );
+        }
+        ContentItem::Resource { resource } => {
+            println!(
+                "{}\n{}",
+                "[Resource]".magenta(),
+                serde_json::to_string_pretty(resource)?
+            );
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn update(progress: f64, total: Option<f64>, message: Option<&str>) -> ProgressParams {
+        ProgressParams {
+            progress_token: json!("t"),
+            progress,
+            total,
+            message: message.map(str::to_string),
+        }
+    }
+
+    #[test]
+    fn test_progress_bar() {
+        assert_eq!(
+            progress_bar(&update(3.0, Some(10.0), Some("Exporting issues"))),
+            format!(
+                "[{}{}]  30% Exporting issues",
+                "#".repeat(9),
+                "-".repeat(21)
+            )
+        );
+        assert_eq!(
+            progress_bar(&update(12.0, Some(10.0), None)),
+            format!("[{}] 100%", "#".repeat(30))
+        );
+        assert_eq!(progress_bar(&update(42.0, None, Some("rows"))), "42 rows");
+        assert_eq!(progress_bar(&update(5.0, Some(0.0), None)), "5");
+    }
+}
@@ -0,0 +1,252 @@
+use crate::config::{stdio_argv, SamplingConfig};
+use crate::mcp::{
+    ContentItem, CreateMessageParams, CreateMessageResult, JsonRpcRequest, JsonRpcResponse,
+    INTERNAL_ERROR, INVALID_PARAMS, USER_REJECTED,
+};
+use crate::paths::state_dir;
+use anyhow::{bail, Context, Result};
+use owo_colors::OwoColorize;
+use serde_json::{json, Value};
+use std::fs::OpenOptions;
+use std::io::{IsTerminal, Write};
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::PathBuf;
+use std::process::Stdio;
+use std::sync::LazyLock;
+use std::time::SystemTime;
+use tokio::io::AsyncWriteExt;
+use tokio::process::Command;
+use tokio::sync::{mpsc, Mutex};
+use tokio_util::sync::CancellationToken;
+
+/// One prompt at a time, however many servers ask at once
+pub(super) static PROMPT: Mutex<()> = Mutex::const_new(());
+
+/// Shared so a line read for a cancelled prompt answers the next one
+static STDIN: LazyLock<Mutex<StdinLines>> = LazyLock::new(|| Mutex::new(StdinLines::spawn()));
+
+/// Reads stdin a line at a time on a thread of its own, only when asked.
+/// A read blocked on a prompt the server cancelled then can't hold up the
+/// runtime's shutdown, as one on tokio's blocking pool would.
+struct StdinLines {
+    wanted: std::sync::mpsc::Sender<()>,
+    lines: mpsc::UnboundedReceiver<Option<String>>,
+    waiting: bool,
+}
+
+impl StdinLines {
+    fn spawn() -> Self {
+        let (wanted, requests) = std::sync::mpsc::channel();
+        let (sender, lines) = mpsc::unbounded_channel();
+        std::thread::spawn(move || {
+            for () in requests {
+                let mut line = String::new();
+                let read = std::io::stdin().read_line(&mut line);
+                let line = matches!(read, Ok(read) if read > 0).then_some(line);
+                if sender.send(line).is_err() {
+                    break;
+                }
+            }
+        });
+        Self {
+            wanted,
+            lines,
+            waiting: false,
+        }
+    }
+
+    /// The next line, or None at end of input. Cancel safe: a line still
+    /// being read is returned by the next call.
+    async fn next(&mut self) -> Option<String> {
+        if !self.waiting {
+            self.wanted.send(()).ok()?;
+            self.waiting = true;
+        }
+        let line = self.lines.recv().await.flatten();
+        self.waiting = false;
+        line
+    }
+}
+
+/// Whether there is a terminal to ask the user on
+pub fn can_prompt() -> bool {
+    std::io::stdin().is_terminal() && std::io::stderr().is_terminal()
+}
+
+/// Where every sampling request and its outcome is recorded
+pub fn sampling_log_path() -> Result<PathBuf> {
+    Ok(state_dir()?.join("sampling.log"))
+}
+
+/// Answers servers' `sampling/createMessage` requests by running the
+/// configured command, once the user has approved each request
+pub struct Sampler {
+    config: SamplingConfig,
+}
+
+impl Sampler {
+    pub fn new(config: SamplingConfig) -> Self {
+        Self { config }
+    }
+
+    /// Whether requests are run without asking
+    pub fn auto_approves(&self) -> bool {
+        self.config.auto_approve
+    }
+
+    /// Answer a request, giving up on the prompt if `cancel` fires
+    pub async fn create_message(
+        &self,
+        server_name: &str,
+        request: JsonRpcRequest,
+        cancel: &CancellationToken,
+    ) -> JsonRpcResponse {
+        let raw = request.params.unwrap_or(Value::Null);
+        let params: CreateMessageParams = match serde_json::from_value(raw.clone()) {
+            Ok(params) => params,
+            Err(e) => {
+                return JsonRpcResponse::failure(
+                    request.id,
+                    INVALID_PARAMS,
+                    format!("Invalid sampling request: {}", e),
+                )
+            }
+        };
+
+        let approved = self.config.auto_approve || approve(server_name, &params, cancel).await;
+        let response = if !approved {
+            JsonRpcResponse::failure(request.id, USER_REJECTED, "User rejected sampling request")
+        } else {
+            match self.run_command(server_name, &raw).await {
+                Ok(result) => JsonRpcResponse::success(request.id, result),
+                Err(e) => JsonRpcResponse::failure(
+                    request.id,
+                    INTERNAL_ERROR,
+                    format!("Sampling command failed: {:#}", e),
+                ),
+            }
+        };
+
+        if let Err(e) = log_request(server_name, &raw, approved, &response) {
+            tracing::debug!("Failed to log sampling request: {:#}", e);
+        }
+        response
+    }
+
+    /// Pipe the request's params through the sampling command
+    async fn run_command(&self, server_name: &str, params: &Value) -> Result<Value> {
+        let argv = stdio_argv(&self.config.command, &[])?;
+        let mut child = Command::new(&argv[0])
+            .args(&argv[1..])
+            .env("RELAY_SERVER", server_name)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true)
+            .spawn()
+            .with_context(|| format!("Failed to run {}", argv[0]))?;
+
+        let mut stdin = child.stdin.take().context("No stdin")?;
+        stdin.write_all(&serde_json::to_vec(params)?).await?;
+        drop(stdin);
+
+        let output = child.wait_with_output().await?;
+        if !output.status.success() {
+            bail!(
+                "{} ({})",
+                output.status,
+                String::from_utf8_lossy(&output.stderr).trim()
+            );
+        }
+        let result: CreateMessageResult = serde_json::from_slice(&output.stdout)
+            .context("Expected a CreateMessageResult on stdout")?;
+        Ok(serde_json::to_value(result)?)
+    }
+}
+
+/// Show the request and ask whether to run it. Without a terminal to ask
+/// on, the request is declined.
+async fn approve(
+    server_name: &str,
+    params: &CreateMessageParams,
+    cancel: &CancellationToken,
+) -> bool {
+    if !can_prompt() {
+        tracing::debug!(
+            "Declining sampling request from '{}': no terminal to ask on",
+            server_name
+        );
+        return false;
+    }
+
+    let _prompt = PROMPT.lock().await;
+    eprintln!(
+        "{} {} wants to sample a model {}",
+        "?".yellow().bold(),
+        server_name.cyan(),
+        format!("(up to {} tokens)", params.max_tokens).dimmed()
+    );
+    if let Some(system) = &params.system_prompt {
+        eprintln!("  {} {}", "system:".dimmed(), system);
+    }
+    for message in &params.messages {
+        let text = match &message.content {
+            ContentItem::Text { text } => text.clone(),
+            ContentItem::Image { mime_type, .. } | ContentItem::Audio { mime_type, .. } => {
+                format!("[{}]", mime_type)
+            }
+            ContentItem::Resource { .. } => "[resource]".to_string(),
+        };
+        eprintln!("  {} {}", format!("{}:", message.role).dimmed(), text);
+    }
+    eprint!("Allow? [y/N] ");
+
+    matches!(
+        read_answer(cancel).await,
+        Some(answer) if matches!(answer.to_lowercase().as_str(), "y" | "yes")
+    )
+}
+
+/// Read one trimmed line from stdin, or None at end of input or once the
+/// server cancels its request
+pub(super) async fn read_answer(cancel: &CancellationToken) -> Option<String> {
+    let mut stdin = STDIN.lock().await;
+    let answer = tokio::select! {
+        answer = stdin.next() => answer,
+        _ = cancel.cancelled() => {
+            eprintln!("{}", " (cancelled by the server)".dimmed());
+            None
+        }
+    };
+    answer.map(|answer| answer.trim().to_string())
+}
+
+/// Append one request and its outcome to the sampling log
+fn log_request(
+    server_name: &str,
+    params: &Value,
+    approved: bool,
+    response: &JsonRpcResponse,
+) -> Result<()> {
+    let path = sampling_log_path()?;
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    let entry = json!({
+        "time": httpdate::fmt_http_date(SystemTime::now()),
+        "server": server_name,
+        "approved": approved,
+        "request": params,
+        "result": response.result,
+        "error": response.error,
+    });
+    // The log holds whole conversations, so only the user may read it
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .mode(0o600)
+        .open(&path)
+        .with_context(|| format!("Failed to open {:?}", path))?;
+    writeln!(file, "{}", entry)?;
+    Ok(())
+}
@@ -0,0 +1,19 @@
+use crate::config::ConfigStore;
+use crate::server::{serve_http, serve_stdio, Aggregator};
+use anyhow::Result;
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+/// Serve every configured server as one MCP server, over stdio by default or
+/// streamable HTTP when an address is given
+pub async fn serve(store: &ConfigStore, http: Option<SocketAddr>) -> Result<()> {
+    let aggregator = Arc::new(Aggregator::new(store.clone())?);
+
+    let result = match http {
+        Some(addr) => serve_http(aggregator.clone(), addr).await,
+        None => serve_stdio(aggregator.clone()).await,
+    };
+
+    aggregator.close().await;
+    result
+}
@@ -1,34 +1,91 @@
-use crate::cli::{OutputFormat, Transport};
-use crate::config::{ConfigStore, ServerConfig, TransportConfig};
-use anyhow::{bail, Result};
+use crate::cli::{OutputFormat, Transport, TransportArgs};
+use crate::config::{
+    display_argv, stdio_argv, Config, ConfigStore, ServerConfig, TransportConfig,
+    PROJECT_CONFIG_FILE,
+};
+use crate::mcp::transport::is_websocket_url;
+use anyhow::{bail, Context, Result};
 use owo_colors::OwoColorize;
+use std::io::{IsTerminal, Write};
+use std::path::Path;
 
 pub fn add_server(
     store: &ConfigStore,
     name: String,
     transport: Transport,
-    cmd: Option<String>,
-    url: Option<String>,
+    args: TransportArgs,
     env: Vec<(String, String)>,
     format: OutputFormat,
 ) -> Result<()> {
-    let mut config = store.load()?;
+    let mut config = store.load_user()?;
+    let TransportArgs {
+        stdio,
+        http,
+        socket,
+    } = args;
 
     let transport_config = match transport {
         Transport::Stdio => {
-            let command =
-                cmd.ok_or_else(|| anyhow::anyhow!("--cmd required for stdio transport"))?;
-            TransportConfig::Stdio { command }
+            let command = stdio
+                .cmd
+                .ok_or_else(|| anyhow::anyhow!("--cmd required for stdio transport"))?;
+            // Catch quoting mistakes now rather than on first use
+            stdio_argv(&command, &stdio.args)?;
+            let cwd = match stdio.cwd {
+                Some(cwd) => Some(std::path::absolute(&cwd)?),
+                None => None,
+            };
+            TransportConfig::Stdio {
+                command,
+                args: stdio.args,
+                cwd,
+                inherit_env: !stdio.clear_env,
+                log_stderr: stdio.log_stderr,
+            }
+        }
+        Transport::Http | Transport::Sse => {
+            let url = http
+                .url
+                .ok_or_else(|| anyhow::anyhow!("--url required for http and sse transports"))?;
+            let headers = http.headers.into_iter().collect();
+            match transport {
+                Transport::Sse if is_websocket_url(&url) => {
+                    bail!("The sse transport needs an http(s) URL; use --transport http for WebSockets")
+                }
+                Transport::Sse => TransportConfig::Sse { url, headers },
+                _ => TransportConfig::Http {
+                    url,
+                    headers,
+                    listen: http.listen,
+                },
+            }
+        }
+        Transport::Unix => {
+            let path = socket
+                .socket
+                .ok_or_else(|| anyhow::anyhow!("--socket required for unix transport"))?;
+            TransportConfig::Unix {
+                path: std::path::absolute(&path)?,
+            }
         }
-        Transport::Http => {
-            let url = url.ok_or_else(|| anyhow::anyhow!("--url required for http transport"))?;
-            TransportConfig::Http { url }
+        Transport::Tcp => {
+            let address = socket
+                .address
+                .ok_or_else(|| anyhow::anyhow!("--address required for tcp transport"))?;
+            // Catch a missing port now rather than on first use
+            let port = address.rsplit_once(':').map(|(_, port)| port);
+            if port.and_then(|port| port.parse::<u16>().ok()).is_none() {
+                bail!("Invalid address '{}': expected host:port", address);
+            }
+            TransportConfig::Tcp { address }
         }
     };
 
     let server_config = ServerConfig {
         transport: transport_config,
         env: env.into_iter().collect(),
+        connection: Default::default(),
+        roots: Vec::new(),
     };
 
     if config.servers.contains_key(&name) {
@@ -56,8 +113,36 @@ pub fn add_server(
     Ok(())
 }
 
+/// Shorten a path under the home directory to `~/...` for display
+fn display_path(path: &Path) -> String {
+    match std::env::var("HOME") {
+        Ok(home) => match path.strip_prefix(&home) {
+            Ok(rest) => format!("~/{}", rest.display()),
+            Err(_) => path.display().to_string(),
+        },
+        Err(_) => path.display().to_string(),
+    }
+}
+
+/// The transport's name and what it connects to, e.g. the command line
+fn describe_transport(server: &ServerConfig) -> (&'static str, String) {
+    match &server.transport {
+        TransportConfig::Stdio { command, args, .. } => (
+            "stdio",
+            stdio_argv(command, args)
+                .map(|argv| display_argv(&argv))
+                .unwrap_or_else(|_| command.clone()),
+        ),
+        TransportConfig::Http { url, .. } if is_websocket_url(url) => ("websocket", url.clone()),
+        TransportConfig::Http { url, .. } => ("http", url.clone()),
+        TransportConfig::Sse { url, .. } => ("sse", url.clone()),
+        TransportConfig::Unix { path } => ("unix", path.display().to_string()),
+        TransportConfig::Tcp { address } => ("tcp", address.clone()),
+    }
+}
+
 pub fn list_servers(store: &ConfigStore, format: OutputFormat) -> Result<()> {
-    let config = store.load()?;
+    let (config, sources) = store.load_with_sources()?;
 
     match format {
         OutputFormat::Human => {
@@ -78,10 +163,7 @@ pub fn list_servers(store: &ConfigStore, format: OutputFormat) -> Result<()> {
             println!("{}", "─".repeat(60).dimmed());
 
             for (name, server) in &config.servers {
-                let (transport, target) = match &server.transport {
-                    TransportConfig::Stdio { command } => ("stdio", command.as_str()),
-                    TransportConfig::Http { url } => ("http", url.as_str()),
-                };
+                let (transport, target) = describe_transport(server);
                 let is_default = config.default_server.as_ref() == Some(name);
                 let name_display = if is_default {
                     format!("{} {}", name.cyan(), "(default)".dimmed())
@@ -89,11 +171,21 @@ pub fn list_servers(store: &ConfigStore, format: OutputFormat) -> Result<()> {
                     name.cyan().to_string()
                 };
                 println!("{:<20} {:<10} {}", name_display, transport.yellow(), target);
+                if let Some(source) = sources.get(name) {
+                    println!("{:<31} {}", "", display_path(source).dimmed());
+                }
             }
         }
         OutputFormat::Json => {
-            let output = serde_json::to_string_pretty(&config.servers)?;
-            println!("{}", output);
+            let mut servers = serde_json::Map::new();
+            for (name, server) in &config.servers {
+                let mut entry = serde_json::to_value(server)?;
+                if let (Some(entry), Some(source)) = (entry.as_object_mut(), sources.get(name)) {
+                    entry.insert("source".to_string(), source.display().to_string().into());
+                }
+                servers.insert(name.clone(), entry);
+            }
+            println!("{}", serde_json::to_string_pretty(&servers)?);
         }
     }
 
@@ -101,9 +193,18 @@ pub fn list_servers(store: &ConfigStore, format: OutputFormat) -> Result<()> {
 }
 
 pub fn remove_server(store: &ConfigStore, name: String, format: OutputFormat) -> Result<()> {
-    let mut config = store.load()?;
+    let mut config = store.load_user()?;
 
     if !config.servers.contains_key(&name) {
+        // Project servers can't be removed from here
+        let (_, sources) = store.load_with_sources()?;
+        if let Some(source) = sources.get(&name) {
+            bail!(
+                "Server '{}' is defined in {}; edit that file to remove it",
+                name,
+                source.display()
+            );
+        }
         bail!("Server '{}' not found", name);
     }
 
@@ -128,3 +229,70 @@ pub fn remove_server(store: &ConfigStore, name: String, format: OutputFormat) ->
 
     Ok(())
 }
+
+/// Before a project config's servers are used, show what they would run and
+/// ask to trust the file, again whenever it changes. Without a terminal to
+/// ask on, the file is ignored until `relay trust` is run.
+pub fn confirm_project_trust(store: &ConfigStore) -> Result<()> {
+    let Some(path) = store.project_path() else {
+        return Ok(());
+    };
+    if store.is_project_trusted()? {
+        return Ok(());
+    }
+    let shown = display_path(path);
+    if !std::io::stdin().is_terminal() || !std::io::stderr().is_terminal() {
+        eprintln!(
+            "{} Ignoring {}: it isn't trusted yet. Run `relay trust` to use its servers.",
+            "!".yellow().bold(),
+            shown
+        );
+        return Ok(());
+    }
+
+    let contents = std::fs::read_to_string(path)?;
+    let project: Config = serde_yaml::from_str(&contents)
+        .with_context(|| format!("Failed to parse config YAML in {:?}", path))?;
+    eprintln!(
+        "{} {} defines servers that relay would start or connect to:",
+        "?".yellow().bold(),
+        shown
+    );
+    for (name, server) in &project.servers {
+        let (transport, target) = describe_transport(server);
+        eprintln!("  {} {} {}", name.cyan(), transport.yellow(), target);
+    }
+    eprint!("Trust this file? [y/N] ");
+    std::io::stderr().flush().ok();
+
+    let mut answer = String::new();
+    std::io::stdin().read_line(&mut answer)?;
+    if matches!(answer.trim().to_lowercase().as_str(), "y" | "yes") {
+        store.trust_project()?;
+    } else {
+        eprintln!("{}", format!("Ignoring {}", shown).dimmed());
+    }
+    Ok(())
+}
+
+/// Trust the project config's current contents without asking
+pub fn trust_project(store: &ConfigStore, format: OutputFormat) -> Result<()> {
+    let Some(path) = store.project_path() else {
+        bail!(
+            "No {} found in this directory or its parents",
+            PROJECT_CONFIG_FILE
+        );
+    };
+    store.trust_project()?;
+
+    match format {
+        OutputFormat::Human => {
+            println!("{} Trusted {}", "✓".green(), display_path(path));
+        }
+        OutputFormat::Json => {
+            let output = serde_json::json!({ "trusted": path });
+            println!("{}", serde_json::to_string_pretty(&output)?);
+        }
+    }
+    Ok(())
+}
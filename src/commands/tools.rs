@@ -1,19 +1,69 @@
+use crate::cache::ToolCache;
 use crate::cli::OutputFormat;
 use crate::commands::{connect, resolve_server_name};
-use crate::config::ConfigStore;
-use anyhow::Result;
+use crate::config::{Config, ConfigStore};
+use crate::mcp::{McpClient, Tool};
+use anyhow::{Context, Result};
 use owo_colors::OwoColorize;
 
+/// List a server's tools, from the cache while it is valid for the
+/// connected server's config and version
+pub async fn fetch_tools(
+    cache: &ToolCache,
+    config: &Config,
+    server_name: &str,
+    client: &McpClient,
+) -> Result<Vec<Tool>> {
+    let server_config = config
+        .servers
+        .get(server_name)
+        .with_context(|| format!("Server '{}' not found", server_name))?;
+    let version = client
+        .server_info()
+        .and_then(|info| info.version.as_deref());
+    if let Some(tools) = cache.get(server_name, server_config, version) {
+        return Ok(tools);
+    }
+
+    let tools = client.list_tools().await?;
+    if let Err(e) = cache.put(server_name, server_config, version, &tools) {
+        tracing::debug!("Failed to cache tools for '{}': {:#}", server_name, e);
+    }
+    Ok(tools)
+}
+
+/// Look up one tool, refetching the list if a cached one doesn't have it yet
+pub async fn find_tool(
+    cache: &ToolCache,
+    config: &Config,
+    server_name: &str,
+    client: &McpClient,
+    tool_name: &str,
+) -> Result<Tool> {
+    let tools = fetch_tools(cache, config, server_name, client).await?;
+    if let Some(tool) = tools.into_iter().find(|t| t.name == tool_name) {
+        return Ok(tool);
+    }
+
+    let refresh = cache.clone().with_refresh(true);
+    let tools = fetch_tools(&refresh, config, server_name, client).await?;
+    tools
+        .into_iter()
+        .find(|t| t.name == tool_name)
+        .with_context(|| format!("Tool '{}' not found on server '{}'", tool_name, server_name))
+}
+
 pub async fn list_tools(
     store: &ConfigStore,
+    cache: &ToolCache,
     server: Option<String>,
     format: OutputFormat,
 ) -> Result<()> {
     let config = store.load()?;
     let server_name = resolve_server_name(&config, server)?;
 
-    let mut client = connect(store, &server_name).await?;
-    let tools = client.list_tools().await?;
+    let client = connect(store, &server_name).await?;
+    let tools = fetch_tools(cache, &config, &server_name, &client).await?;
     client.close().await?;
 
     match format {
@@ -55,6 +105,7 @@ pub async fn list_tools(
 
 pub async fn describe_tool(
     store: &ConfigStore,
+    cache: &ToolCache,
     server: Option<String>,
     tool_name: &str,
     format: OutputFormat,
@@ -62,13 +113,10 @@ pub async fn describe_tool(
     let config = store.load()?;
     let server_name = resolve_server_name(&config, server)?;
 
-    let mut client = connect(store, &server_name).await?;
-    let tools = client.list_tools().await?;
+    let client = connect(store, &server_name).await?;
+    let tool = find_tool(cache, &config, &server_name, &client, tool_name).await;
     client.close().await?;
-
-    let tool = tools.iter().find(|t| t.name == tool_name).ok_or_else(|| {
-        anyhow::anyhow!("Tool '{}' not found on server '{}'", tool_name, server_name)
-    })?;
+    let tool = tool?;
 
     match format {
         OutputFormat::Human => {
@@ -91,7 +139,7 @@ pub async fn describe_tool(
             }
         }
         OutputFormat::Json => {
-            println!("{}", serde_json::to_string_pretty(tool)?);
+            println!("{}", serde_json::to_string_pretty(&tool)?);
         }
     }
 
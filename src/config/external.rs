@@ -0,0 +1,487 @@
+use super::{stdio_argv, ServerConfig, TransportConfig};
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+use std::collections::{BTreeMap, HashMap};
+use std::path::PathBuf;
+use std::sync::LazyLock;
+
+static PLACEHOLDER_REGEX: LazyLock<regex::Regex> =
+    LazyLock::new(|| regex::Regex::new(r"\$\{[^}]*\}").unwrap());
+
+/// MCP client config files relay can import from and export to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExternalFormat {
+    /// `claude_desktop_config.json`: `{"mcpServers": {...}}`
+    Claude,
+    /// `.cursor/mcp.json`: `{"mcpServers": {...}}`, also allowing `url` servers
+    Cursor,
+    /// `.vscode/mcp.json`: `{"servers": {...}}` with an explicit `type`
+    Vscode,
+}
+
+impl ExternalFormat {
+    fn client_name(self) -> &'static str {
+        match self {
+            ExternalFormat::Claude => "Claude Desktop",
+            ExternalFormat::Cursor => "Cursor",
+            ExternalFormat::Vscode => "VS Code",
+        }
+    }
+}
+
+/// A server entry as written by Claude Desktop, Cursor and VS Code
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct ExternalServer {
+    #[serde(rename = "type", default, skip_serializing_if = "Option::is_none")]
+    kind: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    command: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    args: Vec<String>,
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    env: HashMap<String, String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    cwd: Option<PathBuf>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    url: Option<String>,
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    headers: HashMap<String, String>,
+}
+
+/// Parse an external MCP config file into relay server configs.
+///
+/// The format is detected from the top-level key; VS Code's `// comments`
+/// are allowed. Servers that can't be represented are returned as warnings.
+pub fn parse_external_config(
+    contents: &str,
+) -> Result<(BTreeMap<String, ServerConfig>, Vec<String>)> {
+    let root: Value = serde_json::from_str(&strip_json_comments(contents))
+        .context("Failed to parse MCP config JSON")?;
+
+    // VS Code user settings nest the servers under "mcp"
+    let servers = root
+        .get("mcpServers")
+        .or_else(|| root.get("servers"))
+        .or_else(|| root.get("mcp").and_then(|mcp| mcp.get("servers")))
+        .context("No `mcpServers` or `servers` object found")?;
+    let servers: BTreeMap<String, Value> =
+        serde_json::from_value(servers.clone()).context("Server entries must be objects")?;
+
+    let mut configs = BTreeMap::new();
+    let mut warnings = Vec::new();
+    for (name, value) in servers {
+        let server: ExternalServer = match serde_json::from_value(value) {
+            Ok(server) => server,
+            Err(e) => {
+                warnings.push(format!("Skipping '{}': {}", name, e));
+                continue;
+            }
+        };
+        for (key, placeholder, expands_env) in server.placeholders() {
+            if !(expands_env && placeholder.starts_with("${env:")) {
+                warnings.push(format!(
+                    "'{}': {} uses {}, which relay doesn't expand",
+                    name, key, placeholder
+                ));
+            }
+        }
+        match server.into_server_config() {
+            Ok(config) => {
+                configs.insert(name, config);
+            }
+            Err(e) => warnings.push(format!("Skipping '{}': {}", name, e)),
+        }
+    }
+
+    Ok((configs, warnings))
+}
+
+/// Render relay servers in an external format. Servers the format can't
+/// express are left out and returned as warnings.
+pub fn to_external_config<'a>(
+    format: ExternalFormat,
+    servers: impl IntoIterator<Item = (&'a String, &'a ServerConfig)>,
+) -> (Value, Vec<String>) {
+    let mut entries = Map::new();
+    let mut warnings = Vec::new();
+
+    for (name, config) in servers {
+        let mut server = ExternalServer {
+            env: config.env.clone(),
+            ..Default::default()
+        };
+        match &config.transport {
+            TransportConfig::Stdio {
+                command,
+                args,
+                cwd,
+                inherit_env,
+                ..
+            } => {
+                let mut argv = match stdio_argv(command, args) {
+                    Ok(argv) => argv.into_iter(),
+                    Err(e) => {
+                        warnings.push(format!("Skipping '{}': {}", name, e));
+                        continue;
+                    }
+                };
+                if !inherit_env {
+                    warnings.push(format!(
+                        "'{}' clears its environment, which this format can't express",
+                        name
+                    ));
+                }
+                server.command = argv.next();
+                server.args = argv.collect();
+                server.cwd = cwd.clone();
+                if format == ExternalFormat::Vscode {
+                    server.kind = Some("stdio".to_string());
+                }
+            }
+            TransportConfig::Http { url, headers, .. } | TransportConfig::Sse { url, headers } => {
+                let is_sse = matches!(config.transport, TransportConfig::Sse { .. });
+                match format {
+                    ExternalFormat::Claude => {
+                        warnings.push(format!(
+                            "Skipping '{}': Claude Desktop config only supports stdio servers",
+                            name
+                        ));
+                        continue;
+                    }
+                    ExternalFormat::Cursor => {}
+                    ExternalFormat::Vscode => {
+                        let kind = if is_sse { "sse" } else { "http" };
+                        server.kind = Some(kind.to_string());
+                    }
+                }
+                server.url = Some(url.clone());
+                server.headers = headers.clone();
+            }
+            TransportConfig::Unix { .. } | TransportConfig::Tcp { .. } => {
+                warnings.push(format!(
+                    "Skipping '{}': socket servers can't be expressed in this format",
+                    name
+                ));
+                continue;
+            }
+        }
+        if format != ExternalFormat::Vscode {
+            for (key, placeholder, _) in server.placeholders() {
+                if placeholder.starts_with("${env:") {
+                    warnings.push(format!(
+                        "'{}': {} uses {}, which {} doesn't expand",
+                        name,
+                        key,
+                        placeholder,
+                        format.client_name()
+                    ));
+                }
+            }
+        }
+        entries.insert(
+            name.clone(),
+            serde_json::to_value(server).expect("server entry serializes"),
+        );
+    }
+
+    let root = match format {
+        ExternalFormat::Claude | ExternalFormat::Cursor => json!({ "mcpServers": entries }),
+        ExternalFormat::Vscode => json!({ "servers": entries }),
+    };
+    (root, warnings)
+}
+
+impl ExternalServer {
+    /// Each `${...}` placeholder in the entry as (field, placeholder, whether
+    /// relay expands `${env:VAR}` in that field), in a stable order
+    fn placeholders(&self) -> Vec<(String, String, bool)> {
+        let mut fields: Vec<(String, &str, bool)> = Vec::new();
+        fields.extend(
+            self.command
+                .iter()
+                .map(|c| ("command".to_string(), c.as_str(), false)),
+        );
+        fields.extend(
+            self.args
+                .iter()
+                .map(|arg| ("args".to_string(), arg.as_str(), false)),
+        );
+        fields.extend(
+            self.cwd
+                .iter()
+                .filter_map(|cwd| cwd.to_str())
+                .map(|cwd| ("cwd".to_string(), cwd, false)),
+        );
+        fields.extend(
+            self.url
+                .iter()
+                .map(|url| ("url".to_string(), url.as_str(), false)),
+        );
+        let mut env: Vec<_> = self.env.iter().collect();
+        env.sort();
+        fields.extend(
+            env.into_iter()
+                .map(|(key, value)| (format!("env {}", key), value.as_str(), true)),
+        );
+        let mut headers: Vec<_> = self.headers.iter().collect();
+        headers.sort();
+        fields.extend(
+            headers
+                .into_iter()
+                .map(|(key, value)| (format!("header {}", key), value.as_str(), true)),
+        );
+
+        fields
+            .into_iter()
+            .flat_map(|(field, value, expands_env)| {
+                PLACEHOLDER_REGEX
+                    .find_iter(value)
+                    .map(move |found| (field.clone(), found.as_str().to_string(), expands_env))
+                    .collect::<Vec<_>>()
+            })
+            .collect()
+    }
+
+    fn into_server_config(self) -> Result<ServerConfig> {
+        match self.kind.as_deref() {
+            None | Some("stdio" | "http" | "sse" | "streamable-http") => {}
+            Some(kind) => bail!("unsupported server type '{}'", kind),
+        }
+
+        let transport = match (self.command, self.url) {
+            (None, Some(url)) if self.kind.as_deref() == Some("sse") => TransportConfig::Sse {
+                url,
+                headers: self.headers,
+            },
+            (Some(command), _) => TransportConfig::Stdio {
+                command,
+                args: self.args,
+                cwd: self.cwd,
+                inherit_env: true,
+                log_stderr: false,
+            },
+            (None, Some(url)) => TransportConfig::Http {
+                url,
+                headers: self.headers,
+                listen: false,
+            },
+            (None, None) => bail!("expected a `command` or a `url`"),
+        };
+
+        Ok(ServerConfig {
+            transport,
+            env: self.env,
+            connection: Default::default(),
+            roots: Vec::new(),
+        })
+    }
+}
+
+/// Remove `//` and `/* */` comments outside of strings (JSONC, as used by VS Code)
+fn strip_json_comments(input: &str) -> String {
+    let mut output = String::with_capacity(input.len());
+    let mut chars = input.chars().peekable();
+    let mut in_string = false;
+
+    while let Some(c) = chars.next() {
+        if in_string {
+            output.push(c);
+            match c {
+                '\\' => output.extend(chars.next()),
+                '"' => in_string = false,
+                _ => {}
+            }
+            continue;
+        }
+
+        match (c, chars.peek()) {
+            ('"', _) => {
+                in_string = true;
+                output.push(c);
+            }
+            ('/', Some('/')) => {
+                while chars.peek().is_some_and(|&c| c != '\n') {
+                    chars.next();
+                }
+            }
+            ('/', Some('*')) => {
+                chars.next();
+                let mut prev = '\0';
+                for c in chars.by_ref() {
+                    if prev == '*' && c == '/' {
+                        break;
+                    }
+                    prev = c;
+                }
+            }
+            _ => output.push(c),
+        }
+    }
+
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_claude_desktop_config() {
+        let contents = r#"{
+            "mcpServers": {
+                "filesystem": {
+                    "command": "npx",
+                    "args": ["-y", "@modelcontextprotocol/server-filesystem", "/Users/me/My Files"],
+                    "env": {"DEBUG": "1"}
+                }
+            }
+        }"#;
+        let (servers, warnings) = parse_external_config(contents).unwrap();
+        assert!(warnings.is_empty());
+
+        let server = &servers["filesystem"];
+        let TransportConfig::Stdio { command, args, .. } = &server.transport else {
+            panic!("expected stdio transport");
+        };
+        assert_eq!(command, "npx");
+        assert_eq!(
+            args,
+            &[
+                "-y",
+                "@modelcontextprotocol/server-filesystem",
+                "/Users/me/My Files"
+            ]
+        );
+        assert_eq!(server.env["DEBUG"], "1");
+    }
+
+    #[test]
+    fn test_parse_vscode_config_with_comments() {
+        let contents = r#"{
+            // Remote docs server
+            "servers": {
+                "docs": {"type": "http", "url": "https://example.com/mcp", "headers": {"X-API-Key": "${input:key}"}},
+                /* not supported */
+                "odd": {"type": "carrier-pigeon"}
+            },
+            "inputs": []
+        }"#;
+        let (servers, warnings) = parse_external_config(contents).unwrap();
+
+        assert!(matches!(
+            &servers["docs"].transport,
+            TransportConfig::Http { url, headers, .. }
+                if url == "https://example.com/mcp" && headers["X-API-Key"] == "${input:key}"
+        ));
+        assert!(!servers.contains_key("odd"));
+        assert_eq!(
+            warnings,
+            [
+                "'docs': header X-API-Key uses ${input:key}, which relay doesn't expand",
+                "Skipping 'odd': unsupported server type 'carrier-pigeon'",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_import_warns_about_placeholders_relay_leaves_alone() {
+        let contents = r#"{
+            "servers": {
+                "files": {
+                    "command": "npx",
+                    "args": ["server-filesystem", "${workspaceFolder}"],
+                    "env": {"TOKEN": "${env:TOKEN}", "KEY": "${input:key}"}
+                }
+            }
+        }"#;
+        let (servers, warnings) = parse_external_config(contents).unwrap();
+
+        // The server is still imported, for the user to fix up
+        assert!(servers.contains_key("files"));
+        assert_eq!(
+            warnings,
+            [
+                "'files': args uses ${workspaceFolder}, which relay doesn't expand",
+                "'files': env KEY uses ${input:key}, which relay doesn't expand",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_strip_json_comments_keeps_strings() {
+        assert_eq!(
+            strip_json_comments(r#"{"url": "https://x//y", "a": "\"/*"} // done"#),
+            r#"{"url": "https://x//y", "a": "\"/*"} "#
+        );
+    }
+
+    #[test]
+    fn test_export_formats() {
+        let servers: BTreeMap<String, ServerConfig> = [
+            (
+                "linear".to_string(),
+                ServerConfig {
+                    transport: TransportConfig::Stdio {
+                        command: "npx @linear/mcp-server".to_string(),
+                        args: Vec::new(),
+                        cwd: None,
+                        inherit_env: true,
+                        log_stderr: true,
+                    },
+                    env: [(
+                        "LINEAR_TOKEN".to_string(),
+                        "${env:LINEAR_TOKEN}".to_string(),
+                    )]
+                    .into_iter()
+                    .collect(),
+                    connection: Default::default(),
+                    roots: Vec::new(),
+                },
+            ),
+            (
+                "docs".to_string(),
+                ServerConfig {
+                    transport: TransportConfig::Http {
+                        url: "https://example.com/mcp".to_string(),
+                        headers: Default::default(),
+                        listen: false,
+                    },
+                    env: Default::default(),
+                    connection: Default::default(),
+                    roots: Vec::new(),
+                },
+            ),
+        ]
+        .into_iter()
+        .collect();
+
+        let (claude, warnings) = to_external_config(ExternalFormat::Claude, &servers);
+        assert_eq!(
+            claude["mcpServers"]["linear"],
+            json!({
+                "command": "npx",
+                "args": ["@linear/mcp-server"],
+                "env": {"LINEAR_TOKEN": "${env:LINEAR_TOKEN}"}
+            })
+        );
+        assert_eq!(
+            warnings,
+            [
+                "Skipping 'docs': Claude Desktop config only supports stdio servers",
+                "'linear': env LINEAR_TOKEN uses ${env:LINEAR_TOKEN}, which Claude Desktop doesn't expand",
+            ]
+        );
+
+        let (_, warnings) = to_external_config(ExternalFormat::Cursor, &servers);
+        assert_eq!(
+            warnings,
+            ["'linear': env LINEAR_TOKEN uses ${env:LINEAR_TOKEN}, which Cursor doesn't expand"]
+        );
+
+        let (vscode, warnings) = to_external_config(ExternalFormat::Vscode, &servers);
+        assert!(warnings.is_empty());
+        assert_eq!(vscode["servers"]["docs"]["type"], "http");
+        assert_eq!(vscode["servers"]["linear"]["type"], "stdio");
+    }
+}
@@ -1,7 +1,11 @@
+mod duration;
+mod external;
 mod interpolate;
 mod store;
 mod types;
 
+pub use duration::*;
+pub use external::*;
 pub use interpolate::*;
 pub use store::*;
 pub use types::*;
@@ -18,6 +22,10 @@ mod tests {
                 ServerConfig {
                     transport: TransportConfig::Stdio {
                         command: "npx @linear/mcp-server".to_string(),
+                        args: Vec::new(),
+                        cwd: None,
+                        inherit_env: true,
+                        log_stderr: true,
                     },
                     env: [(
                         "LINEAR_API_KEY".to_string(),
@@ -25,11 +33,15 @@ mod tests {
                     )]
                     .into_iter()
                     .collect(),
+                    connection: Default::default(),
+                    roots: Vec::new(),
                 },
             )]
             .into_iter()
             .collect(),
             default_server: Some("linear".to_string()),
+            sampling: None,
+            trusted_projects: Default::default(),
         };
 
         let yaml = serde_yaml::to_string(&config).unwrap();
@@ -38,4 +50,47 @@ mod tests {
         assert_eq!(parsed.default_server, Some("linear".to_string()));
         assert!(parsed.servers.contains_key("linear"));
     }
+
+    #[test]
+    fn test_stdio_config_fields() {
+        let yaml = "servers:\n  tool:\n    transport: stdio\n    command: /Applications/My Tool/bin/server\n    args: [--root, My Files]\n    cwd: work\n    inherit_env: false\n";
+        let config: Config = serde_yaml::from_str(yaml).unwrap();
+        let TransportConfig::Stdio {
+            command,
+            args,
+            cwd,
+            inherit_env,
+            ..
+        } = &config.servers["tool"].transport
+        else {
+            panic!("expected stdio transport");
+        };
+        assert_eq!(
+            stdio_argv(command, args).unwrap(),
+            ["/Applications/My Tool/bin/server", "--root", "My Files"]
+        );
+        assert_eq!(cwd.as_deref(), Some(std::path::Path::new("work")));
+        assert!(!inherit_env);
+
+        // Defaults are omitted when serializing
+        let yaml = serde_yaml::to_string(&TransportConfig::Stdio {
+            command: "server".to_string(),
+            args: Vec::new(),
+            cwd: None,
+            inherit_env: true,
+            log_stderr: false,
+        })
+        .unwrap();
+        assert_eq!(yaml, "transport: stdio\ncommand: server\n");
+    }
+
+    #[test]
+    fn test_stdio_argv_honors_quoting() {
+        assert_eq!(
+            stdio_argv(r#""/Applications/My Tool/bin" --name 'a b' c\ d"#, &[]).unwrap(),
+            ["/Applications/My Tool/bin", "--name", "a b", "c d"]
+        );
+        assert!(stdio_argv("server 'unterminated", &[]).is_err());
+        assert!(stdio_argv("  ", &[]).is_err());
+    }
 }
@@ -1,9 +1,27 @@
-use super::Config;
+use super::{Config, ConnectionSettings, TransportConfig};
+use crate::mcp::LoggingLevel;
 use anyhow::{Context, Result};
-use std::path::PathBuf;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
 
+/// File name of project-local config, discovered by walking up from the
+/// current directory
+pub const PROJECT_CONFIG_FILE: &str = ".relay.yaml";
+
+/// Reads and writes the user config, layering an optional project config on top.
+///
+/// Writes always go to the user config; project files are only ever read, and
+/// only once the user has trusted their current contents.
+#[derive(Clone)]
 pub struct ConfigStore {
     path: PathBuf,
+    project_path: Option<PathBuf>,
+    overrides: ConnectionSettings,
+    auto_approve_sampling: bool,
+    elicit_answers: HashMap<String, String>,
+    server_log_level: Option<LoggingLevel>,
 }
 
 impl ConfigStore {
@@ -12,24 +30,165 @@ impl ConfigStore {
         let config_dir = PathBuf::from(home).join(".config").join("relay");
         std::fs::create_dir_all(&config_dir)
             .with_context(|| format!("Failed to create config directory: {:?}", config_dir))?;
-        Ok(Self {
-            path: config_dir.join("config.yaml"),
-        })
+        Ok(Self::with_path(config_dir.join("config.yaml")))
     }
 
     pub fn with_path(path: PathBuf) -> Self {
-        Self { path }
+        Self {
+            path,
+            project_path: None,
+            overrides: ConnectionSettings::default(),
+            auto_approve_sampling: false,
+            elicit_answers: HashMap::new(),
+            server_log_level: None,
+        }
+    }
+
+    /// Apply connection settings (e.g. from the command line) to every server
+    pub fn with_connection_overrides(mut self, overrides: ConnectionSettings) -> Self {
+        self.overrides = overrides;
+        self
+    }
+
+    /// Run the sampling command without asking, whatever the config says
+    pub fn with_auto_approve_sampling(mut self, auto_approve: bool) -> Self {
+        self.auto_approve_sampling = auto_approve;
+        self
+    }
+
+    pub fn auto_approve_sampling(&self) -> bool {
+        self.auto_approve_sampling
+    }
+
+    /// Answer servers' elicitation requests with these values instead of asking
+    pub fn with_elicit_answers(mut self, answers: HashMap<String, String>) -> Self {
+        self.elicit_answers = answers;
+        self
+    }
+
+    pub fn elicit_answers(&self) -> &HashMap<String, String> {
+        &self.elicit_answers
+    }
+
+    /// Ask servers for log records at this level after initializing
+    pub fn with_server_log_level(mut self, level: Option<LoggingLevel>) -> Self {
+        self.server_log_level = level;
+        self
+    }
+
+    pub fn server_log_level(&self) -> Option<LoggingLevel> {
+        self.server_log_level
+    }
+
+    /// Merge the nearest `.relay.yaml` found in `dir` or any of its ancestors
+    pub fn with_project_config_from(mut self, dir: &Path) -> Self {
+        self.project_path = dir
+            .ancestors()
+            .map(|dir| dir.join(PROJECT_CONFIG_FILE))
+            .find(|path| path.is_file());
+        self
+    }
+
+    /// The project config found by `with_project_config_from`, trusted or not
+    pub fn project_path(&self) -> Option<&Path> {
+        self.project_path.as_deref()
+    }
+
+    /// Whether the user has trusted the project config as it is now. Until
+    /// they have, its servers could run anything, so it isn't merged.
+    pub fn is_project_trusted(&self) -> Result<bool> {
+        let Some(project_path) = &self.project_path else {
+            return Ok(true);
+        };
+        let contents = read_file(project_path)?;
+        Ok(is_trusted(&self.load_user()?, project_path, &contents))
     }
 
+    /// Trust the project config's current contents
+    pub fn trust_project(&self) -> Result<()> {
+        let Some(project_path) = &self.project_path else {
+            return Ok(());
+        };
+        let contents = read_file(project_path)?;
+        let mut config = self.load_user()?;
+        config
+            .trusted_projects
+            .insert(project_path.clone(), content_hash(&contents));
+        self.save(&config)
+    }
+
+    /// The user config merged with the project config, if any
     pub fn load(&self) -> Result<Config> {
+        Ok(self.load_with_sources()?.0)
+    }
+
+    /// Like `load`, also returning the file each server was defined in
+    pub fn load_with_sources(&self) -> Result<(Config, HashMap<String, PathBuf>)> {
+        let mut config = self.load_user()?;
+        resolve_relative_paths(&mut config, &self.path);
+        let mut sources: HashMap<String, PathBuf> = config
+            .servers
+            .keys()
+            .map(|name| (name.clone(), self.path.clone()))
+            .collect();
+
+        if let Some(project_path) = &self.project_path {
+            let contents = read_file(project_path)?;
+            if !is_trusted(&config, project_path, &contents) {
+                tracing::debug!("Ignoring untrusted project config {:?}", project_path);
+                return Ok(self.finish(config, sources));
+            }
+            let mut project = parse_config(&contents, project_path)?;
+            resolve_relative_paths(&mut project, project_path);
+            for name in project.servers.keys() {
+                sources.insert(name.clone(), project_path.clone());
+            }
+            config.merge(project);
+        }
+
+        Ok(self.finish(config, sources))
+    }
+
+    /// Apply the command-line overrides to a loaded config
+    fn finish(
+        &self,
+        mut config: Config,
+        sources: HashMap<String, PathBuf>,
+    ) -> (Config, HashMap<String, PathBuf>) {
+        for server in config.servers.values_mut() {
+            server.connection.merge(&self.overrides);
+        }
+        if let Some(sampling) = &mut config.sampling {
+            sampling.auto_approve |= self.auto_approve_sampling;
+        }
+        (config, sources)
+    }
+
+    /// When the user and project configs were last modified, to tell whether
+    /// a config loaded earlier is stale without reading it again
+    pub fn modified(&self) -> Vec<Option<SystemTime>> {
+        std::iter::once(self.path.as_path())
+            .chain(self.project_path())
+            .map(|path| {
+                std::fs::metadata(path)
+                    .and_then(|meta| meta.modified())
+                    .ok()
+            })
+            .collect()
+    }
+
+    /// The user config alone, for commands that modify it
+    pub fn load_user(&self) -> Result<Config> {
         if !self.path.exists() {
             return Ok(Config::default());
         }
-        let contents = std::fs::read_to_string(&self.path)
-            .with_context(|| format!("Failed to read config from {:?}", self.path))?;
-        let config: Config =
-            serde_yaml::from_str(&contents).with_context(|| "Failed to parse config YAML")?;
-        Ok(config)
+        read_config(&self.path)
+    }
+
+    /// Socket of the `relay daemon` serving this config, kept beside the user
+    /// config so each config file gets its own daemon
+    pub fn daemon_socket(&self) -> PathBuf {
+        self.path.with_file_name("daemon.sock")
     }
 
     pub fn save(&self, config: &Config) -> Result<()> {
@@ -45,6 +204,52 @@ impl ConfigStore {
     }
 }
 
+fn read_file(path: &Path) -> Result<String> {
+    std::fs::read_to_string(path).with_context(|| format!("Failed to read config from {:?}", path))
+}
+
+fn parse_config(contents: &str, path: &Path) -> Result<Config> {
+    serde_yaml::from_str(contents)
+        .with_context(|| format!("Failed to parse config YAML in {:?}", path))
+}
+
+fn read_config(path: &Path) -> Result<Config> {
+    parse_config(&read_file(path)?, path)
+}
+
+/// Hex SHA-256 of a config file's contents, so editing a trusted file asks
+/// for trust again
+fn content_hash(contents: &str) -> String {
+    Sha256::digest(contents.as_bytes())
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+fn is_trusted(user: &Config, project_path: &Path, contents: &str) -> bool {
+    user.trusted_projects.get(project_path) == Some(&content_hash(contents))
+}
+
+/// Make relative stdio working directories, socket paths and roots relative
+/// to the defining file
+fn resolve_relative_paths(config: &mut Config, config_path: &Path) {
+    let Some(base) = config_path.parent() else {
+        return;
+    };
+    for server in config.servers.values_mut() {
+        let transport_path = match &mut server.transport {
+            TransportConfig::Stdio { cwd: Some(cwd), .. } => Some(cwd),
+            TransportConfig::Unix { path } => Some(path),
+            _ => None,
+        };
+        for path in transport_path.into_iter().chain(&mut server.roots) {
+            if path.is_relative() {
+                *path = base.join(&*path);
+            }
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -68,8 +273,12 @@ mod tests {
             ServerConfig {
                 transport: TransportConfig::Http {
                     url: "http://localhost:3000".to_string(),
+                    headers: Default::default(),
+                    listen: false,
                 },
                 env: Default::default(),
+                connection: Default::default(),
+                roots: Vec::new(),
             },
         );
         store.save(&config).unwrap();
@@ -77,4 +286,82 @@ mod tests {
         let loaded = store.load().unwrap();
         assert!(loaded.servers.contains_key("test"));
     }
+
+    #[test]
+    fn test_project_config_merged_over_user_config() {
+        let dir = tempdir().unwrap();
+        let user_path = dir.path().join("config.yaml");
+        std::fs::write(
+            &user_path,
+            "servers:\n  linear:\n    transport: http\n    url: http://user\n  github:\n    transport: http\n    url: http://github\ndefault_server: github\n",
+        )
+        .unwrap();
+
+        let project_dir = dir.path().join("repo");
+        let nested = project_dir.join("src/deep");
+        std::fs::create_dir_all(&nested).unwrap();
+        let project_path = project_dir.join(PROJECT_CONFIG_FILE);
+        std::fs::write(
+            &project_path,
+            "servers:\n  linear:\n    transport: http\n    url: http://project\n  tool:\n    transport: stdio\n    command: ./server\n    cwd: tools\ndefault_server: linear\nsampling:\n  command: ./sample\n  auto_approve: true\n",
+        )
+        .unwrap();
+
+        let store = ConfigStore::with_path(user_path.clone()).with_project_config_from(&nested);
+        store.trust_project().unwrap();
+        let (config, sources) = store.load_with_sources().unwrap();
+
+        assert_eq!(config.default_server.as_deref(), Some("linear"));
+        // A repository can't choose what runs without asking
+        assert!(config.sampling.is_none());
+        assert!(matches!(
+            &config.servers["linear"].transport,
+            TransportConfig::Http { url, .. } if url == "http://project"
+        ));
+        assert_eq!(sources["linear"], project_path);
+        assert_eq!(sources["github"], user_path);
+        assert!(matches!(
+            &config.servers["tool"].transport,
+            TransportConfig::Stdio { cwd: Some(cwd), .. } if *cwd == project_dir.join("tools")
+        ));
+
+        // The user config itself is untouched
+        let user = store.load_user().unwrap();
+        assert_eq!(user.default_server.as_deref(), Some("github"));
+    }
+
+    #[test]
+    fn test_untrusted_project_config_ignored() {
+        let dir = tempdir().unwrap();
+        let user_path = dir.path().join("config.yaml");
+        std::fs::write(
+            &user_path,
+            "servers:\n  github:\n    transport: http\n    url: http://github\n",
+        )
+        .unwrap();
+        let project_path = dir.path().join(PROJECT_CONFIG_FILE);
+        std::fs::write(
+            &project_path,
+            "servers:\n  tool:\n    transport: stdio\n    command: ./server\n",
+        )
+        .unwrap();
+
+        let store = ConfigStore::with_path(user_path).with_project_config_from(dir.path());
+        assert!(!store.is_project_trusted().unwrap());
+        let (config, sources) = store.load_with_sources().unwrap();
+        assert!(!config.servers.contains_key("tool"));
+        assert!(!sources.values().any(|source| *source == project_path));
+
+        store.trust_project().unwrap();
+        assert!(store.load().unwrap().servers.contains_key("tool"));
+
+        // Changing the file withdraws the trust
+        std::fs::write(
+            &project_path,
+            "servers:\n  tool:\n    transport: stdio\n    command: ./other\n",
+        )
+        .unwrap();
+        assert!(!store.is_project_trusted().unwrap());
+        assert!(!store.load().unwrap().servers.contains_key("tool"));
+    }
 }
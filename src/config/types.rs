@@ -1,11 +1,44 @@
+use anyhow::{bail, Result};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::path::PathBuf;
+use std::time::Duration;
 
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
 pub struct Config {
     #[serde(default)]
     pub servers: HashMap<String, ServerConfig>,
     pub default_server: Option<String>,
+    /// How to answer servers' `sampling/createMessage` requests
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sampling: Option<SamplingConfig>,
+    /// Project config files the user has agreed to use, with the SHA-256 of
+    /// the contents they agreed to. Only read from the user config.
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub trusted_projects: HashMap<PathBuf, String>,
+}
+
+impl Config {
+    /// Layer a project config on top: its servers replace same-named ones and
+    /// its default server, if set, wins. Sampling settings decide which
+    /// commands run without asking, so they only come from the user config.
+    pub fn merge(&mut self, other: Config) {
+        self.servers.extend(other.servers);
+        if other.default_server.is_some() {
+            self.default_server = other.default_server;
+        }
+    }
+}
+
+/// A local program that samples a model on behalf of servers
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SamplingConfig {
+    /// Shell-quoted command line. It reads the request's params as JSON on
+    /// stdin and writes a `CreateMessageResult` to stdout.
+    pub command: String,
+    /// Run the command without asking first
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub auto_approve: bool,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -14,11 +47,124 @@ pub struct ServerConfig {
     pub transport: TransportConfig,
     #[serde(default)]
     pub env: HashMap<String, String>,
+    #[serde(flatten)]
+    pub connection: ConnectionSettings,
+    /// Directories the server may work in, relative to the config file that
+    /// defines the server. Defaults to the current directory.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub roots: Vec<PathBuf>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// Timeouts and retry behaviour for talking to a server
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ConnectionSettings {
+    /// Limit for each request, e.g. `30s`
+    #[serde(
+        default,
+        with = "super::optional_duration",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub timeout: Option<Duration>,
+    /// Limit for establishing an HTTP connection
+    #[serde(
+        default,
+        with = "super::optional_duration",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub connect_timeout: Option<Duration>,
+    /// Retries for connection errors and 5xx/429 responses
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub retries: Option<u32>,
+    /// Also retry `tools/call`, which may not be safe to repeat
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub retry_tool_calls: bool,
+}
+
+impl ConnectionSettings {
+    /// Apply overrides on top of these settings
+    pub fn merge(&mut self, overrides: &ConnectionSettings) {
+        self.timeout = overrides.timeout.or(self.timeout);
+        self.connect_timeout = overrides.connect_timeout.or(self.connect_timeout);
+        self.retries = overrides.retries.or(self.retries);
+        self.retry_tool_calls |= overrides.retry_tool_calls;
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(tag = "transport", rename_all = "lowercase")]
 pub enum TransportConfig {
-    Stdio { command: String },
-    Http { url: String },
+    Stdio {
+        /// Program to run, or a whole shell-quoted command line when `args` is empty
+        command: String,
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        args: Vec<String>,
+        /// Working directory, relative to the config file that defines the server
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        cwd: Option<PathBuf>,
+        /// Pass the parent environment through; when false only `env` is set
+        #[serde(default = "default_true", skip_serializing_if = "is_true")]
+        inherit_env: bool,
+        /// Append the server's stderr to its log file for `relay logs`. Off by
+        /// default, since servers may write secrets to stderr.
+        #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+        log_stderr: bool,
+    },
+    Http {
+        url: String,
+        /// Extra request headers; values support `${env:VAR}` interpolation
+        #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+        headers: HashMap<String, String>,
+        /// Keep a GET stream open for messages the server sends unprompted
+        #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+        listen: bool,
+    },
+    /// Legacy HTTP+SSE transport (protocol version 2024-11-05)
+    Sse {
+        url: String,
+        #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+        headers: HashMap<String, String>,
+    },
+    /// A running server listening on a Unix domain socket
+    Unix {
+        /// Socket path, relative to the config file that defines the server
+        path: PathBuf,
+    },
+    /// A running server listening on a TCP port
+    Tcp {
+        /// `host:port`
+        address: String,
+    },
+}
+
+/// The argv for a stdio server. A lone `command` is split with shell-style
+/// quoting; with explicit `args` it is taken verbatim as the program.
+pub fn stdio_argv(command: &str, args: &[String]) -> Result<Vec<String>> {
+    let argv = if args.is_empty() {
+        match shlex::split(command) {
+            Some(argv) => argv,
+            None => bail!("Unbalanced quotes in command: {}", command),
+        }
+    } else {
+        std::iter::once(command.to_string())
+            .chain(args.iter().cloned())
+            .collect()
+    };
+
+    if argv.is_empty() {
+        bail!("Empty command");
+    }
+    Ok(argv)
+}
+
+/// Render an argv as a single shell-quoted command line for display
+pub fn display_argv(argv: &[String]) -> String {
+    shlex::try_join(argv.iter().map(String::as_str)).unwrap_or_else(|_| argv.join(" "))
+}
+
+fn default_true() -> bool {
+    true
+}
+
+fn is_true(value: &bool) -> bool {
+    *value
 }
@@ -0,0 +1,162 @@
+use super::{DaemonReply, DaemonRequest};
+use crate::config::ServerConfig;
+use crate::mcp::transport::Transport;
+use crate::mcp::{
+    CancelledParams, DefaultHandler, JsonRpcNotification, JsonRpcRequest, JsonRpcResponse,
+    LoggingLevel, MessageHandler, RequestId,
+};
+use anyhow::{bail, Context, Result};
+use async_trait::async_trait;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::unix::OwnedWriteHalf;
+use tokio::net::UnixStream;
+use tokio::sync::oneshot;
+
+/// Whether a daemon is accepting connections on `socket`
+pub async fn daemon_running(socket: &Path) -> bool {
+    socket.exists() && UnixStream::connect(socket).await.is_ok()
+}
+
+/// Send one request to the daemon and wait for its reply
+pub async fn daemon_request(socket: &Path, request: &DaemonRequest) -> Result<DaemonReply> {
+    let stream = UnixStream::connect(socket)
+        .await
+        .with_context(|| format!("Failed to connect to daemon at {}", socket.display()))?;
+    let (reader, mut writer) = stream.into_split();
+    send_line(&mut writer, request).await?;
+
+    let mut reply = String::new();
+    BufReader::new(reader)
+        .read_line(&mut reply)
+        .await
+        .context("Failed to read reply from daemon")?;
+    if reply.is_empty() {
+        bail!("Daemon closed the connection without replying");
+    }
+    serde_json::from_str(&reply).context("Invalid reply from daemon")
+}
+
+async fn send_line(writer: &mut OwnedWriteHalf, request: &DaemonRequest) -> Result<()> {
+    let mut line = serde_json::to_string(request)?;
+    line.push('\n');
+    writer
+        .write_all(line.as_bytes())
+        .await
+        .context("Failed to send request to daemon")
+}
+
+/// Routes a server's requests through the daemon's warm session for it
+pub struct DaemonTransport {
+    socket: PathBuf,
+    server: String,
+    config: ServerConfig,
+    log_level: Option<LoggingLevel>,
+    handler: Arc<dyn MessageHandler>,
+    /// Where to send the reason when a request in flight is cancelled
+    cancels: Mutex<HashMap<RequestId, oneshot::Sender<String>>>,
+}
+
+impl DaemonTransport {
+    pub fn new(socket: PathBuf, server: String, config: ServerConfig) -> Self {
+        Self {
+            socket,
+            server,
+            config,
+            log_level: None,
+            handler: Arc::new(DefaultHandler),
+            cancels: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Have the daemon's session send the server's log records at this level
+    pub fn with_log_level(mut self, level: Option<LoggingLevel>) -> Self {
+        self.log_level = level;
+        self
+    }
+
+    /// Handle the notifications the daemon relays for our requests
+    pub fn with_handler(mut self, handler: Arc<dyn MessageHandler>) -> Self {
+        self.handler = handler;
+        self
+    }
+
+    /// Forward a request on its own connection, passing on a cancellation
+    /// while waiting for the reply
+    async fn exchange(
+        &self,
+        req: JsonRpcRequest,
+        mut cancelled: oneshot::Receiver<String>,
+    ) -> Result<JsonRpcResponse> {
+        let stream = UnixStream::connect(&self.socket)
+            .await
+            .with_context(|| format!("Failed to connect to daemon at {}", self.socket.display()))?;
+        let (reader, mut writer) = stream.into_split();
+        let request = DaemonRequest::Forward {
+            server: self.server.clone(),
+            config: Box::new(self.config.clone()),
+            request: req,
+            log_level: self.log_level,
+        };
+        send_line(&mut writer, &request).await?;
+
+        let mut lines = BufReader::new(reader).lines();
+        let mut cancel_sent = false;
+        loop {
+            tokio::select! {
+                line = lines.next_line() => {
+                    let line = line
+                        .context("Failed to read reply from daemon")?
+                        .context("Daemon closed the connection without replying")?;
+                    match serde_json::from_str(&line).context("Invalid reply from daemon")? {
+                        DaemonReply::Notification { notification } => {
+                            self.handler.on_notification(notification).await;
+                        }
+                        DaemonReply::Response { response } => return Ok(response),
+                        DaemonReply::Error { message } => bail!(message),
+                        reply => bail!("Unexpected reply from daemon: {:?}", reply),
+                    }
+                }
+                Ok(reason) = &mut cancelled, if !cancel_sent => {
+                    cancel_sent = true;
+                    send_line(&mut writer, &DaemonRequest::Cancel { reason }).await?;
+                }
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl Transport for DaemonTransport {
+    async fn request(&self, req: JsonRpcRequest) -> Result<JsonRpcResponse> {
+        let id = req.id.clone();
+        let (cancel, cancelled) = oneshot::channel();
+        self.cancels.lock().unwrap().insert(id.clone(), cancel);
+        let result = self.exchange(req, cancelled).await;
+        self.cancels.lock().unwrap().remove(&id);
+        result
+    }
+
+    /// Only cancellations are passed on; the session's other traffic is the
+    /// daemon's business
+    async fn notify(&self, notification: JsonRpcNotification) -> Result<()> {
+        if notification.method != "notifications/cancelled" {
+            tracing::debug!("Not sending {} through the daemon", notification.method);
+            return Ok(());
+        }
+        let params: CancelledParams =
+            serde_json::from_value(notification.params.unwrap_or_default())
+                .context("Invalid cancellation")?;
+        if let Some(cancel) = self.cancels.lock().unwrap().remove(&params.request_id) {
+            cancel.send(params.reason.unwrap_or_default()).ok();
+        }
+        Ok(())
+    }
+
+    /// The session belongs to the daemon, which keeps it open for the next caller
+    async fn close(&self) -> Result<()> {
+        Ok(())
+    }
+}
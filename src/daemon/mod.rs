@@ -0,0 +1,75 @@
+//! `relay daemon`: a background process that keeps server sessions warm so
+//! each CLI invocation skips spawning and initializing its server.
+//!
+//! The daemon listens on a Unix socket beside the user config. Each
+//! connection carries newline-delimited [`DaemonRequest`]s, each answered by
+//! one [`DaemonReply`]. A forwarded request's progress notifications, and the
+//! server's log records while it runs, are relayed ahead of its reply, and a
+//! `Cancel` line sent while it is in flight cancels it.
+
+mod client;
+mod server;
+
+pub use client::*;
+pub use server::*;
+
+use crate::config::ServerConfig;
+use crate::mcp::{JsonRpcNotification, JsonRpcRequest, JsonRpcResponse, LoggingLevel, ServerInfo};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum DaemonRequest {
+    /// Send a JSON-RPC request to a server, connecting it first if needed.
+    /// The config travels with the request so the daemon always uses what
+    /// the caller sees, including project config and command-line overrides.
+    Forward {
+        server: String,
+        config: Box<ServerConfig>,
+        request: JsonRpcRequest,
+        /// The `--server-log-level` to ask the server for records at
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        log_level: Option<LoggingLevel>,
+    },
+    /// Cancel the request being forwarded on this connection
+    Cancel {
+        reason: String,
+    },
+    Status,
+    Stop,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum DaemonReply {
+    /// Progress of the request being forwarded, or a server log record sent
+    /// while it runs, ahead of its response
+    Notification {
+        notification: JsonRpcNotification,
+    },
+    Response {
+        response: JsonRpcResponse,
+    },
+    Error {
+        message: String,
+    },
+    Status(DaemonStatus),
+    Stopping,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DaemonStatus {
+    pub pid: u32,
+    pub uptime_secs: u64,
+    pub idle_timeout_secs: u64,
+    pub sessions: Vec<SessionStatus>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SessionStatus {
+    pub server: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub server_info: Option<ServerInfo>,
+    pub requests: u64,
+    pub idle_secs: u64,
+}
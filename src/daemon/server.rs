@@ -0,0 +1,488 @@
+use super::{DaemonReply, DaemonRequest, DaemonStatus, SessionStatus};
+use crate::commands::{connect_with, session_roots, SessionHandler};
+use crate::config::{ConfigStore, ServerConfig};
+use crate::mcp::{
+    progress_token, InitializeResult, JsonRpcNotification, JsonRpcRequest, JsonRpcResponse,
+    LoggingLevel, McpClient, Root, CANCEL_GRACE, INTERNAL_ERROR, PROTOCOL_VERSION,
+};
+use anyhow::{bail, Context, Result};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::future::Future;
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::unix::OwnedWriteHalf;
+use tokio::net::{UnixListener, UnixStream};
+use tokio::signal::unix::{signal, SignalKind};
+use tokio::sync::{broadcast, mpsc, Notify, OnceCell, RwLock};
+
+/// Sessions are per server and log level, so callers asking for different
+/// records never change the level under each other's requests
+type SessionKey = (String, Option<LoggingLevel>);
+
+/// Log records a session buffers for a slow caller before dropping them
+const RECORD_BACKLOG: usize = 256;
+
+/// A warm connection to one server, opened on first use
+struct Session {
+    /// The config the session was opened with; a request carrying a
+    /// different one replaces the session
+    config: ServerConfig,
+    log_level: Option<LoggingLevel>,
+    key: Value,
+    client: OnceCell<McpClient>,
+    /// The server's log records, for callers with requests in flight
+    records: broadcast::Sender<JsonRpcNotification>,
+    /// Held shared by requests in flight, and exclusively to change the
+    /// roots, so a caller's roots stay put until its request is answered
+    roots: RwLock<()>,
+    requests: AtomicU64,
+    in_flight: AtomicU64,
+    last_used: Mutex<Instant>,
+}
+
+impl Session {
+    fn new(config: ServerConfig, log_level: Option<LoggingLevel>, key: Value) -> Self {
+        Self {
+            config,
+            log_level,
+            key,
+            client: OnceCell::new(),
+            records: broadcast::channel(RECORD_BACKLOG).0,
+            roots: RwLock::new(()),
+            requests: AtomicU64::new(0),
+            in_flight: AtomicU64::new(0),
+            last_used: Mutex::new(Instant::now()),
+        }
+    }
+
+    fn touch(&self) {
+        *self.last_used.lock().unwrap() = Instant::now();
+    }
+
+    fn idle(&self) -> Duration {
+        self.last_used.lock().unwrap().elapsed()
+    }
+}
+
+/// Keeps sessions warm for CLI invocations and closes them once idle
+pub struct Daemon {
+    store: ConfigStore,
+    idle_timeout: Duration,
+    started: Instant,
+    sessions: Mutex<HashMap<SessionKey, Arc<Session>>>,
+    shutdown: Notify,
+}
+
+impl Daemon {
+    pub fn new(store: ConfigStore, idle_timeout: Duration) -> Self {
+        Self {
+            store,
+            idle_timeout,
+            started: Instant::now(),
+            sessions: Mutex::new(HashMap::new()),
+            shutdown: Notify::new(),
+        }
+    }
+
+    /// Serve on `socket` until stopped or signalled, then close every session
+    pub async fn run(self: Arc<Self>, socket: &Path) -> Result<()> {
+        let listener = bind(socket)?;
+        let mut terminate = signal(SignalKind::terminate())?;
+        // Check often enough that idle sessions close near their deadline
+        let mut reaper = tokio::time::interval(
+            (self.idle_timeout / 2).clamp(Duration::from_millis(100), Duration::from_secs(30)),
+        );
+
+        loop {
+            tokio::select! {
+                accepted = listener.accept() => match accepted {
+                    Ok((stream, _)) => {
+                        let daemon = self.clone();
+                        tokio::spawn(async move { daemon.handle(stream).await });
+                    }
+                    Err(e) => tracing::debug!("Failed to accept daemon connection: {}", e),
+                },
+                _ = reaper.tick() => self.reap().await,
+                _ = self.shutdown.notified() => break,
+                _ = tokio::signal::ctrl_c() => break,
+                _ = terminate.recv() => break,
+            }
+        }
+
+        drop(listener);
+        let sessions: Vec<_> = self.sessions.lock().unwrap().drain().collect();
+        for (_, session) in sessions {
+            close_session(&session).await;
+        }
+        std::fs::remove_file(socket).ok();
+        Ok(())
+    }
+
+    /// Answer each request line on a connection
+    async fn handle(&self, stream: UnixStream) {
+        let (reader, writer) = stream.into_split();
+        let mut lines = BufReader::new(reader).lines();
+        let (replies, outgoing) = mpsc::unbounded_channel();
+        let writer = tokio::spawn(write_replies(writer, outgoing));
+        let mut stopping = false;
+
+        while let Ok(Some(line)) = lines.next_line().await {
+            let reply = match serde_json::from_str(&line) {
+                Ok(DaemonRequest::Forward {
+                    server,
+                    config,
+                    request,
+                    log_level,
+                }) => {
+                    // Keep reading while the request runs, for a cancellation
+                    // or the caller going away
+                    let cancelled = async {
+                        loop {
+                            match lines.next_line().await {
+                                Ok(Some(line)) => {
+                                    if let Ok(DaemonRequest::Cancel { reason }) =
+                                        serde_json::from_str(&line)
+                                    {
+                                        return reason;
+                                    }
+                                }
+                                _ => return "Client disconnected".to_string(),
+                            }
+                        }
+                    };
+                    match self
+                        .forward(&server, *config, log_level, request, &replies, cancelled)
+                        .await
+                    {
+                        Ok(response) => DaemonReply::Response { response },
+                        Err(e) => DaemonReply::Error {
+                            message: format!("{:#}", e),
+                        },
+                    }
+                }
+                // Arrived after its request had already finished
+                Ok(DaemonRequest::Cancel { .. }) => continue,
+                Ok(DaemonRequest::Stop) => {
+                    stopping = true;
+                    DaemonReply::Stopping
+                }
+                Ok(DaemonRequest::Status) => DaemonReply::Status(self.status()),
+                Err(e) => DaemonReply::Error {
+                    message: format!("Invalid daemon request: {}", e),
+                },
+            };
+            if replies.send(reply).is_err() || stopping {
+                break;
+            }
+        }
+
+        drop(replies);
+        writer.await.ok();
+        // Only once the reply is written, as shutting down ends the process
+        if stopping {
+            self.shutdown.notify_one();
+        }
+    }
+
+    /// Send a request through the server's session, answering `initialize`
+    /// from the session's own handshake
+    async fn forward(
+        &self,
+        server: &str,
+        config: ServerConfig,
+        log_level: Option<LoggingLevel>,
+        request: JsonRpcRequest,
+        replies: &mpsc::UnboundedSender<DaemonReply>,
+        cancelled: impl Future<Output = String>,
+    ) -> Result<JsonRpcResponse> {
+        let roots = session_roots(&config);
+        let (key, session) = self.session(server, config, log_level)?;
+        session.in_flight.fetch_add(1, Ordering::SeqCst);
+        session.touch();
+        let result = self
+            .forward_to(server, &session, roots, request, replies, cancelled)
+            .await;
+        session.touch();
+        session.in_flight.fetch_sub(1, Ordering::SeqCst);
+
+        match result {
+            Ok(response) => {
+                session.requests.fetch_add(1, Ordering::SeqCst);
+                Ok(response)
+            }
+            Err(e) => {
+                // The connection may be broken; start afresh next time
+                self.discard(&key, &session).await;
+                Err(e)
+            }
+        }
+    }
+
+    async fn forward_to(
+        &self,
+        server: &str,
+        session: &Session,
+        roots: Vec<Root>,
+        request: JsonRpcRequest,
+        replies: &mpsc::UnboundedSender<DaemonReply>,
+        cancelled: impl Future<Output = String>,
+    ) -> Result<JsonRpcResponse> {
+        let mut records = session.records.subscribe();
+        let client = session
+            .client
+            .get_or_try_init(|| async {
+                // Nobody is at the daemon's terminal to answer prompts
+                let handler = SessionHandler::new(server)
+                    .with_sampling(self.store.load()?.sampling)
+                    .with_log_level(session.log_level)
+                    .with_record_forwarding(session.records.clone())
+                    .with_roots(&session.config)
+                    .without_prompts();
+                connect_with(&self.store, server, &session.config, handler).await
+            })
+            .await?;
+
+        if request.method == "initialize" {
+            // Records from the handshake, if this caller opened the session
+            while let Ok(notification) = records.try_recv() {
+                replies
+                    .send(DaemonReply::Notification { notification })
+                    .ok();
+            }
+            let result = InitializeResult {
+                protocol_version: PROTOCOL_VERSION.to_string(),
+                capabilities: client.capabilities().cloned().unwrap_or_default(),
+                server_info: client
+                    .server_info()
+                    .cloned()
+                    .context("Session is not initialized")?,
+            };
+            return Ok(JsonRpcResponse::success(
+                request.id,
+                serde_json::to_value(result)?,
+            ));
+        }
+
+        // Callers share the session from different directories; wait for
+        // requests made with other roots before switching to ours
+        let _in_use = match session.roots.read().await {
+            current if client.roots() == roots => current,
+            current => {
+                drop(current);
+                let switching = session.roots.write().await;
+                client.set_roots(roots).await?;
+                switching.downgrade()
+            }
+        };
+
+        // The caller's progress token is unique to its process, so the
+        // session can route the server's reports for it back here
+        let mut progress =
+            progress_token(request.params.as_ref()).map(|token| client.progress().listen(token));
+        let outgoing = client.new_request(&request.method, request.params);
+        let id = outgoing.id.clone();
+        let response = client.send_request(outgoing);
+        tokio::pin!(response, cancelled);
+
+        let response = loop {
+            tokio::select! {
+                response = &mut response => break response?,
+                Some(update) = async { progress.as_mut()?.recv().await } => {
+                    let notification = JsonRpcNotification::new(
+                        "notifications/progress",
+                        Some(serde_json::to_value(update)?),
+                    );
+                    replies.send(DaemonReply::Notification { notification }).ok();
+                }
+                Ok(notification) = records.recv() => {
+                    replies.send(DaemonReply::Notification { notification }).ok();
+                }
+                reason = &mut cancelled => {
+                    client.cancel(id, &reason).await?;
+                    match tokio::time::timeout(CANCEL_GRACE, response).await {
+                        Ok(response) => break response?,
+                        // The server owes no response to a cancelled request
+                        Err(_) => {
+                            return Ok(JsonRpcResponse::failure(
+                                request.id,
+                                INTERNAL_ERROR,
+                                format!("Request cancelled: {}", reason),
+                            ))
+                        }
+                    }
+                }
+            }
+        };
+        // Records the server sent just ahead of its response
+        while let Ok(notification) = records.try_recv() {
+            replies
+                .send(DaemonReply::Notification { notification })
+                .ok();
+        }
+        Ok(JsonRpcResponse {
+            id: request.id,
+            ..response
+        })
+    }
+
+    /// The session for a server and the caller's log level, replacing it if
+    /// the server's config has changed
+    fn session(
+        &self,
+        server: &str,
+        mut config: ServerConfig,
+        log_level: Option<LoggingLevel>,
+    ) -> Result<(SessionKey, Arc<Session>)> {
+        // Callers enforce their own request timeouts, and roots are updated
+        // in place
+        config.connection.timeout = None;
+        let id = (server.to_string(), log_level);
+        let key = serde_json::to_value(ServerConfig {
+            roots: Vec::new(),
+            ..config.clone()
+        })?;
+
+        let mut sessions = self.sessions.lock().unwrap();
+        if let Some(session) = sessions.get(&id) {
+            if session.key == key {
+                return Ok((id, session.clone()));
+            }
+        }
+
+        let session = Arc::new(Session::new(config, log_level, key));
+        if let Some(stale) = sessions.insert(id.clone(), session.clone()) {
+            tokio::spawn(async move { close_session(&stale).await });
+        }
+        Ok((id, session))
+    }
+
+    /// Remove a session unless it has already been replaced
+    async fn discard(&self, id: &SessionKey, session: &Arc<Session>) {
+        let removed = {
+            let mut sessions = self.sessions.lock().unwrap();
+            match sessions.get(id) {
+                Some(current) if Arc::ptr_eq(current, session) => sessions.remove(id),
+                _ => None,
+            }
+        };
+        if let Some(session) = removed {
+            close_session(&session).await;
+        }
+    }
+
+    /// Close sessions that have been idle for longer than the idle timeout
+    async fn reap(&self) {
+        let expired: Vec<_> = {
+            let mut sessions = self.sessions.lock().unwrap();
+            let ids: Vec<_> = sessions
+                .iter()
+                .filter(|(_, session)| {
+                    session.in_flight.load(Ordering::SeqCst) == 0
+                        && session.idle() >= self.idle_timeout
+                })
+                .map(|(id, _)| id.clone())
+                .collect();
+            ids.into_iter()
+                .filter_map(|id| sessions.remove_entry(&id))
+                .collect()
+        };
+
+        for ((name, ..), session) in expired {
+            tracing::debug!("Closing idle session for '{}'", name);
+            close_session(&session).await;
+        }
+    }
+
+    fn status(&self) -> DaemonStatus {
+        let mut sessions: Vec<_> = self
+            .sessions
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|((name, ..), session)| SessionStatus {
+                server: name.clone(),
+                server_info: session
+                    .client
+                    .get()
+                    .and_then(|client| client.server_info().cloned()),
+                requests: session.requests.load(Ordering::SeqCst),
+                idle_secs: session.idle().as_secs(),
+            })
+            .collect();
+        sessions.sort_by(|a, b| a.server.cmp(&b.server));
+
+        DaemonStatus {
+            pid: std::process::id(),
+            uptime_secs: self.started.elapsed().as_secs(),
+            idle_timeout_secs: self.idle_timeout.as_secs(),
+            sessions,
+        }
+    }
+}
+
+/// Write replies to a connection as they are produced
+async fn write_replies(
+    mut writer: OwnedWriteHalf,
+    mut replies: mpsc::UnboundedReceiver<DaemonReply>,
+) {
+    while let Some(reply) = replies.recv().await {
+        let Ok(mut json) = serde_json::to_string(&reply) else {
+            break;
+        };
+        json.push('\n');
+        if writer.write_all(json.as_bytes()).await.is_err() {
+            break;
+        }
+    }
+}
+
+async fn close_session(session: &Session) {
+    if let Some(client) = session.client.get() {
+        if let Err(e) = client.close().await {
+            tracing::debug!("Failed to close session: {:#}", e);
+        }
+    }
+}
+
+/// Listen on the daemon socket, clearing one left behind by a daemon that
+/// didn't shut down cleanly
+fn bind(socket: &Path) -> Result<UnixListener> {
+    if socket.exists() {
+        if std::os::unix::net::UnixStream::connect(socket).is_ok() {
+            bail!("A daemon is already running on {}", socket.display());
+        }
+        std::fs::remove_file(socket)
+            .with_context(|| format!("Failed to remove stale socket {}", socket.display()))?;
+    }
+    if let Some(parent) = socket.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+
+    // Requests carry commands to spawn, so only the owner may connect. The
+    // umask makes the socket private as it's created, not just after.
+    // SAFETY: umask only swaps the process's file mode mask
+    let previous = unsafe { libc::umask(0o177) };
+    let listener = UnixListener::bind(socket);
+    unsafe { libc::umask(previous) };
+    listener.with_context(|| format!("Failed to listen on {}", socket.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::os::unix::fs::PermissionsExt;
+
+    #[tokio::test]
+    async fn test_socket_is_private() {
+        let dir = tempfile::tempdir().unwrap();
+        let socket = dir.path().join("daemon.sock");
+        let _listener = bind(&socket).unwrap();
+
+        let mode = std::fs::metadata(&socket).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+    }
+}
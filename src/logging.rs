@@ -0,0 +1,170 @@
+use crate::cli::OutputFormat;
+use crate::mcp::{LoggingLevel, LoggingMessageParams};
+use owo_colors::OwoColorize;
+use serde_json::{json, Value};
+use std::io::{IsTerminal, Write};
+use tracing::field::{Field, Visit};
+use tracing::{Event, Level, Subscriber};
+use tracing_subscriber::layer::{Context, Layer};
+
+/// Target of the tracing events that carry servers' log records, so
+/// `RUST_LOG=server_log=warn` filters them like any other
+pub const SERVER_LOG_TARGET: &str = "server_log";
+
+/// The tracing level a server's records are filtered at
+pub fn tracing_level(level: LoggingLevel) -> Level {
+    match level {
+        LoggingLevel::Debug => Level::DEBUG,
+        LoggingLevel::Info | LoggingLevel::Notice => Level::INFO,
+        LoggingLevel::Warning => Level::WARN,
+        LoggingLevel::Error
+        | LoggingLevel::Critical
+        | LoggingLevel::Alert
+        | LoggingLevel::Emergency => Level::ERROR,
+    }
+}
+
+/// Hand a server's log record to tracing. If the subscriber's filter lets it
+/// through, [`ServerLogLayer`] shows it.
+pub fn log_server_record(server_name: &str, record: &LoggingMessageParams) {
+    let severity = record.level.as_str();
+    let logger = record.logger.as_deref().unwrap_or_default();
+    let data = record.data.to_string();
+    macro_rules! emit {
+        ($level:expr) => {
+            tracing::event!(
+                target: SERVER_LOG_TARGET,
+                $level,
+                server = server_name,
+                severity,
+                logger,
+                data
+            )
+        };
+    }
+    match tracing_level(record.level) {
+        Level::DEBUG => emit!(Level::DEBUG),
+        Level::INFO => emit!(Level::INFO),
+        Level::WARN => emit!(Level::WARN),
+        _ => emit!(Level::ERROR),
+    }
+}
+
+/// Shows servers' log records: coloured by severity for people, or as
+/// `{"type":"log",...}` lines in JSON mode. Records go to stderr, or to
+/// stdout when streaming them is the command's output.
+pub struct ServerLogLayer {
+    format: OutputFormat,
+    stdout: bool,
+}
+
+impl ServerLogLayer {
+    pub fn new(format: OutputFormat) -> Self {
+        Self {
+            format,
+            stdout: false,
+        }
+    }
+
+    pub fn with_stdout(mut self, stdout: bool) -> Self {
+        self.stdout = stdout;
+        self
+    }
+}
+
+impl<S: Subscriber> Layer<S> for ServerLogLayer {
+    fn on_event(&self, event: &Event<'_>, _ctx: Context<'_, S>) {
+        if event.metadata().target() != SERVER_LOG_TARGET {
+            return;
+        }
+        let mut record = RecordFields::default();
+        event.record(&mut record);
+        let data: Value = serde_json::from_str(&record.data).unwrap_or(Value::Null);
+
+        let line = match self.format {
+            OutputFormat::Human => {
+                let colour = if self.stdout {
+                    std::io::stdout().is_terminal()
+                } else {
+                    std::io::stderr().is_terminal()
+                };
+                render_record(&record, &data, colour)
+            }
+            OutputFormat::Json => json!({
+                "type": "log",
+                "server": record.server,
+                "level": record.severity,
+                "logger": (!record.logger.is_empty()).then_some(&record.logger),
+                "data": data,
+            })
+            .to_string(),
+        };
+        if self.stdout {
+            writeln!(std::io::stdout(), "{}", line).ok();
+        } else {
+            writeln!(std::io::stderr(), "{}", line).ok();
+        }
+    }
+}
+
+/// `<server> <severity> [<logger>]: <message>`, with string data shown as is.
+/// Colours are left out when the output isn't a terminal.
+fn render_record(record: &RecordFields, data: &Value, colour: bool) -> String {
+    let message = match data {
+        Value::String(text) => text.clone(),
+        other => other.to_string(),
+    };
+    let logger = if record.logger.is_empty() {
+        String::new()
+    } else {
+        format!(" [{}]", record.logger)
+    };
+    if !colour {
+        return format!(
+            "{} {}{}: {}",
+            record.server, record.severity, logger, message
+        );
+    }
+
+    let severity = match record.severity.parse::<LoggingLevel>() {
+        Ok(LoggingLevel::Debug) => record.severity.dimmed().to_string(),
+        Ok(LoggingLevel::Info) => record.severity.green().to_string(),
+        Ok(LoggingLevel::Notice) => record.severity.blue().to_string(),
+        Ok(LoggingLevel::Warning) => record.severity.yellow().to_string(),
+        Ok(LoggingLevel::Error) => record.severity.red().to_string(),
+        _ => record.severity.red().bold().to_string(),
+    };
+    format!(
+        "{} {}{}: {}",
+        record.server.cyan(),
+        severity,
+        logger.dimmed(),
+        message
+    )
+}
+
+/// The fields of a server log record event
+#[derive(Default)]
+struct RecordFields {
+    server: String,
+    severity: String,
+    logger: String,
+    data: String,
+}
+
+impl Visit for RecordFields {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        let slot = match field.name() {
+            "server" => &mut self.server,
+            "severity" => &mut self.severity,
+            "logger" => &mut self.logger,
+            "data" => &mut self.data,
+            _ => return,
+        };
+        *slot = value.to_string();
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+        self.record_str(field, &format!("{:?}", value));
+    }
+}
@@ -1,52 +1,110 @@
 mod auth;
+mod cache;
 mod cli;
 mod commands;
 mod config;
+mod daemon;
+mod logging;
 mod mcp;
+mod paths;
 mod schema;
+mod server;
 
+use cache::ToolCache;
 use clap::Parser;
-use cli::{Cli, Commands};
-use config::ConfigStore;
+use cli::{CacheCommand, Cli, Commands, DaemonCommand, SessionArgs};
+use config::{ConfigStore, ConnectionSettings};
+use logging::{tracing_level, ServerLogLayer, SERVER_LOG_TARGET};
 use owo_colors::OwoColorize;
+use tracing_subscriber::filter::filter_fn;
+use tracing_subscriber::prelude::*;
 
 #[tokio::main]
 async fn main() {
     let cli = Cli::parse();
 
-    tracing_subscriber::fmt()
-        .with_env_filter(
-            tracing_subscriber::EnvFilter::from_default_env().add_directive(if cli.verbose {
-                tracing::Level::DEBUG.into()
-            } else {
-                tracing::Level::INFO.into()
-            }),
+    let mut filter =
+        tracing_subscriber::EnvFilter::from_default_env().add_directive(if cli.verbose {
+            tracing::Level::DEBUG.into()
+        } else {
+            tracing::Level::INFO.into()
+        });
+    // Show the server records asked for, unless RUST_LOG says otherwise
+    if let (Some(level), None) = (cli.session.server_log_level, std::env::var_os("RUST_LOG")) {
+        if let Ok(directive) = format!("{}={}", SERVER_LOG_TARGET, tracing_level(level)).parse() {
+            filter = filter.add_directive(directive);
+        }
+    }
+    let following = matches!(cli.command, Commands::Logs { follow: true, .. });
+    tracing_subscriber::registry()
+        .with(filter)
+        .with(
+            tracing_subscriber::fmt::layer()
+                .with_writer(std::io::stderr)
+                .with_filter(filter_fn(|metadata| metadata.target() != SERVER_LOG_TARGET)),
         )
+        .with(ServerLogLayer::new(cli.format).with_stdout(following))
         .init();
 
-    if let Err(err) = run(cli.verbose, cli.format, cli.command).await {
+    let overrides = ConnectionSettings {
+        timeout: cli.connection.timeout,
+        connect_timeout: cli.connection.connect_timeout,
+        retries: cli.connection.retries,
+        retry_tool_calls: cli.connection.retry_tool_calls,
+    };
+
+    if let Err(err) = run(
+        cli.verbose,
+        cli.format,
+        overrides,
+        cli.no_cache,
+        cli.session,
+        cli.command,
+    )
+    .await
+    {
         print_error(&err, cli.verbose);
-        std::process::exit(1);
+        std::process::exit(exit_code(&err));
     }
 }
 
-async fn run(_verbose: bool, format: cli::OutputFormat, command: Commands) -> anyhow::Result<()> {
+async fn run(
+    _verbose: bool,
+    format: cli::OutputFormat,
+    overrides: ConnectionSettings,
+    no_cache: bool,
+    session: SessionArgs,
+    command: Commands,
+) -> anyhow::Result<()> {
     // Support RELAY_CONFIG env var for testing
     let store = if let Ok(path) = std::env::var("RELAY_CONFIG") {
         ConfigStore::with_path(path.into())
     } else {
         ConfigStore::new()?
     };
+    // Layer a project .relay.yaml over the user config
+    let store = match std::env::current_dir() {
+        Ok(dir) => store.with_project_config_from(&dir),
+        Err(_) => store,
+    };
+    let store = store
+        .with_connection_overrides(overrides)
+        .with_auto_approve_sampling(session.auto_approve_sampling)
+        .with_elicit_answers(session.elicit.into_iter().collect())
+        .with_server_log_level(session.server_log_level);
+    let tool_cache = || ToolCache::new().map(|cache| cache.with_refresh(no_cache));
+    if !matches!(command, Commands::Trust | Commands::Daemon { .. }) {
+        commands::confirm_project_trust(&store)?;
+    }
 
     match command {
         Commands::Add {
             name,
             transport,
-            cmd,
-            url,
+            target,
             env,
         } => {
-            commands::add_server(&store, name, transport, cmd, url, env, format)?;
+            commands::add_server(&store, name, transport, target, env, format)?;
         }
         Commands::List => {
             commands::list_servers(&store, format)?;
@@ -54,14 +112,17 @@ async fn run(_verbose: bool, format: cli::OutputFormat, command: Commands) -> an
         Commands::Remove { name } => {
             commands::remove_server(&store, name, format)?;
         }
+        Commands::Trust => {
+            commands::trust_project(&store, format)?;
+        }
         Commands::Ping { name } => {
             commands::ping_server(&store, &name, format).await?;
         }
         Commands::Tools { server } => {
-            commands::list_tools(&store, server, format).await?;
+            commands::list_tools(&store, &tool_cache()?, server, format).await?;
         }
         Commands::Describe { server, tool } => {
-            commands::describe_tool(&store, server, &tool, format).await?;
+            commands::describe_tool(&store, &tool_cache()?, server, &tool, format).await?;
         }
         Commands::Run {
             server,
@@ -69,8 +130,73 @@ async fn run(_verbose: bool, format: cli::OutputFormat, command: Commands) -> an
             input_json,
             args,
         } => {
-            commands::run_tool(&store, server, &tool, input_json, args, format).await?;
+            commands::run_tool(
+                &store,
+                &tool_cache()?,
+                server,
+                &tool,
+                input_json,
+                args,
+                format,
+            )
+            .await?;
+        }
+        Commands::Resources { server } => {
+            commands::list_resources(&store, server, format).await?;
+        }
+        Commands::Read {
+            uri,
+            server,
+            vars,
+            output,
+        } => {
+            commands::read_resource(&store, server, &uri, vars, output, format).await?;
+        }
+        Commands::Prompts { server } => {
+            commands::list_prompts(&store, server, format).await?;
         }
+        Commands::Prompt { name, server, args } => {
+            commands::get_prompt(&store, server, &name, args, format).await?;
+        }
+        Commands::Import { path, overwrite } => {
+            commands::import_servers(&store, &path, overwrite, format)?;
+        }
+        Commands::Export { target } => {
+            commands::export_servers(&store, target)?;
+        }
+        Commands::Serve { http } => {
+            commands::serve(&store, http).await?;
+        }
+        Commands::Daemon { command } => match command {
+            DaemonCommand::Start { idle_timeout } => {
+                commands::daemon_start(&store, idle_timeout, format).await?;
+            }
+            DaemonCommand::Run { idle_timeout } => {
+                commands::daemon_run(&store, idle_timeout).await?;
+            }
+            DaemonCommand::Status => {
+                commands::daemon_status(&store, format).await?;
+            }
+            DaemonCommand::Stop => {
+                commands::daemon_stop(&store, format).await?;
+            }
+        },
+        Commands::Logs {
+            server,
+            lines,
+            follow,
+        } => {
+            if follow {
+                commands::follow_logs(&store, &server, format).await?;
+            } else {
+                commands::show_logs(&server, lines, format)?;
+            }
+        }
+        Commands::Cache { command } => match command {
+            CacheCommand::Clear => {
+                commands::clear_cache(&tool_cache()?, format)?;
+            }
+        },
         Commands::Update => {
             commands::update(format).await?;
         }
@@ -85,6 +211,21 @@ async fn run(_verbose: bool, format: cli::OutputFormat, command: Commands) -> an
     Ok(())
 }
 
+/// 130 after Ctrl-C and 124 after a timeout, as shells and `timeout(1)` use,
+/// so scripts can tell an abandoned request from a failed one
+fn exit_code(err: &anyhow::Error) -> i32 {
+    if err.is::<mcp::Cancelled>() {
+        130
+    } else if matches!(
+        err.downcast_ref::<mcp::transport::TransportError>(),
+        Some(mcp::transport::TransportError::Timeout(_))
+    ) {
+        124
+    } else {
+        1
+    }
+}
+
 fn print_error(err: &anyhow::Error, verbose: bool) {
     eprintln!("{} {}", "error:".red().bold(), err);
 
@@ -1,16 +1,35 @@
+use super::progress::ProgressRouter;
 use super::protocol::*;
+use super::roots::RootList;
 use super::transport::Transport;
 use anyhow::{bail, Context, Result};
+use serde::de::DeserializeOwned;
 use serde_json::{json, Value};
 use std::collections::HashMap;
+use std::future::Future;
 use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+use thiserror::Error;
 
-const PROTOCOL_VERSION: &str = "2024-11-05";
+/// How long to wait for a server to acknowledge a cancelled request
+pub const CANCEL_GRACE: Duration = Duration::from_secs(1);
 
+/// The request was interrupted and the server asked to cancel it
+#[derive(Debug, Error)]
+#[error("Cancelled")]
+pub struct Cancelled;
+
+/// An MCP client session. Initialization needs exclusive access; every other
+/// request takes `&self`, so an initialized client can be shared (e.g. via
+/// `Arc`) and used from several tasks at once.
 pub struct McpClient {
     transport: Box<dyn Transport>,
     request_id: AtomicU64,
     server_info: Option<ServerInfo>,
+    capabilities: Option<ServerCapabilities>,
+    client_capabilities: ClientCapabilities,
+    progress: ProgressRouter,
+    roots: RootList,
 }
 
 impl McpClient {
@@ -19,9 +38,31 @@ impl McpClient {
             transport,
             request_id: AtomicU64::new(1),
             server_info: None,
+            capabilities: None,
+            client_capabilities: ClientCapabilities::default(),
+            progress: ProgressRouter::default(),
+            roots: RootList::default(),
         }
     }
 
+    /// Advertise what the transport's message handler can answer
+    pub fn with_capabilities(mut self, capabilities: ClientCapabilities) -> Self {
+        self.client_capabilities = capabilities;
+        self
+    }
+
+    /// Use the router the transport's message handler delivers progress to
+    pub fn with_progress(mut self, progress: ProgressRouter) -> Self {
+        self.progress = progress;
+        self
+    }
+
+    /// Use the roots the transport's message handler answers `roots/list` from
+    pub fn with_roots(mut self, roots: RootList) -> Self {
+        self.roots = roots;
+        self
+    }
+
     fn next_id(&self) -> u64 {
         self.request_id.fetch_add(1, Ordering::SeqCst)
     }
@@ -30,7 +71,7 @@ impl McpClient {
     pub async fn initialize(&mut self) -> Result<InitializeResult> {
         let params = InitializeParams {
             protocol_version: PROTOCOL_VERSION.to_string(),
-            capabilities: ClientCapabilities::default(),
+            capabilities: self.client_capabilities.clone(),
             client_info: ClientInfo {
                 name: "relay".to_string(),
                 version: env!("CARGO_PKG_VERSION").to_string(),
@@ -56,12 +97,13 @@ impl McpClient {
         )?;
 
         self.server_info = Some(result.server_info.clone());
+        self.capabilities = Some(result.capabilities.clone());
 
         Ok(result)
     }
 
     /// List all available tools
-    pub async fn list_tools(&mut self) -> Result<Vec<Tool>> {
+    pub async fn list_tools(&self) -> Result<Vec<Tool>> {
         let mut all_tools = Vec::new();
         let mut cursor: Option<String> = None;
 
@@ -93,34 +135,217 @@ impl McpClient {
 
     /// Call a tool with arguments
     pub async fn call_tool(
-        &mut self,
+        &self,
         name: &str,
         arguments: HashMap<String, Value>,
     ) -> Result<ToolCallResult> {
         let params = ToolCallParams {
             name: name.to_string(),
             arguments,
+            meta: None,
         };
 
-        let req = JsonRpcRequest::new(
-            self.next_id(),
-            "tools/call",
-            Some(serde_json::to_value(params)?),
-        );
+        let req = self.new_request("tools/call", Some(serde_json::to_value(params)?));
+        tool_call_result(self.transport.request(req).await?)
+    }
+
+    /// Call a tool, passing the server's progress reports to `on_progress`.
+    /// If `interrupted` completes first, the server is asked to cancel the
+    /// call and given a moment to acknowledge before this fails with [`Cancelled`].
+    pub async fn call_tool_with_progress(
+        &self,
+        name: &str,
+        arguments: HashMap<String, Value>,
+        mut on_progress: impl FnMut(&ProgressParams),
+        interrupted: impl Future<Output = ()>,
+    ) -> Result<ToolCallResult> {
+        let mut progress = self.progress.subscribe();
+        let params = ToolCallParams {
+            name: name.to_string(),
+            arguments,
+            meta: Some(RequestMeta {
+                progress_token: Some(progress.token().clone()),
+            }),
+        };
+
+        let req = self.new_request("tools/call", Some(serde_json::to_value(params)?));
+        let id = req.id.clone();
+        let response = self.transport.request(req);
+        tokio::pin!(response, interrupted);
+
+        loop {
+            tokio::select! {
+                response = &mut response => return tool_call_result(response?),
+                Some(update) = progress.recv() => on_progress(&update),
+                () = &mut interrupted => break,
+            }
+        }
+
+        if let Err(e) = self.cancel(id, "Interrupted").await {
+            tracing::debug!("Failed to cancel tools/call: {:#}", e);
+        }
+        tokio::time::timeout(CANCEL_GRACE, response).await.ok();
+        Err(Cancelled.into())
+    }
+
+    /// List all available resources
+    pub async fn list_resources(&self) -> Result<Vec<Resource>> {
+        let mut all_resources = Vec::new();
+        let mut cursor: Option<String> = None;
+
+        loop {
+            let params = cursor.as_ref().map(|c| json!({ "cursor": c }));
+            let result: ResourcesListResult = self.send("resources/list", params).await?;
+
+            all_resources.extend(result.resources);
+
+            match result.next_cursor {
+                Some(next) => cursor = Some(next),
+                None => break,
+            }
+        }
+
+        Ok(all_resources)
+    }
+
+    /// List all available resource templates
+    pub async fn list_resource_templates(&self) -> Result<Vec<ResourceTemplate>> {
+        let mut all_templates = Vec::new();
+        let mut cursor: Option<String> = None;
+
+        loop {
+            let params = cursor.as_ref().map(|c| json!({ "cursor": c }));
+            let response = self
+                .send_request(self.new_request("resources/templates/list", params))
+                .await?;
+            // Templates are optional, and servers without any may not know the method
+            if response
+                .error
+                .as_ref()
+                .is_some_and(|error| error.code == METHOD_NOT_FOUND)
+            {
+                tracing::debug!("Server doesn't list resource templates");
+                break;
+            }
+            let result: ResourceTemplatesListResult =
+                parse_result("resources/templates/list", response)?;
+
+            all_templates.extend(result.resource_templates);
+
+            match result.next_cursor {
+                Some(next) => cursor = Some(next),
+                None => break,
+            }
+        }
+
+        Ok(all_templates)
+    }
+
+    /// Read the contents of a resource
+    pub async fn read_resource(&self, uri: &str) -> Result<ReadResourceResult> {
+        let params = ReadResourceParams {
+            uri: uri.to_string(),
+        };
+        self.send("resources/read", Some(serde_json::to_value(params)?))
+            .await
+    }
+
+    /// List all available prompts
+    pub async fn list_prompts(&self) -> Result<Vec<Prompt>> {
+        let mut all_prompts = Vec::new();
+        let mut cursor: Option<String> = None;
+
+        loop {
+            let params = cursor.as_ref().map(|c| json!({ "cursor": c }));
+            let result: PromptsListResult = self.send("prompts/list", params).await?;
 
+            all_prompts.extend(result.prompts);
+
+            match result.next_cursor {
+                Some(next) => cursor = Some(next),
+                None => break,
+            }
+        }
+
+        Ok(all_prompts)
+    }
+
+    /// Render a prompt with arguments
+    pub async fn get_prompt(
+        &self,
+        name: &str,
+        arguments: HashMap<String, String>,
+    ) -> Result<GetPromptResult> {
+        let params = GetPromptParams {
+            name: name.to_string(),
+            arguments,
+        };
+        self.send("prompts/get", Some(serde_json::to_value(params)?))
+            .await
+    }
+
+    /// Send a request and deserialize its result
+    async fn send<T: DeserializeOwned>(&self, method: &str, params: Option<Value>) -> Result<T> {
+        let req = JsonRpcRequest::new(self.next_id(), method, params);
         let response = self.transport.request(req).await?;
+        parse_result(method, response)
+    }
 
+    /// A request under this session's next id
+    pub fn new_request(&self, method: &str, params: Option<Value>) -> JsonRpcRequest {
+        JsonRpcRequest::new(self.next_id(), method, params)
+    }
+
+    /// Send a request and return the raw response, leaving any JSON-RPC
+    /// error for the caller to pass on
+    pub async fn send_request(&self, req: JsonRpcRequest) -> Result<JsonRpcResponse> {
+        self.transport.request(req).await
+    }
+
+    /// Ask the server to stop working on a request
+    pub async fn cancel(&self, id: RequestId, reason: &str) -> Result<()> {
+        self.transport
+            .notify(JsonRpcNotification::cancelled(id, reason))
+            .await
+    }
+
+    /// Ask the server to send log records at this level and above
+    pub async fn set_log_level(&self, level: LoggingLevel) -> Result<()> {
+        let params = serde_json::to_value(SetLevelParams { level })?;
+        let response = self
+            .send_request(self.new_request("logging/setLevel", Some(params)))
+            .await?;
         if let Some(error) = response.error {
-            bail!("tools/call failed: {} (code {})", error.message, error.code);
+            bail!(
+                "logging/setLevel failed: {} (code {})",
+                error.message,
+                error.code
+            );
         }
+        Ok(())
+    }
 
-        let result: ToolCallResult = serde_json::from_value(
-            response
-                .result
-                .context("No result in tools/call response")?,
-        )?;
+    /// The roots the session currently exposes
+    pub fn roots(&self) -> Vec<Root> {
+        self.roots.get()
+    }
 
-        Ok(result)
+    /// Change the session's roots, telling the server if they differ
+    pub async fn set_roots(&self, roots: Vec<Root>) -> Result<()> {
+        if !self.roots.replace(roots) {
+            return Ok(());
+        }
+        self.transport
+            .notify(JsonRpcNotification::new(
+                "notifications/roots/list_changed",
+                None,
+            ))
+            .await
+    }
+
+    /// Where the transport's message handler delivers progress for this session
+    pub fn progress(&self) -> &ProgressRouter {
+        &self.progress
     }
 
     /// Get server info (after initialization)
@@ -128,8 +353,40 @@ impl McpClient {
         self.server_info.as_ref()
     }
 
+    /// Get server capabilities (after initialization)
+    pub fn capabilities(&self) -> Option<&ServerCapabilities> {
+        self.capabilities.as_ref()
+    }
+
     /// Close the connection
-    pub async fn close(&mut self) -> Result<()> {
+    pub async fn close(&self) -> Result<()> {
         self.transport.close().await
     }
 }
+
+/// The typed result of a response, or its JSON-RPC error
+fn parse_result<T: DeserializeOwned>(method: &str, response: JsonRpcResponse) -> Result<T> {
+    if let Some(error) = response.error {
+        bail!("{} failed: {} (code {})", method, error.message, error.code);
+    }
+
+    let result = response
+        .result
+        .with_context(|| format!("No result in {} response", method))?;
+
+    Ok(serde_json::from_value(result)?)
+}
+
+fn tool_call_result(response: JsonRpcResponse) -> Result<ToolCallResult> {
+    if let Some(error) = response.error {
+        bail!("tools/call failed: {} (code {})", error.message, error.code);
+    }
+
+    let result: ToolCallResult = serde_json::from_value(
+        response
+            .result
+            .context("No result in tools/call response")?,
+    )?;
+
+    Ok(result)
+}
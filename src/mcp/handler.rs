@@ -0,0 +1,38 @@
+use super::protocol::*;
+use async_trait::async_trait;
+use serde_json::json;
+
+/// Handles messages initiated by the server: notifications and server-to-client requests
+#[async_trait]
+pub trait MessageHandler: Send + Sync {
+    /// Called for every notification the server sends
+    async fn on_notification(&self, notification: JsonRpcNotification);
+
+    /// Called for every request the server sends; the returned response is sent back
+    async fn on_request(&self, request: JsonRpcRequest) -> JsonRpcResponse;
+}
+
+/// Answers `ping` and logs notifications; rejects every other server request
+pub struct DefaultHandler;
+
+#[async_trait]
+impl MessageHandler for DefaultHandler {
+    async fn on_notification(&self, notification: JsonRpcNotification) {
+        tracing::debug!(
+            method = %notification.method,
+            params = ?notification.params,
+            "server notification"
+        );
+    }
+
+    async fn on_request(&self, request: JsonRpcRequest) -> JsonRpcResponse {
+        match request.method.as_str() {
+            "ping" => JsonRpcResponse::success(request.id, json!({})),
+            method => JsonRpcResponse::failure(
+                request.id,
+                METHOD_NOT_FOUND,
+                format!("Method not supported by relay: {}", method),
+            ),
+        }
+    }
+}
@@ -1,6 +1,14 @@
 mod client;
+mod handler;
+mod progress;
 mod protocol;
+mod roots;
 pub mod transport;
+mod uri_template;
 
-pub use client::McpClient;
+pub use client::{Cancelled, McpClient, CANCEL_GRACE};
+pub use handler::*;
+pub use progress::*;
 pub use protocol::*;
+pub use roots::*;
+pub use uri_template::*;
@@ -0,0 +1,135 @@
+use super::protocol::{JsonRpcNotification, ProgressParams};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use tokio::sync::mpsc;
+
+type Listeners = Arc<Mutex<HashMap<String, mpsc::UnboundedSender<ProgressParams>>>>;
+
+static NEXT_TOKEN: AtomicU64 = AtomicU64::new(1);
+
+/// Hands each `notifications/progress` to whoever is waiting on its token.
+/// Clones share their subscriptions, so the session's message handler and
+/// its client can each hold one.
+#[derive(Clone, Default)]
+pub struct ProgressRouter {
+    listeners: Listeners,
+}
+
+impl ProgressRouter {
+    /// Listen for progress under a fresh token, unique across relay processes
+    /// so a daemon session shared by several callers can tell them apart
+    pub fn subscribe(&self) -> ProgressSubscription {
+        let n = NEXT_TOKEN.fetch_add(1, Ordering::SeqCst);
+        self.listen(Value::String(format!("relay-{}-{}", std::process::id(), n)))
+    }
+
+    /// Listen for progress reported under a token chosen elsewhere
+    pub fn listen(&self, token: Value) -> ProgressSubscription {
+        let (sender, receiver) = mpsc::unbounded_channel();
+        let key = token.to_string();
+        self.listeners.lock().unwrap().insert(key.clone(), sender);
+        ProgressSubscription {
+            token,
+            key,
+            receiver,
+            listeners: self.listeners.clone(),
+        }
+    }
+
+    /// Deliver a progress notification. Returns false if it isn't one or
+    /// nobody is listening for its token.
+    pub fn route(&self, notification: &JsonRpcNotification) -> bool {
+        if notification.method != "notifications/progress" {
+            return false;
+        }
+        let Some(params) = notification
+            .params
+            .clone()
+            .and_then(|params| serde_json::from_value::<ProgressParams>(params).ok())
+        else {
+            return false;
+        };
+
+        let listeners = self.listeners.lock().unwrap();
+        match listeners.get(&params.progress_token.to_string()) {
+            Some(sender) => sender.send(params).is_ok(),
+            None => false,
+        }
+    }
+}
+
+/// Progress updates for one request, until dropped
+pub struct ProgressSubscription {
+    token: Value,
+    key: String,
+    receiver: mpsc::UnboundedReceiver<ProgressParams>,
+    listeners: Listeners,
+}
+
+impl ProgressSubscription {
+    /// The token to send as the request's `_meta.progressToken`
+    pub fn token(&self) -> &Value {
+        &self.token
+    }
+
+    pub async fn recv(&mut self) -> Option<ProgressParams> {
+        self.receiver.recv().await
+    }
+}
+
+impl Drop for ProgressSubscription {
+    fn drop(&mut self) {
+        self.listeners.lock().unwrap().remove(&self.key);
+    }
+}
+
+/// The progress token a request's params ask the server to report under
+pub fn progress_token(params: Option<&Value>) -> Option<Value> {
+    params?.pointer("/_meta/progressToken").cloned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn progress(token: Value, progress: f64) -> JsonRpcNotification {
+        JsonRpcNotification::new(
+            "notifications/progress",
+            Some(json!({ "progressToken": token, "progress": progress, "total": 10 })),
+        )
+    }
+
+    #[tokio::test]
+    async fn test_routes_by_token_until_dropped() {
+        let router = ProgressRouter::default();
+        let mut first = router.subscribe();
+        let mut second = router.listen(json!(7));
+        assert_ne!(first.token(), router.subscribe().token());
+
+        assert!(router.route(&progress(first.token().clone(), 1.0)));
+        assert!(router.route(&progress(json!(7), 2.0)));
+        // A string token is not the same token as the number
+        assert!(!router.route(&progress(json!("7"), 3.0)));
+        assert!(!router.route(&JsonRpcNotification::new("notifications/message", None)));
+
+        assert_eq!(first.recv().await.unwrap().progress, 1.0);
+        let update = second.recv().await.unwrap();
+        assert_eq!(update.progress, 2.0);
+        assert_eq!(update.total, Some(10.0));
+
+        let token = first.token().clone();
+        drop(first);
+        assert!(!router.route(&progress(token, 4.0)));
+    }
+
+    #[test]
+    fn test_progress_token_from_params() {
+        let params = json!({ "name": "export", "_meta": { "progressToken": "abc" } });
+        assert_eq!(progress_token(Some(&params)), Some(json!("abc")));
+        assert_eq!(progress_token(Some(&json!({ "name": "export" }))), None);
+        assert_eq!(progress_token(None), None);
+    }
+}
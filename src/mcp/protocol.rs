@@ -2,8 +2,11 @@ use serde::{Deserialize, Serialize};
 use serde_json::Value;
 use std::collections::HashMap;
 
+/// MCP protocol revision spoken by relay (as client and as server)
+pub const PROTOCOL_VERSION: &str = "2024-11-05";
+
 /// JSON-RPC 2.0 request ID (can be string, number, or null per spec)
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum RequestId {
     Number(u64),
     String(String),
@@ -138,6 +141,28 @@ pub struct JsonRpcResponse {
 
 #[allow(dead_code)]
 impl JsonRpcResponse {
+    pub fn success(id: RequestId, result: Value) -> Self {
+        Self {
+            jsonrpc: "2.0".to_string(),
+            id,
+            result: Some(result),
+            error: None,
+        }
+    }
+
+    pub fn failure(id: RequestId, code: i32, message: impl Into<String>) -> Self {
+        Self {
+            jsonrpc: "2.0".to_string(),
+            id,
+            result: None,
+            error: Some(JsonRpcError {
+                code,
+                message: message.into(),
+                data: None,
+            }),
+        }
+    }
+
     pub fn is_success(&self) -> bool {
         self.result.is_some() && self.error.is_none()
     }
@@ -155,6 +180,54 @@ pub struct JsonRpcError {
     pub data: Option<Value>,
 }
 
+/// JSON-RPC 2.0 Notification (a request without an id)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JsonRpcNotification {
+    pub jsonrpc: String,
+    pub method: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub params: Option<Value>,
+}
+
+impl JsonRpcNotification {
+    pub fn new(method: impl Into<String>, params: Option<Value>) -> Self {
+        Self {
+            jsonrpc: "2.0".to_string(),
+            method: method.into(),
+            params,
+        }
+    }
+
+    /// Ask the peer to stop working on a request it has not yet answered
+    pub fn cancelled(request_id: RequestId, reason: &str) -> Self {
+        let params = CancelledParams {
+            request_id,
+            reason: Some(reason.to_string()),
+        };
+        Self::new(
+            "notifications/cancelled",
+            Some(serde_json::to_value(params).unwrap_or_default()),
+        )
+    }
+}
+
+/// Any JSON-RPC 2.0 message a peer can send
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum JsonRpcMessage {
+    Request(JsonRpcRequest),
+    Response(JsonRpcResponse),
+    Notification(JsonRpcNotification),
+}
+
+/// JSON-RPC 2.0 error codes
+pub const PARSE_ERROR: i32 = -32700;
+pub const METHOD_NOT_FOUND: i32 = -32601;
+pub const INVALID_PARAMS: i32 = -32602;
+pub const INTERNAL_ERROR: i32 = -32603;
+/// MCP: the user declined a server's request
+pub const USER_REJECTED: i32 = -1;
+
 /// MCP Initialize params
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -165,7 +238,27 @@ pub struct InitializeParams {
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
-pub struct ClientCapabilities {}
+pub struct ClientCapabilities {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sampling: Option<SamplingCapability>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub roots: Option<RootsCapability>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub elicitation: Option<ElicitationCapability>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct SamplingCapability {}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct ElicitationCapability {}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct RootsCapability {
+    #[serde(default)]
+    pub list_changed: bool,
+}
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ClientInfo {
@@ -186,8 +279,17 @@ pub struct InitializeResult {
 pub struct ServerCapabilities {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub tools: Option<ToolsCapability>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub resources: Option<ResourcesCapability>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub prompts: Option<PromptsCapability>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub logging: Option<LoggingCapability>,
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct LoggingCapability {}
+
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
 #[serde(rename_all = "camelCase")]
 pub struct ToolsCapability {
@@ -195,6 +297,22 @@ pub struct ToolsCapability {
     pub list_changed: bool,
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct ResourcesCapability {
+    #[serde(default)]
+    pub subscribe: bool,
+    #[serde(default)]
+    pub list_changed: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct PromptsCapability {
+    #[serde(default)]
+    pub list_changed: bool,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ServerInfo {
     pub name: String,
@@ -228,6 +346,38 @@ pub struct ToolCallParams {
     pub name: String,
     #[serde(default)]
     pub arguments: HashMap<String, Value>,
+    #[serde(rename = "_meta", default, skip_serializing_if = "Option::is_none")]
+    pub meta: Option<RequestMeta>,
+}
+
+/// Metadata a client may attach to a request
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct RequestMeta {
+    /// Token the server quotes in `notifications/progress` for this request
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub progress_token: Option<Value>,
+}
+
+/// MCP notifications/progress params
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProgressParams {
+    pub progress_token: Value,
+    pub progress: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub total: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+}
+
+/// MCP notifications/cancelled params
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CancelledParams {
+    pub request_id: RequestId,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
 }
 
 /// MCP tools/call result
@@ -253,11 +403,292 @@ pub enum ContentItem {
         #[serde(rename = "mimeType")]
         mime_type: String,
     },
+    Audio {
+        data: String,
+        #[serde(rename = "mimeType")]
+        mime_type: String,
+    },
     Resource {
         resource: Value,
     },
 }
 
+/// MCP Resource definition
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Resource {
+    pub uri: String,
+    pub name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mime_type: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub size: Option<u64>,
+}
+
+/// MCP resources/list result
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ResourcesListResult {
+    pub resources: Vec<Resource>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub next_cursor: Option<String>,
+}
+
+/// MCP Resource template definition (RFC 6570 URI template)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ResourceTemplate {
+    pub uri_template: String,
+    pub name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mime_type: Option<String>,
+}
+
+/// MCP resources/templates/list result
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ResourceTemplatesListResult {
+    pub resource_templates: Vec<ResourceTemplate>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub next_cursor: Option<String>,
+}
+
+/// MCP resources/read params
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReadResourceParams {
+    pub uri: String,
+}
+
+/// MCP resources/read result
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReadResourceResult {
+    pub contents: Vec<ResourceContents>,
+}
+
+/// Contents of a resource, either text or base64-encoded binary
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum ResourceContents {
+    Text {
+        uri: String,
+        #[serde(rename = "mimeType", skip_serializing_if = "Option::is_none")]
+        mime_type: Option<String>,
+        text: String,
+    },
+    Blob {
+        uri: String,
+        #[serde(rename = "mimeType", skip_serializing_if = "Option::is_none")]
+        mime_type: Option<String>,
+        blob: String,
+    },
+}
+
+/// MCP Prompt definition
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Prompt {
+    pub name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(default)]
+    pub arguments: Vec<PromptArgument>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PromptArgument {
+    pub name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(default)]
+    pub required: bool,
+}
+
+/// MCP prompts/list result
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PromptsListResult {
+    pub prompts: Vec<Prompt>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub next_cursor: Option<String>,
+}
+
+/// MCP prompts/get params
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GetPromptParams {
+    pub name: String,
+    #[serde(default)]
+    pub arguments: HashMap<String, String>,
+}
+
+/// MCP prompts/get result
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GetPromptResult {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    pub messages: Vec<PromptMessage>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    User,
+    Assistant,
+}
+
+impl std::fmt::Display for Role {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Role::User => write!(f, "user"),
+            Role::Assistant => write!(f, "assistant"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PromptMessage {
+    pub role: Role,
+    pub content: ContentItem,
+}
+
+/// MCP sampling/createMessage params
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CreateMessageParams {
+    pub messages: Vec<PromptMessage>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub system_prompt: Option<String>,
+    pub max_tokens: u64,
+}
+
+/// MCP sampling/createMessage result
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CreateMessageResult {
+    pub role: Role,
+    pub content: ContentItem,
+    pub model: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stop_reason: Option<String>,
+}
+
+/// Severity of a server's log record, from least to most severe
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LoggingLevel {
+    Debug,
+    Info,
+    Notice,
+    Warning,
+    Error,
+    Critical,
+    Alert,
+    Emergency,
+}
+
+impl LoggingLevel {
+    pub const ALL: [LoggingLevel; 8] = [
+        LoggingLevel::Debug,
+        LoggingLevel::Info,
+        LoggingLevel::Notice,
+        LoggingLevel::Warning,
+        LoggingLevel::Error,
+        LoggingLevel::Critical,
+        LoggingLevel::Alert,
+        LoggingLevel::Emergency,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            LoggingLevel::Debug => "debug",
+            LoggingLevel::Info => "info",
+            LoggingLevel::Notice => "notice",
+            LoggingLevel::Warning => "warning",
+            LoggingLevel::Error => "error",
+            LoggingLevel::Critical => "critical",
+            LoggingLevel::Alert => "alert",
+            LoggingLevel::Emergency => "emergency",
+        }
+    }
+}
+
+impl std::fmt::Display for LoggingLevel {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl std::str::FromStr for LoggingLevel {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .into_iter()
+            .find(|level| level.as_str() == s.to_lowercase())
+            .ok_or_else(|| {
+                let levels: Vec<&str> = Self::ALL.iter().map(LoggingLevel::as_str).collect();
+                format!("expected one of {}", levels.join(", "))
+            })
+    }
+}
+
+/// MCP logging/setLevel params
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SetLevelParams {
+    pub level: LoggingLevel,
+}
+
+/// MCP notifications/message params: one log record from the server
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LoggingMessageParams {
+    pub level: LoggingLevel,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub logger: Option<String>,
+    pub data: Value,
+}
+
+/// A directory the client lets the server work in
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Root {
+    pub uri: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+}
+
+/// MCP elicitation/create params
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ElicitParams {
+    pub message: String,
+    pub requested_schema: Value,
+}
+
+/// How the user responded to an elicitation request
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ElicitAction {
+    Accept,
+    Decline,
+    Cancel,
+}
+
+/// MCP elicitation/create result
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ElicitResult {
+    pub action: ElicitAction,
+    /// The submitted values, present only when accepted
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub content: Option<serde_json::Map<String, Value>>,
+}
+
+/// MCP roots/list result
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ListRootsResult {
+    pub roots: Vec<Root>,
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -396,4 +827,131 @@ mod tests {
             _ => panic!("Expected Image variant"),
         }
     }
+
+    #[test]
+    fn test_resources_list_result_deserialization() {
+        let json = r#"{
+            "resources": [
+                {
+                    "uri": "file:///project/README.md",
+                    "name": "README.md",
+                    "mimeType": "text/markdown"
+                }
+            ],
+            "nextCursor": "page-2"
+        }"#;
+
+        let result: ResourcesListResult = serde_json::from_str(json).unwrap();
+        assert_eq!(result.resources.len(), 1);
+        assert_eq!(result.resources[0].uri, "file:///project/README.md");
+        assert_eq!(
+            result.resources[0].mime_type.as_deref(),
+            Some("text/markdown")
+        );
+        assert_eq!(result.next_cursor.as_deref(), Some("page-2"));
+    }
+
+    #[test]
+    fn test_resource_templates_list_result_deserialization() {
+        let json = r#"{
+            "resourceTemplates": [
+                { "uriTemplate": "file:///{path}", "name": "Project files" }
+            ]
+        }"#;
+
+        let result: ResourceTemplatesListResult = serde_json::from_str(json).unwrap();
+        assert_eq!(result.resource_templates[0].uri_template, "file:///{path}");
+        assert!(result.next_cursor.is_none());
+    }
+
+    #[test]
+    fn test_resource_contents_text_and_blob() {
+        let json = r#"{
+            "contents": [
+                { "uri": "file:///a.txt", "mimeType": "text/plain", "text": "hello" },
+                { "uri": "file:///b.png", "mimeType": "image/png", "blob": "aGVsbG8=" }
+            ]
+        }"#;
+
+        let result: ReadResourceResult = serde_json::from_str(json).unwrap();
+        assert!(
+            matches!(&result.contents[0], ResourceContents::Text { text, .. } if text == "hello")
+        );
+        assert!(
+            matches!(&result.contents[1], ResourceContents::Blob { blob, .. } if blob == "aGVsbG8=")
+        );
+    }
+
+    #[test]
+    fn test_prompts_list_result_deserialization() {
+        let json = r#"{
+            "prompts": [
+                {
+                    "name": "code_review",
+                    "description": "Review a piece of code",
+                    "arguments": [
+                        { "name": "code", "required": true },
+                        { "name": "language" }
+                    ]
+                },
+                { "name": "summarize" }
+            ]
+        }"#;
+
+        let result: PromptsListResult = serde_json::from_str(json).unwrap();
+        assert_eq!(result.prompts.len(), 2);
+        assert!(result.prompts[0].arguments[0].required);
+        assert!(!result.prompts[0].arguments[1].required);
+        assert!(result.prompts[1].arguments.is_empty());
+    }
+
+    #[test]
+    fn test_get_prompt_result_deserialization() {
+        let json = r#"{
+            "description": "Code review prompt",
+            "messages": [
+                { "role": "user", "content": { "type": "text", "text": "Review this" } },
+                { "role": "assistant", "content": { "type": "audio", "data": "AAA=", "mimeType": "audio/wav" } }
+            ]
+        }"#;
+
+        let result: GetPromptResult = serde_json::from_str(json).unwrap();
+        assert_eq!(result.messages[0].role, Role::User);
+        assert_eq!(result.messages[1].role, Role::Assistant);
+        assert!(matches!(
+            result.messages[1].content,
+            ContentItem::Audio { .. }
+        ));
+    }
+
+    #[test]
+    fn test_jsonrpc_message_routing() {
+        let request: JsonRpcMessage =
+            serde_json::from_str(r#"{"jsonrpc": "2.0", "id": "srv-1", "method": "ping"}"#).unwrap();
+        assert!(matches!(request, JsonRpcMessage::Request(r) if r.method == "ping"));
+
+        let response: JsonRpcMessage =
+            serde_json::from_str(r#"{"jsonrpc": "2.0", "id": 7, "result": {}}"#).unwrap();
+        assert!(matches!(response, JsonRpcMessage::Response(r) if r.id == RequestId::Number(7)));
+
+        let notification: JsonRpcMessage = serde_json::from_str(
+            r#"{"jsonrpc": "2.0", "method": "notifications/progress", "params": {"progress": 1}}"#,
+        )
+        .unwrap();
+        assert!(matches!(
+            notification,
+            JsonRpcMessage::Notification(n) if n.method == "notifications/progress"
+        ));
+    }
+
+    #[test]
+    fn test_logging_level_order_and_parse() {
+        assert!(LoggingLevel::Warning > LoggingLevel::Info);
+        assert_eq!("Notice".parse::<LoggingLevel>(), Ok(LoggingLevel::Notice));
+        assert!("verbose".parse::<LoggingLevel>().is_err());
+        assert_eq!(
+            serde_json::to_value(LoggingLevel::Emergency).unwrap(),
+            "emergency"
+        );
+    }
 }
@@ -0,0 +1,54 @@
+use super::protocol::Root;
+use std::sync::{Arc, RwLock};
+
+/// The roots a session exposes to its server. Clones share the list, so the
+/// message handler answering `roots/list` sees updates made through the
+/// client.
+#[derive(Clone, Default)]
+pub struct RootList {
+    roots: Arc<RwLock<Vec<Root>>>,
+}
+
+impl RootList {
+    pub fn new(roots: Vec<Root>) -> Self {
+        Self {
+            roots: Arc::new(RwLock::new(roots)),
+        }
+    }
+
+    pub fn get(&self) -> Vec<Root> {
+        self.roots.read().unwrap().clone()
+    }
+
+    /// Swap in a new list. Returns whether it differs from the old one.
+    pub fn replace(&self, roots: Vec<Root>) -> bool {
+        let mut current = self.roots.write().unwrap();
+        if *current == roots {
+            return false;
+        }
+        *current = roots;
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn root(uri: &str) -> Root {
+        Root {
+            uri: uri.to_string(),
+            name: None,
+        }
+    }
+
+    #[test]
+    fn test_replace_reports_changes_to_clones() {
+        let roots = RootList::new(vec![root("file:///a")]);
+        let shared = roots.clone();
+
+        assert!(!roots.replace(vec![root("file:///a")]));
+        assert!(roots.replace(vec![root("file:///b")]));
+        assert_eq!(shared.get(), vec![root("file:///b")]);
+    }
+}
@@ -0,0 +1,288 @@
+//! `text/event-stream` parsing, following the WHATWG EventSource spec
+//! (https://html.spec.whatwg.org/multipage/server-sent-events.html)
+
+use std::time::Duration;
+
+/// A dispatched server-sent event
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SseEvent {
+    /// Event type; `message` when the server didn't name it
+    pub event: String,
+    /// Data lines joined with `\n`
+    pub data: String,
+    /// Last event id seen on the stream when this event was dispatched
+    pub id: Option<String>,
+    /// Reconnection delay requested by the server
+    pub retry: Option<Duration>,
+}
+
+/// Incremental event stream decoder: feed it chunks as they arrive and it
+/// returns the events completed so far. Lines may end in `\n`, `\r\n` or
+/// `\r`, and chunk boundaries may fall anywhere, even inside a character.
+#[derive(Debug, Default)]
+pub struct EventStreamDecoder {
+    buffer: Vec<u8>,
+    started: bool,
+    /// The previous chunk ended in `\r`, so a leading `\n` belongs to it
+    after_cr: bool,
+    event: String,
+    data: String,
+    last_event_id: Option<String>,
+    retry: Option<Duration>,
+}
+
+impl EventStreamDecoder {
+    /// The id of the last `id:` field, which persists across events
+    pub fn last_event_id(&self) -> Option<&str> {
+        self.last_event_id.as_deref()
+    }
+
+    pub fn push(&mut self, chunk: &[u8]) -> Vec<SseEvent> {
+        let mut chunk = chunk;
+        if self.after_cr && !chunk.is_empty() {
+            self.after_cr = false;
+            chunk = chunk.strip_prefix(b"\n").unwrap_or(chunk);
+        }
+        self.buffer.extend_from_slice(chunk);
+
+        if !self.started {
+            // A byte order mark can only be told apart once three bytes are in
+            if self.buffer.len() < 3 && b"\xEF\xBB\xBF".starts_with(&self.buffer) {
+                return Vec::new();
+            }
+            self.started = true;
+            if self.buffer.starts_with(b"\xEF\xBB\xBF") {
+                self.buffer.drain(..3);
+            }
+        }
+
+        let mut events = Vec::new();
+        let mut start = 0;
+        while let Some(offset) = self.buffer[start..]
+            .iter()
+            .position(|&b| b == b'\n' || b == b'\r')
+        {
+            let end = start + offset;
+            let line = String::from_utf8_lossy(&self.buffer[start..end]).into_owned();
+
+            start = end + 1;
+            if self.buffer[end] == b'\r' {
+                match self.buffer.get(start) {
+                    Some(b'\n') => start += 1,
+                    Some(_) => {}
+                    None => self.after_cr = true,
+                }
+            }
+
+            if let Some(event) = self.process_line(&line) {
+                events.push(event);
+            }
+        }
+        self.buffer.drain(..start);
+
+        events
+    }
+
+    fn process_line(&mut self, line: &str) -> Option<SseEvent> {
+        if line.is_empty() {
+            return self.dispatch();
+        }
+        if line.starts_with(':') {
+            // Comment, typically a keep-alive
+            return None;
+        }
+
+        let (field, value) = line.split_once(':').unwrap_or((line, ""));
+        let value = value.strip_prefix(' ').unwrap_or(value);
+        match field {
+            "event" => self.event = value.to_string(),
+            "data" => {
+                self.data.push_str(value);
+                self.data.push('\n');
+            }
+            "id" if !value.contains('\0') => self.last_event_id = Some(value.to_string()),
+            "retry" if !value.is_empty() && value.bytes().all(|b| b.is_ascii_digit()) => {
+                self.retry = value.parse().ok().map(Duration::from_millis);
+            }
+            _ => {}
+        }
+        None
+    }
+
+    fn dispatch(&mut self) -> Option<SseEvent> {
+        let event = std::mem::take(&mut self.event);
+        if self.data.is_empty() {
+            return None;
+        }
+
+        let mut data = std::mem::take(&mut self.data);
+        data.pop();
+        Some(SseEvent {
+            event: if event.is_empty() {
+                "message".to_string()
+            } else {
+                event
+            },
+            data,
+            id: self.last_event_id.clone(),
+            retry: self.retry,
+        })
+    }
+}
+
+/// Parse a complete event stream body. A final event without the closing
+/// blank line is still dispatched.
+pub fn parse_events(body: &str) -> Vec<SseEvent> {
+    let mut decoder = EventStreamDecoder::default();
+    let mut events = decoder.push(body.as_bytes());
+    events.extend(decoder.push(b"\n\n"));
+    events
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn event(event: &str, data: &str, id: Option<&str>) -> SseEvent {
+        SseEvent {
+            event: event.to_string(),
+            data: data.to_string(),
+            id: id.map(str::to_string),
+            retry: None,
+        }
+    }
+
+    #[test]
+    fn test_fields() {
+        let body = ": keep-alive\n\
+                    event: endpoint\n\
+                    data:/messages?sessionId=1\n\n\
+                    id: 7\n\
+                    data: first\n\
+                    data:  second\n\
+                    unknown: field\n\
+                    data\n\n\
+                    data: third\n\n";
+        assert_eq!(
+            parse_events(body),
+            [
+                event("endpoint", "/messages?sessionId=1", None),
+                event("message", "first\n second\n", Some("7")),
+                event("message", "third", Some("7")),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_line_endings_and_bom() {
+        let body = "\u{FEFF}data: a\r\n\r\ndata: b\r\rdata: c\n\n";
+        let data: Vec<_> = parse_events(body).into_iter().map(|e| e.data).collect();
+        assert_eq!(data, ["a", "b", "c"]);
+    }
+
+    #[test]
+    fn test_events_without_data_are_not_dispatched() {
+        let mut decoder = EventStreamDecoder::default();
+        assert!(decoder
+            .push(b"event: ping\nid: 3\nretry: 1500\n\n")
+            .is_empty());
+        assert_eq!(decoder.last_event_id(), Some("3"));
+
+        let events = decoder.push(b"data: x\n\n");
+        assert_eq!(events[0].event, "message");
+        assert_eq!(events[0].retry, Some(Duration::from_millis(1500)));
+    }
+
+    /// Encode random events with every allowed variation of the syntax,
+    /// split the bytes at random points and check they decode unchanged
+    #[test]
+    fn test_fuzz_encodings_and_chunking() {
+        for seed in 0..500 {
+            let mut rng = fastrand::Rng::with_seed(seed);
+            let mut expected = Vec::new();
+            let mut encoded = String::new();
+            let mut id = None;
+
+            for _ in 0..rng.usize(1..6) {
+                let name = if rng.bool() { "endpoint" } else { "message" };
+                // An id field sets the id of every later event too
+                let new_id = rng.bool().then(|| rng.u32(..1000).to_string());
+                if new_id.is_some() {
+                    id = new_id.clone();
+                }
+                let data = (0..rng.usize(1..4))
+                    .map(|_| fuzz::text(&mut rng))
+                    .collect::<Vec<_>>()
+                    .join("\n");
+                let event_field = (name == "endpoint").then_some(name);
+                encoded.push_str(&fuzz::encode(
+                    &mut rng,
+                    event_field,
+                    new_id.as_deref(),
+                    &data,
+                ));
+                expected.push(event(name, &data, id.as_deref()));
+            }
+
+            let mut decoder = EventStreamDecoder::default();
+            let events: Vec<_> = fuzz::split(&mut rng, encoded.as_bytes())
+                .iter()
+                .flat_map(|chunk| decoder.push(chunk))
+                .collect();
+
+            assert_eq!(events, expected, "seed {}: {:?}", seed, encoded);
+        }
+    }
+}
+
+/// Random but valid encodings of events, shared by the transport tests
+#[cfg(test)]
+pub(crate) mod fuzz {
+    use fastrand::Rng;
+
+    /// Encode an event with a random line ending, optional comment and
+    /// optional space after each field's colon
+    pub fn encode(rng: &mut Rng, event: Option<&str>, id: Option<&str>, data: &str) -> String {
+        let newline = ["\n", "\r\n", "\r"][rng.usize(..3)];
+        let space = if rng.bool() { " " } else { "" };
+        let mut encoded = String::new();
+
+        if rng.bool() {
+            encoded.push_str(&format!(":comment{}", newline));
+        }
+        if let Some(event) = event {
+            encoded.push_str(&format!("event:{}{}{}", space, event, newline));
+        }
+        if let Some(id) = id {
+            encoded.push_str(&format!("id:{}{}{}", space, id, newline));
+        }
+        for line in data.split('\n') {
+            // A value starting with a space needs the separator space
+            let space = if line.starts_with(' ') { " " } else { space };
+            encoded.push_str(&format!("data:{}{}{}", space, line, newline));
+        }
+        encoded.push_str(newline);
+        encoded
+    }
+
+    /// A short line of text mixing multi-byte characters with the ones the
+    /// syntax gives meaning to
+    pub fn text(rng: &mut Rng) -> String {
+        let len = rng.usize(0..12);
+        std::iter::repeat_with(|| ['a', 'é', '{', ':', ' ', '"', '✓'][rng.usize(..7)])
+            .take(len)
+            .collect()
+    }
+
+    /// Split bytes into chunks of one to eight bytes, ignoring character
+    /// boundaries
+    pub fn split(rng: &mut Rng, mut bytes: &[u8]) -> Vec<Vec<u8>> {
+        let mut chunks = Vec::new();
+        while !bytes.is_empty() {
+            let (chunk, rest) = bytes.split_at(rng.usize(1..=bytes.len().min(8)));
+            chunks.push(chunk.to_vec());
+            bytes = rest;
+        }
+        chunks
+    }
+}
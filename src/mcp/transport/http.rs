@@ -1,9 +1,26 @@
-use super::Transport;
-use crate::mcp::{JsonRpcRequest, JsonRpcResponse};
-use anyhow::{anyhow, Context, Result};
+use super::{
+    parse_events, EventStreamDecoder, PendingRequests, TokenRefresher, Transport, TransportError,
+};
+use crate::auth::authorization_value;
+use crate::mcp::{
+    DefaultHandler, JsonRpcMessage, JsonRpcNotification, JsonRpcRequest, JsonRpcResponse,
+    MessageHandler, RequestId,
+};
+use anyhow::{anyhow, bail, Context, Result};
 use async_trait::async_trait;
-use reqwest::Client;
+use futures_util::StreamExt;
+use reqwest::{Client, RequestBuilder, Response, StatusCode};
+use serde::Serialize;
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::Duration;
 use thiserror::Error;
+use tokio::task::JoinHandle;
+
+/// How many times to resume a response stream that drops before the response
+const MAX_RESUMES: usize = 3;
+
+/// Consecutive failures after which the GET stream is given up
+const MAX_LISTEN_FAILURES: usize = 5;
 
 #[derive(Debug, Error)]
 pub enum HttpTransportError {
@@ -11,84 +28,188 @@ pub enum HttpTransportError {
     AuthRequired { server_name: String },
 }
 
+/// Streamable HTTP transport: each message is POSTed and answered with either
+/// a JSON body or an event stream, which may carry notifications and server
+/// requests ahead of the response
 pub struct HttpTransport {
+    inner: Arc<Inner>,
+    listener: Mutex<Option<JoinHandle<()>>>,
+}
+
+struct Inner {
     client: Client,
     url: String,
-    access_token: Option<String>,
+    access_token: RwLock<Option<String>>,
+    token_refresher: Option<Arc<dyn TokenRefresher>>,
     server_name: String,
-    session_id: Option<String>,
+    session_id: RwLock<Option<String>>,
+    pending: PendingRequests,
+    handler: Arc<dyn MessageHandler>,
+    listen: bool,
 }
 
-impl HttpTransport {
-    pub fn new(url: String, server_name: String) -> Self {
-        Self {
-            client: Client::new(),
-            url,
-            access_token: None,
-            server_name,
-            session_id: None,
-        }
+/// Collects an [`HttpTransport`]'s settings before it is shared with the task
+/// that listens for server messages
+pub struct HttpTransportBuilder {
+    inner: Inner,
+}
+
+impl HttpTransportBuilder {
+    /// Use a preconfigured client, e.g. one that sends extra headers
+    pub fn with_client(mut self, client: Client) -> Self {
+        self.inner.client = client;
+        self
     }
 
     pub fn with_token(mut self, token: Option<String>) -> Self {
-        self.access_token = token;
+        self.inner.access_token = RwLock::new(token);
         self
     }
+
+    /// Refresh the token and retry once when the server rejects it mid-session
+    pub fn with_token_refresher(mut self, refresher: Option<Arc<dyn TokenRefresher>>) -> Self {
+        self.inner.token_refresher = refresher;
+        self
+    }
+
+    /// After initialization, keep a GET stream open for messages the server
+    /// sends on its own
+    pub fn with_listen(mut self, listen: bool) -> Self {
+        self.inner.listen = listen;
+        self
+    }
+
+    /// Handle notifications and requests the server sends on its own
+    pub fn with_handler(mut self, handler: Arc<dyn MessageHandler>) -> Self {
+        self.inner.handler = handler;
+        self
+    }
+
+    pub fn build(self) -> HttpTransport {
+        HttpTransport {
+            inner: Arc::new(self.inner),
+            listener: Mutex::new(None),
+        }
+    }
 }
 
-#[async_trait]
-impl Transport for HttpTransport {
-    async fn request(&mut self, req: JsonRpcRequest) -> Result<JsonRpcResponse> {
-        let mut request = self
-            .client
-            .post(&self.url)
-            .header("Accept", "application/json, text/event-stream");
+impl HttpTransport {
+    pub fn builder(url: String, server_name: String) -> HttpTransportBuilder {
+        HttpTransportBuilder {
+            inner: Inner {
+                client: Client::new(),
+                url,
+                access_token: RwLock::new(None),
+                token_refresher: None,
+                server_name,
+                session_id: RwLock::new(None),
+                pending: PendingRequests::default(),
+                handler: Arc::new(DefaultHandler),
+                listen: false,
+            },
+        }
+    }
 
-        if let Some(token) = &self.access_token {
-            // Support different auth formats: if token already has a prefix, use as-is
-            let auth_value = if token.starts_with("Bearer ")
-                || token.starts_with("token ")
-                || token.starts_with("Basic ")
-            {
-                token.clone()
-            } else {
-                format!("Bearer {}", token)
-            };
-            request = request.header("Authorization", auth_value);
+    fn start_listening(&self) {
+        let mut listener = self.listener.lock().unwrap();
+        if listener.is_none() {
+            *listener = Some(tokio::spawn(self.inner.clone().listen()));
+        }
+    }
+}
+
+impl Inner {
+    /// Add the access token and session id to a request
+    fn authorize(&self, mut request: RequestBuilder) -> RequestBuilder {
+        if let Some(token) = self.access_token.read().unwrap().as_ref() {
+            request = request.header("Authorization", authorization_value(token));
         }
 
         // Include session ID for Streamable HTTP transport
-        if let Some(session_id) = &self.session_id {
+        if let Some(session_id) = self.session_id.read().unwrap().as_ref() {
             request = request.header("Mcp-Session-Id", session_id);
         }
 
-        let response = request
-            .json(&req)
+        request
+    }
+
+    fn auth_required(&self) -> anyhow::Error {
+        HttpTransportError::AuthRequired {
+            server_name: self.server_name.clone(),
+        }
+        .into()
+    }
+
+    /// POST a JSON-RPC message, refreshing the access token and retrying once
+    /// if the server rejects it
+    async fn send(&self, message: &(impl Serialize + Sync)) -> Result<Response> {
+        let result = self.send_once(message).await;
+
+        let Some(refresher) = &self.token_refresher else {
+            return result;
+        };
+        match result {
+            Err(e) if e.downcast_ref::<HttpTransportError>().is_some() => {
+                let stale = self
+                    .access_token
+                    .read()
+                    .unwrap()
+                    .clone()
+                    .unwrap_or_default();
+                match refresher.refresh(&stale).await {
+                    Ok(token) => {
+                        *self.access_token.write().unwrap() = Some(token);
+                        self.send_once(message).await
+                    }
+                    Err(refresh_err) => {
+                        tracing::debug!("Token refresh failed: {:#}", refresh_err);
+                        Err(e)
+                    }
+                }
+            }
+            other => other,
+        }
+    }
+
+    async fn send_once(&self, message: &impl Serialize) -> Result<Response> {
+        let request = self
+            .client
+            .post(&self.url)
+            .header("Accept", "application/json, text/event-stream");
+        let response = self
+            .authorize(request)
+            .json(message)
             .send()
             .await
-            .with_context(|| format!("Failed to send request to {}", self.url))?;
+            .map_err(|e| TransportError::from_send_error(e, &self.url))?;
 
         // Extract and store session ID from response headers
         if let Some(session_id) = response.headers().get("mcp-session-id") {
             if let Ok(id) = session_id.to_str() {
-                self.session_id = Some(id.to_string());
+                *self.session_id.write().unwrap() = Some(id.to_string());
             }
         }
 
+        self.check_status(response).await
+    }
+
+    /// Turn error statuses into errors and pass successful responses through
+    async fn check_status(&self, response: Response) -> Result<Response> {
         // Check for authentication errors
-        if response.status() == reqwest::StatusCode::UNAUTHORIZED {
-            return Err(HttpTransportError::AuthRequired {
-                server_name: self.server_name.clone(),
-            }
-            .into());
+        if response.status() == StatusCode::UNAUTHORIZED {
+            return Err(self.auth_required());
         }
 
         // Check for other HTTP errors with OAuth error format
         if !response.status().is_success() {
             let status = response.status();
+            let headers = response.headers().clone();
             let body = response.text().await.unwrap_or_default();
 
-            // Try to parse as OAuth error
+            // Try to parse client errors as OAuth errors
+            if !status.is_client_error() {
+                return Err(TransportError::from_status(status, &headers, &body));
+            }
             if let Ok(oauth_error) = serde_json::from_str::<serde_json::Value>(&body) {
                 if let Some(error) = oauth_error.get("error").and_then(|e| e.as_str()) {
                     let description = oauth_error
@@ -97,41 +218,393 @@ impl Transport for HttpTransport {
                         .unwrap_or("");
 
                     if error == "invalid_token" {
-                        return Err(HttpTransportError::AuthRequired {
-                            server_name: self.server_name.clone(),
-                        }
-                        .into());
+                        return Err(self.auth_required());
                     }
 
                     return Err(anyhow!("{}: {}", error, description));
                 }
             }
 
-            return Err(anyhow!("HTTP error {}: {}", status, body));
+            return Err(TransportError::from_status(status, &headers, &body));
+        }
+
+        Ok(response)
+    }
+
+    /// Open a GET event stream, resuming after `last_event_id` if given
+    async fn open_event_stream(&self, last_event_id: Option<&str>) -> Result<Response> {
+        let mut request = self
+            .client
+            .get(&self.url)
+            .header("Accept", "text/event-stream");
+        if let Some(id) = last_event_id {
+            request = request.header("Last-Event-ID", id);
+        }
+
+        let response = self
+            .authorize(request)
+            .send()
+            .await
+            .map_err(|e| TransportError::from_send_error(e, &self.url))?;
+        self.check_status(response).await
+    }
+
+    /// POST a request and route everything the server answers with, until
+    /// the response to it has been delivered
+    async fn exchange(&self, req: &JsonRpcRequest) -> Result<()> {
+        let response = self.send(req).await?;
+
+        if !is_event_stream(&response) {
+            let body = response
+                .text()
+                .await
+                .context("Failed to read response body")?;
+            for message in parse_body(&body)? {
+                self.deliver(message).await;
+            }
+            return Ok(());
         }
 
-        // Read response as text to handle both plain JSON and SSE format
-        let body = response
-            .text()
+        let mut last_event_id = None;
+        if self
+            .read_events(response, &mut last_event_id, Some(&req.id))
             .await
-            .context("Failed to read response body")?;
+        {
+            return Ok(());
+        }
+
+        // The stream dropped before the response arrived: pick up where it left off
+        for _ in 0..MAX_RESUMES {
+            let Some(event_id) = last_event_id.clone() else {
+                break;
+            };
+            tracing::debug!("Resuming event stream after event {}", event_id);
+            let response = self.open_event_stream(Some(&event_id)).await?;
+            if self
+                .read_events(response, &mut last_event_id, Some(&req.id))
+                .await
+            {
+                break;
+            }
+        }
+        Ok(())
+    }
 
-        // Handle SSE-formatted responses (Streamable HTTP transport)
-        // These may come as "data: {...}" or "event: message\ndata: {...}"
-        let json_str = body
-            .lines()
-            .find(|line| line.starts_with("data: "))
-            .and_then(|line| line.strip_prefix("data: "))
-            .map(|s| s.trim())
-            .unwrap_or_else(|| body.trim());
+    /// Deliver the messages of an event stream as they arrive, remembering
+    /// the last event id. Returns true once the response to `until` has been
+    /// delivered and false if the stream ends first.
+    async fn read_events(
+        &self,
+        response: Response,
+        last_event_id: &mut Option<String>,
+        until: Option<&RequestId>,
+    ) -> bool {
+        let mut stream = response.bytes_stream();
+        let mut decoder = EventStreamDecoder::default();
 
-        let response: JsonRpcResponse =
-            serde_json::from_str(json_str).context("Failed to parse JSON-RPC response")?;
+        while let Some(chunk) = stream.next().await {
+            let chunk = match chunk {
+                Ok(chunk) => chunk,
+                Err(e) => {
+                    tracing::debug!("Event stream dropped: {}", e);
+                    return false;
+                }
+            };
+
+            let events = decoder.push(&chunk);
+            if let Some(id) = decoder.last_event_id() {
+                *last_event_id = Some(id.to_string());
+            }
+            for event in events {
+                if event.data.trim().is_empty() {
+                    continue;
+                }
+                let message = match serde_json::from_str(&event.data) {
+                    Ok(message) => message,
+                    Err(e) => {
+                        tracing::debug!("Ignoring malformed event ({}): {}", e, event.data);
+                        continue;
+                    }
+                };
+                let delivered = self.deliver(message).await;
+                if until.is_some() && delivered.as_ref() == until {
+                    return true;
+                }
+            }
+        }
+
+        false
+    }
+
+    /// Route one message: responses to their waiting callers, notifications
+    /// to the handler and server requests answered with a POST. Returns the
+    /// id of a delivered response.
+    async fn deliver(&self, message: JsonRpcMessage) -> Option<RequestId> {
+        match message {
+            JsonRpcMessage::Response(response) => {
+                let id = response.id.clone();
+                if self.pending.complete(response) {
+                    return Some(id);
+                }
+                tracing::debug!("Ignoring response for unknown request id {:?}", id);
+            }
+            JsonRpcMessage::Notification(notification) => {
+                self.handler.on_notification(notification).await;
+            }
+            JsonRpcMessage::Request(request) => {
+                let response = self.handler.on_request(request).await;
+                if let Err(e) = self.send(&response).await {
+                    tracing::debug!("Failed to answer server request: {:#}", e);
+                }
+            }
+        }
+        None
+    }
+
+    /// Keep a GET stream open for server-initiated messages, resuming with
+    /// `Last-Event-ID` whenever it drops
+    async fn listen(self: Arc<Self>) {
+        let mut last_event_id = None;
+        let mut failures = 0;
+
+        loop {
+            match self.open_event_stream(last_event_id.as_deref()).await {
+                Ok(response) => {
+                    failures = 0;
+                    self.read_events(response, &mut last_event_id, None).await;
+                }
+                Err(e) => {
+                    // 405 means the server doesn't offer a GET stream at all
+                    if let Some(TransportError::HttpStatus { status, .. }) = e.downcast_ref() {
+                        if *status == StatusCode::METHOD_NOT_ALLOWED {
+                            tracing::debug!("Server does not offer a GET event stream");
+                            return;
+                        }
+                    }
+                    failures += 1;
+                    if failures >= MAX_LISTEN_FAILURES {
+                        tracing::debug!("Giving up on the GET event stream: {:#}", e);
+                        return;
+                    }
+                }
+            }
+            tokio::time::sleep(Duration::from_secs(1)).await;
+        }
+    }
+
+    /// Tell the server the session is over
+    async fn end_session(&self) {
+        if self.session_id.read().unwrap().is_none() {
+            return;
+        }
+        // Servers that don't allow clients to end sessions answer 405
+        match self.authorize(self.client.delete(&self.url)).send().await {
+            Ok(response) => tracing::debug!("Session DELETE returned {}", response.status()),
+            Err(e) => tracing::debug!("Failed to end session: {}", e),
+        }
+    }
+}
+
+fn is_event_stream(response: &Response) -> bool {
+    response
+        .headers()
+        .get(reqwest::header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.starts_with("text/event-stream"))
+}
+
+/// Parse a response body, which is either plain JSON (a single message or a
+/// batch) or an SSE stream of `data:` lines (Streamable HTTP transport)
+pub(super) fn parse_body(body: &str) -> Result<Vec<JsonRpcMessage>> {
+    let body = body.trim();
+    if body.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let is_sse = body.lines().any(|line| {
+        ["data:", "event:", "id:", ":"]
+            .iter()
+            .any(|field| line.starts_with(field))
+    });
+    if !is_sse {
+        if body.starts_with('[') {
+            return serde_json::from_str(body).context("Failed to parse JSON-RPC response");
+        }
+        let message = serde_json::from_str(body).context("Failed to parse JSON-RPC response")?;
+        return Ok(vec![message]);
+    }
 
+    // These may come as "data: {...}" or "event: message\ndata: {...}"
+    parse_events(body)
+        .into_iter()
+        .filter(|event| !event.data.trim().is_empty())
+        .map(|event| serde_json::from_str(&event.data).context("Failed to parse JSON-RPC response"))
+        .collect()
+}
+
+#[async_trait]
+impl Transport for HttpTransport {
+    async fn request(&self, req: JsonRpcRequest) -> Result<JsonRpcResponse> {
+        let id = req.id.clone();
+        let mut rx = self.inner.pending.register(id.clone())?;
+
+        if let Err(e) = self.inner.exchange(&req).await {
+            self.inner.pending.cancel(&id);
+            return Err(e);
+        }
+
+        let response = match rx.try_recv() {
+            Ok(response) => response,
+            Err(_) => {
+                self.inner.pending.cancel(&id);
+                bail!("No response received for request {:?}", id)
+            }
+        };
+
+        if self.inner.listen && req.method == "initialize" {
+            self.start_listening();
+        }
         Ok(response)
     }
 
-    async fn close(&mut self) -> Result<()> {
+    /// The server acknowledges notifications with 202 and no body
+    async fn notify(&self, notification: JsonRpcNotification) -> Result<()> {
+        self.inner.send(&notification).await?;
         Ok(())
     }
+
+    async fn close(&self) -> Result<()> {
+        if let Some(listener) = self.listener.lock().unwrap().take() {
+            listener.abort();
+        }
+        self.inner.pending.close();
+        self.inner.end_session().await;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::event_stream::fuzz;
+    use super::*;
+    use axum::body::Body;
+    use axum::extract::State;
+    use axum::http::header;
+    use axum::response::{IntoResponse, Response as AxumResponse};
+    use axum::Json;
+    use serde_json::{json, Value};
+
+    #[test]
+    fn test_parse_plain_json_body() {
+        let messages = parse_body(r#"{"jsonrpc":"2.0","id":1,"result":{}}"#).unwrap();
+        assert!(matches!(messages.as_slice(), [JsonRpcMessage::Response(_)]));
+    }
+
+    #[test]
+    fn test_parse_sse_body_with_notifications() {
+        let body = "event: message\n\
+                    data: {\"jsonrpc\":\"2.0\",\"method\":\"notifications/progress\",\"params\":{}}\n\n\
+                    event: message\n\
+                    data: {\"jsonrpc\":\"2.0\",\"id\":1,\"result\":{}}\n\n";
+        let messages = parse_body(body).unwrap();
+        assert!(matches!(
+            messages.as_slice(),
+            [JsonRpcMessage::Notification(_), JsonRpcMessage::Response(_)]
+        ));
+    }
+
+    /// Answer each POST with an event stream of a few notifications and the
+    /// response echoing the request's `text` param, every event encoded at
+    /// random and sent in random chunks
+    async fn fuzz_post(
+        State(rng): State<Arc<Mutex<fastrand::Rng>>>,
+        Json(request): Json<Value>,
+    ) -> AxumResponse {
+        let mut rng = rng.lock().unwrap();
+        let text = &request["params"]["text"];
+        let count = rng.usize(..3);
+        let notification = json!({
+            "jsonrpc": "2.0",
+            "method": "notifications/message",
+            "params": {"text": text},
+        });
+        let response = json!({
+            "jsonrpc": "2.0",
+            "id": request["id"],
+            "result": {"text": text, "notifications": count},
+        });
+
+        let mut encoded = String::new();
+        for message in std::iter::repeat_n(&notification, count).chain([&response]) {
+            let data = if rng.bool() {
+                serde_json::to_string_pretty(message).unwrap()
+            } else {
+                message.to_string()
+            };
+            let event = rng.bool().then_some("message");
+            let id = rng.bool().then(|| rng.u32(..1000).to_string());
+            encoded.push_str(&fuzz::encode(&mut rng, event, id.as_deref(), &data));
+        }
+        let chunks = fuzz::split(&mut rng, encoded.as_bytes())
+            .into_iter()
+            .map(Ok::<_, std::convert::Infallible>);
+        (
+            [(header::CONTENT_TYPE, "text/event-stream")],
+            Body::from_stream(futures_util::stream::iter(chunks)),
+        )
+            .into_response()
+    }
+
+    #[derive(Default)]
+    struct RecordingHandler {
+        notifications: Mutex<Vec<Value>>,
+    }
+
+    #[async_trait]
+    impl MessageHandler for RecordingHandler {
+        async fn on_notification(&self, notification: JsonRpcNotification) {
+            let params = notification.params.unwrap_or_default();
+            self.notifications.lock().unwrap().push(params);
+        }
+
+        async fn on_request(&self, request: JsonRpcRequest) -> JsonRpcResponse {
+            DefaultHandler.on_request(request).await
+        }
+    }
+
+    #[tokio::test]
+    async fn test_fuzz_event_stream_responses() {
+        for seed in 0..20 {
+            let rng = Arc::new(Mutex::new(fastrand::Rng::with_seed(seed)));
+            let app = axum::Router::new()
+                .route("/mcp", axum::routing::post(fuzz_post))
+                .with_state(rng);
+            let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let url = format!("http://{}/mcp", listener.local_addr().unwrap());
+            let serve = tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+
+            let handler = Arc::new(RecordingHandler::default());
+            let transport = HttpTransport::builder(url, "test".to_string())
+                .with_handler(handler.clone())
+                .build();
+            let mut rng = fastrand::Rng::with_seed(seed);
+            for id in 0..3u64 {
+                let text = fuzz::text(&mut rng);
+                let params = json!({ "text": text });
+                let response = transport
+                    .request(JsonRpcRequest::new(id, "echo", Some(params.clone())))
+                    .await
+                    .unwrap_or_else(|e| panic!("seed {}: {:#}", seed, e));
+                let result = response.result.unwrap();
+                assert_eq!(result["text"], text, "seed {}", seed);
+
+                let notifications = std::mem::take(&mut *handler.notifications.lock().unwrap());
+                let count = result["notifications"].as_u64().unwrap() as usize;
+                assert_eq!(notifications, vec![params; count], "seed {}", seed);
+            }
+
+            transport.close().await.unwrap();
+            serve.abort();
+        }
+    }
 }
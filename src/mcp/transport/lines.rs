@@ -0,0 +1,150 @@
+use super::PendingRequests;
+use crate::mcp::{
+    JsonRpcMessage, JsonRpcNotification, JsonRpcRequest, JsonRpcResponse, MessageHandler,
+};
+use anyhow::{anyhow, Result};
+use std::sync::Arc;
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::sync::Mutex;
+use tokio::task::JoinHandle;
+
+/// Taken (and so dropped) on close, which is what closes a child's stdin
+type Writer = Arc<Mutex<Option<Box<dyn AsyncWrite + Send + Unpin>>>>;
+
+/// Newline-delimited JSON-RPC over a byte stream pair, as spoken over stdio
+/// and local sockets
+pub struct LineConnection {
+    writer: Writer,
+    pending: PendingRequests,
+    reader: JoinHandle<()>,
+}
+
+impl LineConnection {
+    /// Start routing messages read from `reader`; requests go to `writer`
+    /// and server-initiated messages to `handler`
+    pub fn start(
+        reader: impl AsyncBufRead + Send + Unpin + 'static,
+        writer: impl AsyncWrite + Send + Unpin + 'static,
+        handler: Arc<dyn MessageHandler>,
+    ) -> Self {
+        let writer: Writer = Arc::new(Mutex::new(Some(Box::new(writer))));
+        let pending = PendingRequests::default();
+        let reader = tokio::spawn(read_loop(reader, writer.clone(), pending.clone(), handler));
+
+        Self {
+            writer,
+            pending,
+            reader,
+        }
+    }
+
+    pub async fn request(&self, req: JsonRpcRequest) -> Result<JsonRpcResponse> {
+        let id = req.id.clone();
+        let rx = self
+            .pending
+            .register(id.clone())
+            .map_err(|_| anyhow!("Server closed connection unexpectedly"))?;
+
+        // Write request as JSON line
+        let json = serde_json::to_string(&req)?;
+        if let Err(e) = write_line(&self.writer, &json).await {
+            self.pending.cancel(&id);
+            return Err(e.context("Failed to write request to server"));
+        }
+
+        rx.await
+            .map_err(|_| anyhow!("Server closed connection unexpectedly"))
+    }
+
+    pub async fn notify(&self, notification: &JsonRpcNotification) -> Result<()> {
+        let json = serde_json::to_string(notification)?;
+        write_line(&self.writer, &json)
+            .await
+            .map_err(|e| e.context("Failed to write notification to server"))
+    }
+
+    /// Stop reading and fail every in-flight request
+    pub async fn close(&self) {
+        self.reader.abort();
+        self.pending.close();
+        if let Some(mut writer) = self.writer.lock().await.take() {
+            writer.shutdown().await.ok();
+        }
+    }
+}
+
+async fn write_line(writer: &Writer, json: &str) -> Result<()> {
+    let mut writer = writer.lock().await;
+    let writer = writer
+        .as_mut()
+        .ok_or_else(|| anyhow!("Connection closed"))?;
+    writer.write_all(json.as_bytes()).await?;
+    writer.write_all(b"\n").await?;
+    writer.flush().await?;
+    Ok(())
+}
+
+/// Read messages line by line and route them by JSON-RPC id
+async fn read_loop(
+    mut reader: impl AsyncBufRead + Unpin,
+    writer: Writer,
+    pending: PendingRequests,
+    handler: Arc<dyn MessageHandler>,
+) {
+    let mut line = String::new();
+
+    loop {
+        line.clear();
+        match reader.read_line(&mut line).await {
+            Ok(0) => break,
+            Ok(_) => {}
+            Err(e) => {
+                tracing::debug!("Failed to read from server: {}", e);
+                break;
+            }
+        }
+
+        let trimmed = line.trim();
+        if trimmed.is_empty() {
+            continue;
+        }
+
+        let message: JsonRpcMessage = match serde_json::from_str(trimmed) {
+            Ok(message) => message,
+            Err(e) => {
+                tracing::debug!("Ignoring unparseable server output ({}): {}", e, trimmed);
+                continue;
+            }
+        };
+
+        match message {
+            JsonRpcMessage::Response(response) => {
+                let id = response.id.clone();
+                if !pending.complete(response) {
+                    tracing::debug!("Ignoring response for unknown request id {:?}", id);
+                }
+            }
+            JsonRpcMessage::Notification(notification) => {
+                handler.on_notification(notification).await;
+            }
+            JsonRpcMessage::Request(request) => {
+                // Answer in the background so a slow handler doesn't stall responses
+                let writer = writer.clone();
+                let handler = handler.clone();
+                tokio::spawn(async move {
+                    let response = handler.on_request(request).await;
+                    match serde_json::to_string(&response) {
+                        Ok(json) => {
+                            if let Err(e) = write_line(&writer, &json).await {
+                                tracing::debug!("Failed to answer server request: {}", e);
+                            }
+                        }
+                        Err(e) => tracing::debug!("Failed to serialize response: {}", e),
+                    }
+                });
+            }
+        }
+    }
+
+    pending.close();
+}
@@ -1,9 +1,21 @@
+mod event_stream;
 mod http;
+mod lines;
+mod pending;
+mod retry;
+mod socket;
 mod sse;
 mod stdio;
 mod traits;
+mod websocket;
 
+pub use event_stream::{parse_events, EventStreamDecoder, SseEvent};
 pub use http::HttpTransport;
+use lines::LineConnection;
+pub use pending::PendingRequests;
+pub use retry::{RetryPolicy, RetryingTransport, TransportError};
+pub use socket::SocketTransport;
 pub use sse::SseTransport;
-pub use stdio::StdioTransport;
+pub use stdio::{StderrCapture, StdioTransport};
 pub use traits::*;
+pub use websocket::{is_websocket_url, WebSocketTransport};
@@ -0,0 +1,112 @@
+use crate::mcp::{JsonRpcResponse, RequestId};
+use anyhow::{bail, Result};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use tokio::sync::oneshot;
+
+type Waiters = HashMap<RequestId, oneshot::Sender<JsonRpcResponse>>;
+
+/// In-flight requests awaiting a response, keyed by request id.
+///
+/// Once closed, every waiter is dropped (so callers see the connection as gone)
+/// and new registrations are rejected.
+#[derive(Clone)]
+pub struct PendingRequests {
+    waiters: Arc<Mutex<Option<Waiters>>>,
+}
+
+impl Default for PendingRequests {
+    fn default() -> Self {
+        Self {
+            waiters: Arc::new(Mutex::new(Some(HashMap::new()))),
+        }
+    }
+}
+
+impl PendingRequests {
+    /// Register interest in the response to `id`
+    pub fn register(&self, id: RequestId) -> Result<oneshot::Receiver<JsonRpcResponse>> {
+        let mut waiters = self.waiters.lock().unwrap();
+        let Some(waiters) = waiters.as_mut() else {
+            bail!("Connection closed");
+        };
+        let (tx, rx) = oneshot::channel();
+        waiters.insert(id, tx);
+        Ok(rx)
+    }
+
+    /// Deliver a response to its waiter. Returns false if nobody was waiting for it.
+    pub fn complete(&self, response: JsonRpcResponse) -> bool {
+        let waiter = self
+            .waiters
+            .lock()
+            .unwrap()
+            .as_mut()
+            .and_then(|w| w.remove(&response.id));
+        match waiter {
+            Some(tx) => tx.send(response).is_ok(),
+            None => false,
+        }
+    }
+
+    /// Stop waiting for `id` (e.g. when sending the request failed)
+    pub fn cancel(&self, id: &RequestId) {
+        if let Some(waiters) = self.waiters.lock().unwrap().as_mut() {
+            waiters.remove(id);
+        }
+    }
+
+    /// Fail every in-flight request and reject new ones
+    pub fn close(&self) {
+        self.waiters.lock().unwrap().take();
+    }
+
+    /// Close if no request is in flight. Returns whether it is closed.
+    pub fn close_if_idle(&self) -> bool {
+        let mut waiters = self.waiters.lock().unwrap();
+        if waiters.as_ref().is_some_and(|w| !w.is_empty()) {
+            return false;
+        }
+        waiters.take();
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_responses_routed_by_id() {
+        let pending = PendingRequests::default();
+        let rx1 = pending.register(RequestId::Number(1)).unwrap();
+        let rx2 = pending.register(RequestId::Number(2)).unwrap();
+
+        assert!(pending.complete(JsonRpcResponse::success(
+            RequestId::Number(2),
+            serde_json::json!("second")
+        )));
+        assert!(pending.complete(JsonRpcResponse::success(
+            RequestId::Number(1),
+            serde_json::json!("first")
+        )));
+        assert!(!pending.complete(JsonRpcResponse::success(
+            RequestId::Number(3),
+            serde_json::json!("unknown")
+        )));
+
+        assert_eq!(rx1.await.unwrap().result, Some(serde_json::json!("first")));
+        assert_eq!(rx2.await.unwrap().result, Some(serde_json::json!("second")));
+    }
+
+    #[tokio::test]
+    async fn test_close_fails_waiters() {
+        let pending = PendingRequests::default();
+        let rx = pending.register(RequestId::Number(1)).unwrap();
+
+        pending.close();
+
+        assert!(rx.await.is_err());
+        assert!(pending.register(RequestId::Number(2)).is_err());
+    }
+}
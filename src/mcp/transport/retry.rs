@@ -0,0 +1,438 @@
+use super::Transport;
+use crate::mcp::{JsonRpcNotification, JsonRpcRequest, JsonRpcResponse, CANCEL_GRACE};
+use anyhow::Result;
+use async_trait::async_trait;
+use reqwest::header::{HeaderMap, RETRY_AFTER};
+use reqwest::StatusCode;
+use std::time::{Duration, Instant, SystemTime};
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum TransportError {
+    /// The server took longer than the configured timeout to answer
+    #[error("Request timed out after {0:?}")]
+    Timeout(Duration),
+    /// Every attempt failed with a retryable error
+    #[error("Request failed after {attempts} attempts: {last:#}")]
+    RetriesExhausted { attempts: u32, last: anyhow::Error },
+    /// An HTTP error status that isn't worth retrying
+    #[error("HTTP error {status}: {body}")]
+    HttpStatus { status: StatusCode, body: String },
+    /// A transient failure (connection error, 5xx or 429) that may be retried
+    #[error("{message}")]
+    Retryable {
+        message: String,
+        retry_after: Option<Duration>,
+    },
+}
+
+impl TransportError {
+    /// Classify a failure to send an HTTP request: connection problems are transient
+    pub fn from_send_error(error: reqwest::Error, url: &str) -> anyhow::Error {
+        if error.is_connect() || error.is_timeout() {
+            TransportError::Retryable {
+                message: format!("Failed to connect to {}: {}", url, error),
+                retry_after: None,
+            }
+            .into()
+        } else {
+            anyhow::Error::new(error).context(format!("Failed to send request to {}", url))
+        }
+    }
+
+    /// Classify an HTTP error status: 5xx and 429 are transient
+    pub fn from_status(status: StatusCode, headers: &HeaderMap, body: &str) -> anyhow::Error {
+        if status.is_server_error() || status == StatusCode::TOO_MANY_REQUESTS {
+            TransportError::Retryable {
+                message: format!("HTTP error {}: {}", status, body),
+                retry_after: retry_after(headers),
+            }
+            .into()
+        } else {
+            TransportError::HttpStatus {
+                status,
+                body: body.to_string(),
+            }
+            .into()
+        }
+    }
+}
+
+/// Parse a `Retry-After` header given in seconds or as an HTTP date
+fn retry_after(headers: &HeaderMap) -> Option<Duration> {
+    let value = headers.get(RETRY_AFTER)?.to_str().ok()?.trim();
+    if let Ok(seconds) = value.parse::<u64>() {
+        return Some(Duration::from_secs(seconds));
+    }
+    let date = httpdate::parse_http_date(value).ok()?;
+    Some(
+        date.duration_since(SystemTime::now())
+            .unwrap_or(Duration::ZERO),
+    )
+}
+
+/// When and how often to retry requests
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    /// Retries after the first attempt
+    pub retries: u32,
+    /// Limit for each attempt. A retry isn't waited for if the wait alone
+    /// would take the request past it.
+    pub timeout: Option<Duration>,
+    /// Also retry `tools/call`, which may not be safe to repeat
+    pub retry_tool_calls: bool,
+    /// Delay before the first retry, doubled for each one after
+    pub base_delay: Duration,
+    /// Upper bound for the delay, including one asked for with Retry-After
+    pub max_delay: Duration,
+    /// How long to wait for the server to wind down a request that timed out
+    pub cancel_grace: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            retries: 2,
+            timeout: None,
+            retry_tool_calls: false,
+            base_delay: Duration::from_millis(250),
+            max_delay: Duration::from_secs(10),
+            cancel_grace: CANCEL_GRACE,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Exponential backoff with jitter, unless the server asked for a specific delay
+    fn delay(&self, attempt: u32, retry_after: Option<Duration>) -> Duration {
+        if let Some(retry_after) = retry_after {
+            return retry_after.min(self.max_delay);
+        }
+        let delay = self
+            .base_delay
+            .saturating_mul(2u32.saturating_pow(attempt))
+            .min(self.max_delay);
+        // Pick uniformly from the upper half so concurrent clients spread out
+        let half = delay / 2;
+        half + half.mul_f64(fastrand::f64())
+    }
+
+    fn may_retry(&self, req: &JsonRpcRequest) -> bool {
+        req.method != "tools/call" || self.retry_tool_calls
+    }
+}
+
+/// Wraps a transport with per-request timeouts and retries of transient failures
+pub struct RetryingTransport {
+    inner: Box<dyn Transport>,
+    policy: RetryPolicy,
+}
+
+impl RetryingTransport {
+    pub fn new(inner: Box<dyn Transport>, policy: RetryPolicy) -> Self {
+        Self { inner, policy }
+    }
+
+    /// Send a request once. If it times out, tell the server to cancel it
+    /// and give it a moment to acknowledge before giving up.
+    async fn attempt(&self, req: &JsonRpcRequest) -> Result<JsonRpcResponse> {
+        let Some(timeout) = self.policy.timeout else {
+            return self.inner.request(req.clone()).await;
+        };
+        let response = self.inner.request(req.clone());
+        tokio::pin!(response);
+        if let Ok(result) = tokio::time::timeout(timeout, &mut response).await {
+            return result;
+        }
+
+        // The spec forbids cancelling initialize
+        if req.method != "initialize" {
+            let reason = format!("Timed out after {:?}", timeout);
+            let cancel = JsonRpcNotification::cancelled(req.id.clone(), &reason);
+            match self.inner.notify(cancel).await {
+                Ok(()) => {
+                    tokio::time::timeout(self.policy.cancel_grace, response)
+                        .await
+                        .ok();
+                }
+                Err(e) => tracing::debug!("Failed to cancel {}: {:#}", req.method, e),
+            }
+        }
+        Err(TransportError::Timeout(timeout).into())
+    }
+}
+
+#[async_trait]
+impl Transport for RetryingTransport {
+    async fn request(&self, req: JsonRpcRequest) -> Result<JsonRpcResponse> {
+        let started = Instant::now();
+        let mut attempt = 0;
+        loop {
+            let error = match self.attempt(&req).await {
+                Ok(response) => return Ok(response),
+                Err(error) => error,
+            };
+
+            let retry_after = match error.downcast_ref::<TransportError>() {
+                Some(TransportError::Retryable { retry_after, .. })
+                    if self.policy.may_retry(&req) =>
+                {
+                    *retry_after
+                }
+                _ => return Err(error),
+            };
+
+            if attempt >= self.policy.retries {
+                if attempt == 0 {
+                    return Err(error);
+                }
+                return Err(TransportError::RetriesExhausted {
+                    attempts: attempt + 1,
+                    last: error,
+                }
+                .into());
+            }
+
+            let delay = self.policy.delay(attempt, retry_after);
+            if let Some(timeout) = self.policy.timeout {
+                if started.elapsed() + delay >= timeout {
+                    tracing::debug!(
+                        "Not retrying {}: waiting {:?} would exceed the {:?} timeout",
+                        req.method,
+                        delay,
+                        timeout
+                    );
+                    return Err(error);
+                }
+            }
+            tracing::debug!("{} failed ({}), retrying in {:?}", req.method, error, delay);
+            tokio::time::sleep(delay).await;
+            attempt += 1;
+        }
+    }
+
+    async fn notify(&self, notification: JsonRpcNotification) -> Result<()> {
+        self.inner.notify(notification).await
+    }
+
+    async fn close(&self) -> Result<()> {
+        self.inner.close().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicU32, Ordering};
+    use std::sync::{Arc, Mutex};
+
+    /// Fails with a retryable error a set number of times, then succeeds
+    struct FlakyTransport {
+        failures: u32,
+        calls: Arc<AtomicU32>,
+        hang: bool,
+        retry_after: Option<Duration>,
+        notified: Notified,
+    }
+
+    type Notified = Arc<Mutex<Vec<JsonRpcNotification>>>;
+
+    #[async_trait]
+    impl Transport for FlakyTransport {
+        async fn request(&self, req: JsonRpcRequest) -> Result<JsonRpcResponse> {
+            if self.hang {
+                std::future::pending::<()>().await;
+            }
+            let call = self.calls.fetch_add(1, Ordering::SeqCst);
+            if call < self.failures {
+                return Err(TransportError::Retryable {
+                    message: "HTTP error 503".to_string(),
+                    retry_after: self.retry_after,
+                }
+                .into());
+            }
+            Ok(JsonRpcResponse::success(req.id, serde_json::json!({})))
+        }
+
+        async fn notify(&self, notification: JsonRpcNotification) -> Result<()> {
+            self.notified.lock().unwrap().push(notification);
+            Ok(())
+        }
+
+        async fn close(&self) -> Result<()> {
+            Ok(())
+        }
+    }
+
+    fn flaky(
+        failures: u32,
+        hang: bool,
+        policy: RetryPolicy,
+    ) -> (RetryingTransport, Arc<AtomicU32>, Notified) {
+        let calls = Arc::new(AtomicU32::new(0));
+        let notified = Notified::default();
+        let inner = FlakyTransport {
+            failures,
+            calls: calls.clone(),
+            hang,
+            retry_after: None,
+            notified: notified.clone(),
+        };
+        (
+            RetryingTransport::new(Box::new(inner), policy),
+            calls,
+            notified,
+        )
+    }
+
+    fn fast_policy(retries: u32) -> RetryPolicy {
+        RetryPolicy {
+            retries,
+            base_delay: Duration::from_millis(1),
+            cancel_grace: Duration::from_millis(1),
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_retries_transient_failures() {
+        let (transport, calls, _) = flaky(2, false, fast_policy(2));
+        let response = transport
+            .request(JsonRpcRequest::new(1u64, "tools/list", None))
+            .await;
+        assert!(response.is_ok());
+        assert_eq!(calls.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn test_gives_up_after_retries() {
+        let (transport, calls, _) = flaky(5, false, fast_policy(1));
+        let error = transport
+            .request(JsonRpcRequest::new(1u64, "tools/list", None))
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            error.downcast_ref::<TransportError>(),
+            Some(TransportError::RetriesExhausted { attempts: 2, .. })
+        ));
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn test_tool_calls_not_retried_by_default() {
+        let (transport, calls, _) = flaky(1, false, fast_policy(3));
+        let error = transport
+            .request(JsonRpcRequest::new(1u64, "tools/call", None))
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            error.downcast_ref::<TransportError>(),
+            Some(TransportError::Retryable { .. })
+        ));
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+
+        let policy = RetryPolicy {
+            retry_tool_calls: true,
+            ..fast_policy(3)
+        };
+        let (transport, _, _) = flaky(1, false, policy);
+        assert!(transport
+            .request(JsonRpcRequest::new(1u64, "tools/call", None))
+            .await
+            .is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_timeout_cancels_request() {
+        let policy = RetryPolicy {
+            timeout: Some(Duration::from_millis(20)),
+            ..fast_policy(2)
+        };
+        let (transport, _, notified) = flaky(0, true, policy);
+        let error = transport
+            .request(JsonRpcRequest::new(7u64, "tools/call", None))
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            error.downcast_ref::<TransportError>(),
+            Some(TransportError::Timeout(_))
+        ));
+
+        let notified = notified.lock().unwrap();
+        assert_eq!(notified.len(), 1);
+        assert_eq!(notified[0].method, "notifications/cancelled");
+        let params = notified[0].params.as_ref().unwrap();
+        assert_eq!(params["requestId"], 7);
+        assert_eq!(params["reason"], "Timed out after 20ms");
+    }
+
+    #[tokio::test]
+    async fn test_initialize_timeout_not_cancelled() {
+        let policy = RetryPolicy {
+            timeout: Some(Duration::from_millis(20)),
+            ..fast_policy(0)
+        };
+        let (transport, _, notified) = flaky(0, true, policy);
+        assert!(transport
+            .request(JsonRpcRequest::new(1u64, "initialize", None))
+            .await
+            .is_err());
+        assert!(notified.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_no_retry_past_timeout() {
+        let calls = Arc::new(AtomicU32::new(0));
+        let inner = FlakyTransport {
+            failures: 1,
+            calls: calls.clone(),
+            hang: false,
+            retry_after: Some(Duration::from_secs(86_400)),
+            notified: Notified::default(),
+        };
+        let policy = RetryPolicy {
+            timeout: Some(Duration::from_secs(5)),
+            ..fast_policy(3)
+        };
+        let transport = RetryingTransport::new(Box::new(inner), policy);
+
+        let started = Instant::now();
+        let error = transport
+            .request(JsonRpcRequest::new(1u64, "tools/list", None))
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            error.downcast_ref::<TransportError>(),
+            Some(TransportError::Retryable { .. })
+        ));
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+        assert!(started.elapsed() < Duration::from_secs(1));
+    }
+
+    #[test]
+    fn test_backoff_and_retry_after() {
+        let policy = RetryPolicy::default();
+        for attempt in 0..10 {
+            let delay = policy.delay(attempt, None);
+            let full = (policy.base_delay * 2u32.pow(attempt)).min(policy.max_delay);
+            assert!(delay >= full / 2 && delay <= full);
+        }
+        assert_eq!(
+            policy.delay(0, Some(Duration::from_secs(7))),
+            Duration::from_secs(7)
+        );
+        assert_eq!(
+            policy.delay(0, Some(Duration::from_secs(u32::MAX as u64))),
+            policy.max_delay
+        );
+
+        let mut headers = HeaderMap::new();
+        headers.insert(RETRY_AFTER, "3".parse().unwrap());
+        assert_eq!(retry_after(&headers), Some(Duration::from_secs(3)));
+        headers.insert(
+            RETRY_AFTER,
+            "Wed, 21 Oct 2015 07:28:00 GMT".parse().unwrap(),
+        );
+        assert_eq!(retry_after(&headers), Some(Duration::ZERO));
+    }
+}
@@ -0,0 +1,159 @@
+use super::{LineConnection, Transport};
+use crate::mcp::{JsonRpcNotification, JsonRpcRequest, JsonRpcResponse, MessageHandler};
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::BufReader;
+use tokio::net::TcpStream;
+
+/// Newline-delimited JSON-RPC to a server that is already running and
+/// listening on a Unix domain socket or a TCP port
+pub struct SocketTransport {
+    connection: LineConnection,
+}
+
+impl SocketTransport {
+    /// Connect to a Unix domain socket
+    #[cfg(unix)]
+    pub async fn connect_unix(
+        path: &Path,
+        timeout: Option<Duration>,
+        handler: Arc<dyn MessageHandler>,
+    ) -> Result<Self> {
+        let stream = with_timeout(
+            timeout,
+            tokio::net::UnixStream::connect(path),
+            &path.display().to_string(),
+        )
+        .await?;
+        let (reader, writer) = stream.into_split();
+        Ok(Self {
+            connection: LineConnection::start(BufReader::new(reader), writer, handler),
+        })
+    }
+
+    #[cfg(not(unix))]
+    pub async fn connect_unix(
+        _path: &Path,
+        _timeout: Option<Duration>,
+        _handler: Arc<dyn MessageHandler>,
+    ) -> Result<Self> {
+        anyhow::bail!("Unix domain sockets are not supported on this platform")
+    }
+
+    /// Connect to a `host:port` address
+    pub async fn connect_tcp(
+        address: &str,
+        timeout: Option<Duration>,
+        handler: Arc<dyn MessageHandler>,
+    ) -> Result<Self> {
+        let stream = with_timeout(timeout, TcpStream::connect(address), address).await?;
+        // Requests are small and latency matters more than throughput
+        stream.set_nodelay(true)?;
+        let (reader, writer) = stream.into_split();
+        Ok(Self {
+            connection: LineConnection::start(BufReader::new(reader), writer, handler),
+        })
+    }
+}
+
+/// Connect, giving up after the connect timeout if there is one
+async fn with_timeout<T>(
+    timeout: Option<Duration>,
+    connect: impl std::future::Future<Output = std::io::Result<T>>,
+    target: &str,
+) -> Result<T> {
+    let result = match timeout {
+        Some(timeout) => tokio::time::timeout(timeout, connect)
+            .await
+            .with_context(|| format!("Timed out connecting to {}", target))?,
+        None => connect.await,
+    };
+    result.with_context(|| format!("Failed to connect to {}", target))
+}
+
+#[async_trait]
+impl Transport for SocketTransport {
+    async fn request(&self, req: JsonRpcRequest) -> Result<JsonRpcResponse> {
+        self.connection.request(req).await
+    }
+
+    async fn notify(&self, notification: JsonRpcNotification) -> Result<()> {
+        self.connection.notify(&notification).await
+    }
+
+    async fn close(&self) -> Result<()> {
+        self.connection.close().await;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mcp::DefaultHandler;
+    use serde_json::{json, Value};
+    use tokio::io::{AsyncBufReadExt, AsyncWriteExt};
+
+    /// Answer each request line with its method name
+    async fn serve(stream: impl tokio::io::AsyncRead + tokio::io::AsyncWrite + Unpin) {
+        let (reader, mut writer) = tokio::io::split(stream);
+        let mut lines = BufReader::new(reader).lines();
+        while let Ok(Some(line)) = lines.next_line().await {
+            let request: Value = serde_json::from_str(&line).unwrap();
+            let response =
+                json!({"jsonrpc": "2.0", "id": request["id"], "result": request["method"]});
+            writer
+                .write_all(format!("{}\n", response).as_bytes())
+                .await
+                .unwrap();
+        }
+    }
+
+    #[tokio::test]
+    async fn test_tcp() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        tokio::spawn(async move { serve(listener.accept().await.unwrap().0).await });
+
+        let transport = SocketTransport::connect_tcp(&address, None, Arc::new(DefaultHandler))
+            .await
+            .unwrap();
+        let response = transport
+            .request(JsonRpcRequest::new(1u64, "tools/list", None))
+            .await
+            .unwrap();
+        assert_eq!(response.result, Some(json!("tools/list")));
+        transport.close().await.unwrap();
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_unix() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("mcp.sock");
+        let listener = tokio::net::UnixListener::bind(&path).unwrap();
+        tokio::spawn(async move { serve(listener.accept().await.unwrap().0).await });
+
+        let transport = SocketTransport::connect_unix(&path, None, Arc::new(DefaultHandler))
+            .await
+            .unwrap();
+        let response = transport
+            .request(JsonRpcRequest::new(1u64, "ping", None))
+            .await
+            .unwrap();
+        assert_eq!(response.result, Some(json!("ping")));
+
+        let error = SocketTransport::connect_unix(
+            &dir.path().join("missing.sock"),
+            None,
+            Arc::new(DefaultHandler),
+        )
+        .await
+        .err()
+        .unwrap();
+        assert!(error.to_string().contains("missing.sock"));
+    }
+}
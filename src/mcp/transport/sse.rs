@@ -1,23 +1,63 @@
-use super::Transport;
-use crate::mcp::{JsonRpcRequest, JsonRpcResponse};
-use anyhow::{anyhow, Context, Result};
+use super::http::{parse_body, HttpTransportError};
+use super::{
+    EventStreamDecoder, PendingRequests, SseEvent, TokenRefresher, Transport, TransportError,
+};
+use crate::auth::authorization_value;
+use crate::mcp::{
+    DefaultHandler, JsonRpcMessage, JsonRpcNotification, JsonRpcRequest, JsonRpcResponse,
+    MessageHandler,
+};
+use anyhow::{anyhow, bail, Context, Result};
 use async_trait::async_trait;
 use futures_util::StreamExt;
-use reqwest::Client;
+use reqwest::{Client, RequestBuilder};
 use std::sync::Arc;
-use tokio::sync::{mpsc, Mutex};
+use tokio::sync::Mutex;
+use tokio::task::JoinHandle;
 
 struct SseConnection {
     message_endpoint: String,
-    response_rx: mpsc::Receiver<String>,
+    pending: PendingRequests,
+    reader: JoinHandle<()>,
+}
+
+impl SseConnection {
+    /// Stop reading once nothing is waiting on this connection
+    fn close_if_idle(&self) -> bool {
+        if self.reader.is_finished() || self.pending.close_if_idle() {
+            self.reader.abort();
+            return true;
+        }
+        false
+    }
+}
+
+#[derive(Default)]
+struct SseState {
+    current: Option<SseConnection>,
+    /// Streams opened with a token the server has since rejected. They stay
+    /// open until the requests sent on them have been answered.
+    retired: Vec<SseConnection>,
 }
 
 pub struct SseTransport {
     client: Client,
     base_url: String,
-    connection: Arc<Mutex<Option<SseConnection>>>,
-    access_token: Option<String>,
+    state: Mutex<SseState>,
+    access_token: std::sync::RwLock<Option<String>>,
+    /// Held while refreshing, so concurrent 401s refresh the token only once
+    refreshing: Mutex<()>,
+    token_refresher: Option<Arc<dyn TokenRefresher>>,
     server_name: String,
+    handler: Arc<dyn MessageHandler>,
+}
+
+/// Attach the Authorization header for a stored token
+fn authorize(request: RequestBuilder, token: Option<&str>) -> RequestBuilder {
+    match token {
+        Some(token) => request.header("Authorization", authorization_value(token)),
+        None => request,
+    }
 }
 
 impl SseTransport {
@@ -25,82 +65,97 @@ impl SseTransport {
         Self {
             client: Client::new(),
             base_url: url,
-            connection: Arc::new(Mutex::new(None)),
-            access_token: None,
+            state: Mutex::new(SseState::default()),
+            access_token: std::sync::RwLock::new(None),
+            refreshing: Mutex::new(()),
+            token_refresher: None,
             server_name,
+            handler: Arc::new(DefaultHandler),
         }
     }
 
+    /// Use a preconfigured client, e.g. one that sends extra headers
+    pub fn with_client(mut self, client: Client) -> Self {
+        self.client = client;
+        self
+    }
+
     pub fn with_token(mut self, token: Option<String>) -> Self {
-        self.access_token = token;
+        self.access_token = std::sync::RwLock::new(token);
         self
     }
 
-    async fn ensure_connected(&self) -> Result<String> {
-        // Check if we already have a connection
-        {
-            let conn = self.connection.lock().await;
-            if let Some(ref c) = *conn {
-                return Ok(c.message_endpoint.clone());
+    /// Refresh the token and retry once when the server rejects it mid-session
+    pub fn with_token_refresher(mut self, refresher: Option<Arc<dyn TokenRefresher>>) -> Self {
+        self.token_refresher = refresher;
+        self
+    }
+
+    /// Handle notifications and requests the server sends on its own
+    pub fn with_handler(mut self, handler: Arc<dyn MessageHandler>) -> Self {
+        self.handler = handler;
+        self
+    }
+
+    fn token(&self) -> Option<String> {
+        self.access_token.read().unwrap().clone()
+    }
+
+    fn auth_required(&self) -> anyhow::Error {
+        HttpTransportError::AuthRequired {
+            server_name: self.server_name.clone(),
+        }
+        .into()
+    }
+
+    /// The live connection's message endpoint and pending requests, connecting
+    /// first if needed. Callers hold the state lock so concurrent requests
+    /// share one stream.
+    async fn ensure_connected(&self, state: &mut SseState) -> Result<(String, PendingRequests)> {
+        state.retired.retain(|c| !c.close_if_idle());
+        if let Some(ref c) = state.current {
+            if !c.reader.is_finished() {
+                return Ok((c.message_endpoint.clone(), c.pending.clone()));
             }
         }
 
         // Open SSE connection
-        let mut request = self
+        let request = self
             .client
             .get(&self.base_url)
             .header("Accept", "text/event-stream");
 
-        if let Some(ref token) = self.access_token {
-            // Support different auth formats: if token already has a prefix, use as-is
-            let auth_value = if token.starts_with("Bearer ")
-                || token.starts_with("token ")
-                || token.starts_with("Basic ")
-            {
-                token.clone()
-            } else {
-                format!("Bearer {}", token)
-            };
-            request = request.header("Authorization", auth_value);
-        }
-
-        let response = request
+        let response = authorize(request, self.token().as_deref())
             .send()
             .await
-            .with_context(|| format!("Failed to connect to SSE endpoint: {}", self.base_url))?;
+            .map_err(|e| TransportError::from_send_error(e, &self.base_url))?;
 
         if response.status() == reqwest::StatusCode::UNAUTHORIZED {
-            return Err(anyhow!(
-                "Authentication required. Run: relay auth {}",
-                self.server_name
-            ));
+            return Err(self.auth_required());
         }
 
         if !response.status().is_success() {
-            return Err(anyhow!("SSE connection failed: HTTP {}", response.status()));
+            let status = response.status();
+            let headers = response.headers().clone();
+            let body = response.text().await.unwrap_or_default();
+            return Err(TransportError::from_status(status, &headers, &body)
+                .context("SSE connection failed"));
         }
 
-        // Create channel for responses
-        let (tx, rx) = mpsc::channel::<String>(100);
-
         // Read SSE stream to get endpoint and start background reader
         let mut stream = response.bytes_stream();
-        let mut buffer = String::new();
+        let mut decoder = EventStreamDecoder::default();
         let mut endpoint_url: Option<String> = None;
+        let mut early = Vec::new();
 
-        // Read until we get the endpoint
+        // Read until we get the endpoint; keep any messages that came first
         while let Some(chunk) = stream.next().await {
             let chunk = chunk.with_context(|| "Failed to read SSE stream")?;
-            buffer.push_str(&String::from_utf8_lossy(&chunk));
-
-            for line in buffer.lines() {
-                if line.starts_with("data: ") {
-                    let data = line.strip_prefix("data: ").unwrap_or("");
-                    if data.contains("sessionId=") || data.starts_with("/") {
-                        let base = self.base_url.trim_end_matches("/sse");
-                        endpoint_url = Some(format!("{}{}", base, data));
-                        break;
-                    }
+            for event in decoder.push(&chunk) {
+                if endpoint_url.is_none() && is_endpoint_event(&event) {
+                    endpoint_url = Some(resolve_endpoint(&self.base_url, event.data.trim())?);
+                } else {
+                    early.push(event);
                 }
             }
 
@@ -110,132 +165,540 @@ impl SseTransport {
         }
 
         let endpoint = endpoint_url.ok_or_else(|| anyhow!("No endpoint received"))?;
-
-        // Spawn background task to read SSE responses
-        let tx_clone = tx.clone();
-        tokio::spawn(async move {
-            let mut buf = buffer;
-            while let Some(chunk) = stream.next().await {
-                if let Ok(chunk) = chunk {
-                    buf.push_str(&String::from_utf8_lossy(&chunk));
-
-                    // Process complete SSE events
-                    while let Some(pos) = buf.find("\n\n") {
-                        let event = buf[..pos].to_string();
-                        buf = buf[pos + 2..].to_string();
-
-                        // Extract data from event
-                        for line in event.lines() {
-                            if line.starts_with("data: ") {
-                                let data = line.strip_prefix("data: ").unwrap_or("");
-                                if data.starts_with("{") {
-                                    let _ = tx_clone.send(data.to_string()).await;
-                                }
-                            }
+        let pending = PendingRequests::default();
+
+        // Spawn background task to route SSE messages by request id
+        let reader = {
+            let pending = pending.clone();
+            let handler = self.handler.clone();
+            let client = self.client.clone();
+            let endpoint = endpoint.clone();
+            let token = self.token();
+            tokio::spawn(async move {
+                let route = |event: SseEvent| {
+                    let (pending, handler, client) = (&pending, &handler, &client);
+                    let (endpoint, token) = (&endpoint, token.as_deref());
+                    async move {
+                        if event.event == "message" {
+                            route_message(&event.data, pending, handler, client, endpoint, token)
+                                .await;
                         }
                     }
+                };
+
+                for event in early {
+                    route(event).await;
                 }
-            }
-        });
+                while let Some(Ok(chunk)) = stream.next().await {
+                    for event in decoder.push(&chunk) {
+                        route(event).await;
+                    }
+                }
+                pending.close();
+            })
+        };
 
         // Store connection
-        {
-            let mut conn = self.connection.lock().await;
-            *conn = Some(SseConnection {
-                message_endpoint: endpoint.clone(),
-                response_rx: rx,
-            });
+        state.current = Some(SseConnection {
+            message_endpoint: endpoint.clone(),
+            pending: pending.clone(),
+            reader,
+        });
+
+        Ok((endpoint, pending))
+    }
+}
+
+/// The event announcing where to POST messages. Some servers send it
+/// without the `endpoint` event name.
+fn is_endpoint_event(event: &SseEvent) -> bool {
+    let data = event.data.trim();
+    event.event == "endpoint"
+        || (event.event == "message" && (data.contains("sessionId=") || data.starts_with('/')))
+}
+
+/// Resolve the endpoint event's (usually relative) URL against the stream URL
+fn resolve_endpoint(base_url: &str, endpoint: &str) -> Result<String> {
+    let base =
+        reqwest::Url::parse(base_url).with_context(|| format!("Invalid SSE URL: {}", base_url))?;
+    let url = base
+        .join(endpoint)
+        .with_context(|| format!("Invalid endpoint URL: {}", endpoint))?;
+    Ok(url.to_string())
+}
+
+/// Deliver one message from the SSE stream: responses to their waiting callers,
+/// notifications to the handler, and server requests answered via the message endpoint
+async fn route_message(
+    data: &str,
+    pending: &PendingRequests,
+    handler: &Arc<dyn MessageHandler>,
+    client: &Client,
+    endpoint: &str,
+    token: Option<&str>,
+) {
+    let message: JsonRpcMessage = match serde_json::from_str(data) {
+        Ok(message) => message,
+        Err(e) => {
+            tracing::debug!("Ignoring unparseable SSE message ({}): {}", e, data);
+            return;
         }
+    };
 
-        Ok(endpoint)
+    match message {
+        JsonRpcMessage::Response(response) => {
+            let id = response.id.clone();
+            if !pending.complete(response) {
+                tracing::debug!("Ignoring response for unknown request id {:?}", id);
+            }
+        }
+        JsonRpcMessage::Notification(notification) => {
+            handler.on_notification(notification).await;
+        }
+        JsonRpcMessage::Request(request) => {
+            let response = handler.on_request(request).await;
+            let post = authorize(client.post(endpoint), token).json(&response);
+            if let Err(e) = post.send().await {
+                tracing::debug!("Failed to answer server request: {}", e);
+            }
+        }
     }
 }
 
-#[async_trait]
-impl Transport for SseTransport {
-    async fn request(&mut self, req: JsonRpcRequest) -> Result<JsonRpcResponse> {
-        let message_url = self.ensure_connected().await?;
+impl SseTransport {
+    async fn request_once(&self, req: &JsonRpcRequest) -> Result<JsonRpcResponse> {
         let request_id = req.id.clone();
+        let (message_url, pending, rx) = {
+            let mut state = self.state.lock().await;
+            let (message_url, pending) = self.ensure_connected(&mut state).await?;
+            let rx = pending
+                .register(request_id.clone())
+                .map_err(|_| anyhow!("SSE stream closed before request was sent"))?;
+            (message_url, pending, rx)
+        };
 
-        let mut request = self
+        let request = self
             .client
             .post(&message_url)
             .header("Content-Type", "application/json");
 
-        if let Some(ref token) = self.access_token {
-            // Support different auth formats: if token already has a prefix, use as-is
-            let auth_value = if token.starts_with("Bearer ")
-                || token.starts_with("token ")
-                || token.starts_with("Basic ")
-            {
-                token.clone()
-            } else {
-                format!("Bearer {}", token)
-            };
-            request = request.header("Authorization", auth_value);
-        }
-
-        let response = request
-            .json(&req)
+        let response = match authorize(request, self.token().as_deref())
+            .json(req)
             .send()
             .await
-            .with_context(|| format!("Failed to send request to {}", message_url))?;
-
-        if response.status() == reqwest::StatusCode::UNAUTHORIZED {
-            return Err(anyhow!(
-                "Authentication required. Run: relay auth {}",
-                self.server_name
-            ));
-        }
+        {
+            Ok(response) => response,
+            Err(e) => {
+                pending.cancel(&request_id);
+                return Err(TransportError::from_send_error(e, &message_url));
+            }
+        };
 
         // 202 Accepted means response will come via SSE
         if response.status() == reqwest::StatusCode::ACCEPTED {
-            // Wait for response on SSE channel
-            let mut conn = self.connection.lock().await;
-            if let Some(ref mut c) = *conn {
-                // Read responses until we find the one matching our request ID
-                while let Some(data) = c.response_rx.recv().await {
-                    if let Ok(resp) = serde_json::from_str::<JsonRpcResponse>(&data) {
-                        if resp.id == request_id {
-                            return Ok(resp);
-                        }
-                    }
-                }
-            }
-            return Err(anyhow!("Connection closed before response received"));
+            return rx
+                .await
+                .map_err(|_| anyhow!("Connection closed before response received"));
+        }
+
+        // Any other status carries the response (or an error) directly
+        pending.cancel(&request_id);
+
+        if response.status() == reqwest::StatusCode::UNAUTHORIZED {
+            return Err(self.auth_required());
         }
 
         if !response.status().is_success() {
             let status = response.status();
+            let headers = response.headers().clone();
             let body = response.text().await.unwrap_or_default();
-            return Err(anyhow!("HTTP error {}: {}", status, body));
+            return Err(TransportError::from_status(status, &headers, &body));
         }
 
         // Direct response (non-SSE servers)
         let text = response.text().await?;
-        let json_text = if text.contains("data: ") {
-            text.lines()
-                .filter(|l| l.starts_with("data: "))
-                .map(|l| l.strip_prefix("data: ").unwrap_or(""))
-                .collect::<Vec<_>>()
-                .join("")
+        parse_body(&text)?
+            .into_iter()
+            .find_map(|message| match message {
+                JsonRpcMessage::Response(response) => Some(response),
+                _ => None,
+            })
+            .ok_or_else(|| anyhow!("Empty response from server"))
+    }
+
+    /// Replace `stale`, the token a request was rejected with, unless another
+    /// request already did. The stream opened with the stale token is retired
+    /// rather than closed, so responses to requests sent on it still arrive.
+    async fn refresh_token(
+        &self,
+        refresher: &Arc<dyn TokenRefresher>,
+        stale: Option<String>,
+    ) -> Result<()> {
+        let _refreshing = self.refreshing.lock().await;
+        if self.token() != stale {
+            return Ok(());
+        }
+
+        let token = refresher
+            .refresh(stale.as_deref().unwrap_or_default())
+            .await?;
+        *self.access_token.write().unwrap() = Some(token);
+        let mut state = self.state.lock().await;
+        if let Some(connection) = state.current.take() {
+            state.retired.push(connection);
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl Transport for SseTransport {
+    async fn request(&self, req: JsonRpcRequest) -> Result<JsonRpcResponse> {
+        let sent_with = self.token();
+        let result = self.request_once(&req).await;
+
+        let Some(refresher) = &self.token_refresher else {
+            return result;
+        };
+        match result {
+            Err(e) if e.downcast_ref::<HttpTransportError>().is_some() => {
+                match self.refresh_token(refresher, sent_with).await {
+                    Ok(()) => self.request_once(&req).await,
+                    Err(refresh_err) => {
+                        tracing::debug!("Token refresh failed: {:#}", refresh_err);
+                        Err(e)
+                    }
+                }
+            }
+            other => other,
+        }
+    }
+
+    async fn notify(&self, notification: JsonRpcNotification) -> Result<()> {
+        let (message_url, _) = {
+            let mut state = self.state.lock().await;
+            self.ensure_connected(&mut state).await?
+        };
+        let request = self
+            .client
+            .post(&message_url)
+            .header("Content-Type", "application/json");
+        let response = authorize(request, self.token().as_deref())
+            .json(&notification)
+            .send()
+            .await
+            .map_err(|e| TransportError::from_send_error(e, &message_url))?;
+        if !response.status().is_success() {
+            bail!(
+                "Server rejected {}: HTTP {}",
+                notification.method,
+                response.status()
+            );
+        }
+        Ok(())
+    }
+
+    async fn close(&self) -> Result<()> {
+        let mut state = self.state.lock().await;
+        for c in state
+            .current
+            .take()
+            .into_iter()
+            .chain(state.retired.drain(..))
+        {
+            c.reader.abort();
+            c.pending.close();
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::event_stream::fuzz;
+    use super::*;
+    use axum::body::Body;
+    use axum::extract::State;
+    use axum::http::{header, HeaderMap, StatusCode, Uri};
+    use axum::response::{IntoResponse, Response};
+    use axum::Json;
+    use serde_json::{json, Value};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::Duration;
+    use tokio::sync::{mpsc, Notify};
+
+    /// An SSE server that accepts one bearer token at a time. `slow` requests
+    /// are answered once `release` is notified; everything else right away.
+    /// Results name the stream the response was sent on.
+    #[derive(Default)]
+    struct TestServer {
+        token: std::sync::Mutex<String>,
+        streams: std::sync::Mutex<Vec<mpsc::UnboundedSender<String>>>,
+        slow_received: Notify,
+        release: Notify,
+    }
+
+    impl TestServer {
+        fn authorized(&self, headers: &HeaderMap) -> bool {
+            let expected = format!("Bearer {}", self.token.lock().unwrap());
+            headers
+                .get(header::AUTHORIZATION)
+                .and_then(|v| v.to_str().ok())
+                == Some(&expected)
+        }
+    }
+
+    async fn open_stream(State(server): State<Arc<TestServer>>, headers: HeaderMap) -> Response {
+        if !server.authorized(&headers) {
+            return StatusCode::UNAUTHORIZED.into_response();
+        }
+        let (tx, rx) = mpsc::unbounded_channel();
+        let mut streams = server.streams.lock().unwrap();
+        tx.send(format!(
+            "event: endpoint\ndata: /message?stream={}\n\n",
+            streams.len()
+        ))
+        .unwrap();
+        streams.push(tx);
+
+        let events = futures_util::stream::unfold(rx, |mut rx| async move {
+            let event = rx.recv().await?;
+            Some((Ok::<_, std::convert::Infallible>(event), rx))
+        });
+        (
+            [(header::CONTENT_TYPE, "text/event-stream")],
+            Body::from_stream(events),
+        )
+            .into_response()
+    }
+
+    async fn post_message(
+        State(server): State<Arc<TestServer>>,
+        uri: Uri,
+        headers: HeaderMap,
+        Json(request): Json<Value>,
+    ) -> StatusCode {
+        if !server.authorized(&headers) {
+            return StatusCode::UNAUTHORIZED;
+        }
+        let stream: usize = uri.query().unwrap()["stream=".len()..].parse().unwrap();
+        let response = json!({"jsonrpc": "2.0", "id": request["id"], "result": {"stream": stream}});
+        let event = format!("event: message\ndata: {}\n\n", response);
+        let tx = server.streams.lock().unwrap()[stream].clone();
+        if request["method"] == "slow" {
+            tokio::spawn(async move {
+                server.slow_received.notify_one();
+                server.release.notified().await;
+                tx.send(event).ok();
+            });
         } else {
-            text
+            tx.send(event).ok();
+        }
+        StatusCode::ACCEPTED
+    }
+
+    async fn start_server() -> (String, Arc<TestServer>) {
+        let server = Arc::new(TestServer::default());
+        *server.token.lock().unwrap() = "old".to_string();
+        let app = axum::Router::new()
+            .route("/sse", axum::routing::get(open_stream))
+            .route("/message", axum::routing::post(post_message))
+            .with_state(server.clone());
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/sse", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        (url, server)
+    }
+
+    #[derive(Default)]
+    struct CountingRefresher {
+        calls: AtomicUsize,
+    }
+
+    #[async_trait]
+    impl TokenRefresher for CountingRefresher {
+        async fn refresh(&self, stale_token: &str) -> Result<String> {
+            assert_eq!(stale_token, "old");
+            self.calls.fetch_add(1, Ordering::SeqCst);
+            // Give the other rejected request time to ask for a refresh too
+            tokio::time::sleep(Duration::from_millis(100)).await;
+            Ok("new".to_string())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_refresh_keeps_in_flight_requests() {
+        let (url, server) = start_server().await;
+        let refresher = Arc::new(CountingRefresher::default());
+        let transport = SseTransport::new(url, "test".to_string())
+            .with_token(Some("old".to_string()))
+            .with_token_refresher(Some(refresher.clone()));
+
+        let slow = transport.request(JsonRpcRequest::new(1u64, "slow", None));
+        let rejected = async {
+            server.slow_received.notified().await;
+            *server.token.lock().unwrap() = "new".to_string();
+            let both = tokio::join!(
+                transport.request(JsonRpcRequest::new(2u64, "fast", None)),
+                transport.request(JsonRpcRequest::new(3u64, "fast", None)),
+            );
+            server.release.notify_one();
+            both
+        };
+        let (slow, (second, third)) = tokio::join!(slow, rejected);
+
+        assert_eq!(slow.unwrap().result, Some(json!({ "stream": 0 })));
+        assert_eq!(second.unwrap().result, Some(json!({ "stream": 1 })));
+        assert_eq!(third.unwrap().result, Some(json!({ "stream": 1 })));
+        assert_eq!(refresher.calls.load(Ordering::SeqCst), 1);
+
+        transport.close().await.unwrap();
+    }
+
+    /// A server that encodes every event at random and sends it in random
+    /// chunks, announcing its endpoint in each of the forms servers use
+    struct FuzzServer {
+        rng: std::sync::Mutex<fastrand::Rng>,
+        base_url: String,
+        stream: std::sync::Mutex<Option<mpsc::UnboundedSender<Vec<u8>>>>,
+    }
+
+    impl FuzzServer {
+        /// Encode an event, sometimes after one the client should ignore,
+        /// and send it in chunks
+        fn send(&self, event: Option<&str>, data: &str) {
+            let mut rng = self.rng.lock().unwrap();
+            let mut encoded = String::new();
+            if rng.bool() {
+                let noise = fuzz::text(&mut rng);
+                encoded.push_str(&fuzz::encode(&mut rng, Some("ping"), None, &noise));
+            }
+            let id = rng.bool().then(|| rng.u32(..1000).to_string());
+            encoded.push_str(&fuzz::encode(&mut rng, event, id.as_deref(), data));
+
+            let stream = self.stream.lock().unwrap();
+            for chunk in fuzz::split(&mut rng, encoded.as_bytes()) {
+                stream.as_ref().unwrap().send(chunk).unwrap();
+            }
+        }
+    }
+
+    async fn open_fuzz_stream(State(server): State<Arc<FuzzServer>>) -> Response {
+        let (tx, rx) = mpsc::unbounded_channel();
+        *server.stream.lock().unwrap() = Some(tx);
+
+        let (endpoint, event) = {
+            let mut rng = server.rng.lock().unwrap();
+            let endpoint = match rng.usize(..4) {
+                0 => "/message?sessionId=1".to_string(),
+                1 => "message?sessionId=1".to_string(),
+                2 => "?sessionId=1".to_string(),
+                _ => format!("{}/message?sessionId=1", server.base_url),
+            };
+            (endpoint, rng.bool().then_some("endpoint"))
         };
+        server.send(event, &endpoint);
+
+        let chunks = futures_util::stream::unfold(rx, |mut rx| async move {
+            let chunk = rx.recv().await?;
+            Some((Ok::<_, std::convert::Infallible>(chunk), rx))
+        });
+        (
+            [(header::CONTENT_TYPE, "text/event-stream")],
+            Body::from_stream(chunks),
+        )
+            .into_response()
+    }
 
-        if json_text.is_empty() {
-            return Err(anyhow!("Empty response from server"));
+    /// Echo the request's `text` param back on the stream
+    async fn post_fuzz_message(
+        State(server): State<Arc<FuzzServer>>,
+        uri: Uri,
+        Json(request): Json<Value>,
+    ) -> StatusCode {
+        if uri.query() != Some("sessionId=1") {
+            return StatusCode::BAD_REQUEST;
         }
+        let response = json!({
+            "jsonrpc": "2.0",
+            "id": request["id"],
+            "result": {"text": request["params"]["text"]},
+        });
+        let (data, event) = {
+            let mut rng = server.rng.lock().unwrap();
+            let data = if rng.bool() {
+                serde_json::to_string_pretty(&response).unwrap()
+            } else {
+                response.to_string()
+            };
+            (data, rng.bool().then_some("message"))
+        };
+        server.send(event, &data);
+        StatusCode::ACCEPTED
+    }
 
-        let response: JsonRpcResponse = serde_json::from_str(&json_text)
-            .with_context(|| format!("Failed to parse response: {}", json_text))?;
+    #[tokio::test]
+    async fn test_fuzz_over_http() {
+        for seed in 0..20 {
+            let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let base_url = format!("http://{}", listener.local_addr().unwrap());
+            let server = Arc::new(FuzzServer {
+                rng: std::sync::Mutex::new(fastrand::Rng::with_seed(seed)),
+                base_url: base_url.clone(),
+                stream: std::sync::Mutex::new(None),
+            });
+            let app = axum::Router::new()
+                .route(
+                    "/sse",
+                    axum::routing::get(open_fuzz_stream).post(post_fuzz_message),
+                )
+                .route("/message", axum::routing::post(post_fuzz_message))
+                .with_state(server);
+            let serve = tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+
+            let transport = SseTransport::new(format!("{}/sse", base_url), "test".to_string());
+            let mut rng = fastrand::Rng::with_seed(seed);
+            for id in 0..3u64 {
+                let text = fuzz::text(&mut rng);
+                let params = json!({ "text": text });
+                let response = transport
+                    .request(JsonRpcRequest::new(id, "echo", Some(params)))
+                    .await
+                    .unwrap_or_else(|e| panic!("seed {}: {:#}", seed, e));
+                assert_eq!(
+                    response.result,
+                    Some(json!({ "text": text })),
+                    "seed {}",
+                    seed
+                );
+            }
 
-        Ok(response)
+            transport.close().await.unwrap();
+            serve.abort();
+        }
     }
 
-    async fn close(&mut self) -> Result<()> {
-        let mut conn = self.connection.lock().await;
-        *conn = None;
-        Ok(())
+    #[test]
+    fn test_resolve_endpoint() {
+        assert_eq!(
+            resolve_endpoint("http://host/sse", "/messages?sessionId=1").unwrap(),
+            "http://host/messages?sessionId=1"
+        );
+        assert_eq!(
+            resolve_endpoint("http://host/api/events", "messages?sessionId=1").unwrap(),
+            "http://host/api/messages?sessionId=1"
+        );
+        assert_eq!(
+            resolve_endpoint("http://host/sse", "https://other/messages").unwrap(),
+            "https://other/messages"
+        );
+        assert_eq!(
+            resolve_endpoint("http://host/sse?key=abc", "/messages?sessionId=1").unwrap(),
+            "http://host/messages?sessionId=1"
+        );
+        assert_eq!(
+            resolve_endpoint("http://host/v1/sse", "?sessionId=1").unwrap(),
+            "http://host/v1/sse?sessionId=1"
+        );
     }
 }
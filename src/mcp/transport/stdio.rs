@@ -1,76 +1,409 @@
-use super::Transport;
-use crate::mcp::{JsonRpcRequest, JsonRpcResponse};
-use anyhow::{bail, Context, Result};
+use super::{LineConnection, Transport};
+use crate::mcp::{JsonRpcNotification, JsonRpcRequest, JsonRpcResponse, MessageHandler};
+use anyhow::{anyhow, Context, Result};
 use async_trait::async_trait;
-use std::collections::HashMap;
+use std::collections::VecDeque;
 use std::process::Stdio;
-use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, AsyncWriteExt, BufReader};
 use tokio::process::{Child, Command};
+use tokio::sync::Mutex;
+use tokio::task::JoinHandle;
+
+/// How long a server gets to exit after its stdin closes, and again after SIGTERM
+const SHUTDOWN_GRACE: Duration = Duration::from_secs(2);
+
+/// How often to check whether a closing server has exited
+const EXIT_POLL_INTERVAL: Duration = Duration::from_millis(20);
+
+/// How long to wait for a server that stopped answering to be reaped
+const EXIT_WAIT: Duration = Duration::from_millis(500);
+
+/// Lines of stderr kept to explain an unexpected exit
+const STDERR_TAIL_LINES: usize = 20;
+
+type StderrTail = Arc<std::sync::Mutex<VecDeque<String>>>;
+
+/// Where a server's stderr is recorded. It never reaches relay's own stderr
+/// directly: lines go to debug logging (shown with `--verbose`), the optional
+/// log file, and the tail kept for crash reports.
+#[derive(Default)]
+pub struct StderrCapture {
+    pub server_name: String,
+    pub log_file: Option<std::fs::File>,
+}
 
 pub struct StdioTransport {
-    child: Child,
-    stdin: tokio::process::ChildStdin,
-    stdout: BufReader<tokio::process::ChildStdout>,
+    child: Mutex<Child>,
+    connection: LineConnection,
+    stderr_tail: StderrTail,
+    stderr_reader: Mutex<Option<JoinHandle<()>>>,
+    grace: Duration,
 }
 
 impl StdioTransport {
-    pub async fn spawn(command: &str, env: HashMap<String, String>) -> Result<Self> {
-        // Parse command - first word is the program, rest are args
-        let parts: Vec<&str> = command.split_whitespace().collect();
-        if parts.is_empty() {
-            bail!("Empty command");
-        }
-
-        let (program, args) = parts.split_first().unwrap();
-
-        let mut cmd = Command::new(program);
-        cmd.args(args)
-            .stdin(Stdio::piped())
+    /// Spawn a server process. The caller sets up the program, arguments,
+    /// working directory and environment; stdin and stdout are taken over here.
+    ///
+    /// The server gets its own process group, so everything it starts can be
+    /// signalled along with it.
+    pub async fn spawn(
+        mut cmd: Command,
+        handler: Arc<dyn MessageHandler>,
+        stderr: StderrCapture,
+    ) -> Result<Self> {
+        cmd.stdin(Stdio::piped())
             .stdout(Stdio::piped())
-            .stderr(Stdio::inherit())
-            .envs(env);
+            .stderr(Stdio::piped())
+            .process_group(0);
 
-        let mut child = cmd
-            .spawn()
-            .with_context(|| format!("Failed to spawn: {}", command))?;
+        let mut child = cmd.spawn().with_context(|| {
+            format!(
+                "Failed to spawn: {}",
+                cmd.as_std().get_program().to_string_lossy()
+            )
+        })?;
 
         let stdin = child.stdin.take().context("Failed to get stdin")?;
         let stdout = child.stdout.take().context("Failed to get stdout")?;
+        let stderr_pipe = child.stderr.take().context("Failed to get stderr")?;
+
+        let stderr_tail = StderrTail::default();
+        let stderr_reader = tokio::spawn(capture_stderr(
+            BufReader::new(stderr_pipe),
+            stderr_tail.clone(),
+            stderr,
+        ));
 
         Ok(Self {
-            child,
-            stdin,
-            stdout: BufReader::new(stdout),
+            child: Mutex::new(child),
+            connection: LineConnection::start(BufReader::new(stdout), stdin, handler),
+            stderr_tail,
+            stderr_reader: Mutex::new(Some(stderr_reader)),
+            grace: SHUTDOWN_GRACE,
         })
     }
+
+    /// If the server has exited, say how, with the last lines of its stderr
+    async fn exit_report(&self) -> Option<String> {
+        let status = {
+            let mut child = self.child.lock().await;
+            tokio::time::timeout(EXIT_WAIT, child.wait())
+                .await
+                .ok()?
+                .ok()?
+        };
+
+        // Let the final lines drain before reading the tail
+        if let Some(reader) = self.stderr_reader.lock().await.take() {
+            tokio::time::timeout(EXIT_WAIT, reader).await.ok();
+        }
+
+        let mut report = format!("Server exited unexpectedly ({})", status);
+        let tail = self.stderr_tail.lock().unwrap();
+        if !tail.is_empty() {
+            report.push_str("\nstderr:");
+            for line in tail.iter() {
+                report.push_str("\n  ");
+                report.push_str(line);
+            }
+        }
+        Some(report)
+    }
+}
+
+/// Record each line the server writes to stderr, remembering the last few
+async fn capture_stderr(
+    mut reader: impl AsyncBufRead + Unpin,
+    tail: StderrTail,
+    capture: StderrCapture,
+) {
+    let mut log_file = capture.log_file.map(tokio::fs::File::from_std);
+    let mut line = Vec::new();
+    loop {
+        line.clear();
+        match reader.read_until(b'\n', &mut line).await {
+            Ok(0) | Err(_) => break,
+            Ok(_) => {}
+        }
+
+        if let Some(file) = &mut log_file {
+            if let Err(e) = write_log_line(file, &line).await {
+                tracing::debug!("Failed to write server log: {}", e);
+                log_file = None;
+            }
+        }
+
+        let text = String::from_utf8_lossy(&line).trim_end().to_string();
+        tracing::debug!("{} stderr: {}", capture.server_name, text);
+        let mut tail = tail.lock().unwrap();
+        if tail.len() == STDERR_TAIL_LINES {
+            tail.pop_front();
+        }
+        tail.push_back(text);
+    }
+}
+
+async fn write_log_line(file: &mut tokio::fs::File, line: &[u8]) -> std::io::Result<()> {
+    file.write_all(line).await?;
+    if !line.ends_with(b"\n") {
+        file.write_all(b"\n").await?;
+    }
+    // Flush so `relay logs` sees lines as they arrive
+    file.flush().await
+}
+
+/// Wait up to `timeout` for the child to exit. It is left unreaped, so its
+/// pid, and with it the process group id, can't be reused meanwhile.
+async fn exits_within(pid: u32, timeout: Duration) -> bool {
+    let deadline = Instant::now() + timeout;
+    loop {
+        if has_exited(pid) {
+            return true;
+        }
+        if Instant::now() >= deadline {
+            return false;
+        }
+        tokio::time::sleep(EXIT_POLL_INTERVAL).await;
+    }
+}
+
+fn has_exited(pid: u32) -> bool {
+    // SAFETY: waitid only writes to `info`. WNOWAIT leaves the child for
+    // tokio to reap.
+    unsafe {
+        let mut info: libc::siginfo_t = std::mem::zeroed();
+        let result = libc::waitid(
+            libc::P_PID,
+            pid as libc::id_t,
+            &mut info,
+            libc::WEXITED | libc::WNOHANG | libc::WNOWAIT,
+        );
+        // An error means there's no such child left to wait for
+        result != 0 || info.si_pid() != 0
+    }
+}
+
+/// Send a signal to every process in the group led by `pid`
+fn signal_group(pid: u32, signal: libc::c_int) {
+    // SAFETY: killpg only reads its arguments; failure (e.g. an empty group) is harmless
+    unsafe {
+        libc::killpg(pid as libc::pid_t, signal);
+    }
 }
 
 #[async_trait]
 impl Transport for StdioTransport {
-    async fn request(&mut self, req: JsonRpcRequest) -> Result<JsonRpcResponse> {
-        // Write request as JSON line
-        let mut json = serde_json::to_string(&req)?;
-        json.push('\n');
+    async fn request(&self, req: JsonRpcRequest) -> Result<JsonRpcResponse> {
+        let result = self.connection.request(req).await;
+        if result.is_err() {
+            if let Some(report) = self.exit_report().await {
+                return Err(anyhow!(report));
+            }
+        }
+        result
+    }
 
-        self.stdin.write_all(json.as_bytes()).await?;
-        self.stdin.flush().await?;
+    async fn notify(&self, notification: JsonRpcNotification) -> Result<()> {
+        self.connection.notify(&notification).await
+    }
 
-        // Read response line
-        let mut line = String::new();
-        self.stdout.read_line(&mut line).await?;
+    /// Close stdin and give the server a grace period to exit, then send
+    /// SIGTERM and finally SIGKILL to its process group
+    async fn close(&self) -> Result<()> {
+        self.connection.close().await;
 
-        if line.is_empty() {
-            bail!("Server closed connection unexpectedly");
+        let mut child = self.child.lock().await;
+        // Already reaped, e.g. after reporting an unexpected exit
+        let Some(pid) = child.id() else {
+            return Ok(());
+        };
+
+        if !exits_within(pid, self.grace).await {
+            tracing::debug!("Server still running after stdin closed; sending SIGTERM");
+            signal_group(pid, libc::SIGTERM);
+            if !exits_within(pid, self.grace).await {
+                tracing::debug!("Server ignored SIGTERM; sending SIGKILL");
+                signal_group(pid, libc::SIGKILL);
+            }
         }
 
-        let response: JsonRpcResponse = serde_json::from_str(&line)
-            .with_context(|| format!("Failed to parse response: {}", line))?;
+        // Stop anything the server left running behind it. Until the server
+        // is reaped below, its group id can't belong to anyone else.
+        signal_group(pid, libc::SIGTERM);
+        child.wait().await.ok();
+        Ok(())
+    }
+}
 
-        Ok(response)
+impl Drop for StdioTransport {
+    /// Don't leave the server running when the transport goes away without
+    /// being closed, e.g. when relay panics
+    fn drop(&mut self) {
+        let child = self.child.get_mut();
+        if let (Some(pid), Ok(None)) = (child.id(), child.try_wait()) {
+            signal_group(pid, libc::SIGKILL);
+        }
     }
+}
 
-    async fn close(&mut self) -> Result<()> {
-        self.child.kill().await.ok();
-        Ok(())
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mcp::DefaultHandler;
+    use serde_json::json;
+
+    #[tokio::test]
+    async fn test_concurrent_requests_matched_by_id() {
+        let dir = tempfile::tempdir().unwrap();
+        let script = dir.path().join("server.sh");
+        // Reads two requests, then answers them in reverse order
+        std::fs::write(
+            &script,
+            r#"read first
+read second
+id() { echo "$1" | sed 's/.*"id":\([0-9]*\).*/\1/'; }
+a=$(id "$first")
+b=$(id "$second")
+echo "{\"jsonrpc\":\"2.0\",\"id\":$b,\"result\":{\"id\":$b}}"
+echo "{\"jsonrpc\":\"2.0\",\"id\":$a,\"result\":{\"id\":$a}}"
+"#,
+        )
+        .unwrap();
+
+        let mut cmd = Command::new("sh");
+        cmd.arg(&script);
+        let transport =
+            StdioTransport::spawn(cmd, Arc::new(DefaultHandler), StderrCapture::default())
+                .await
+                .unwrap();
+
+        let (first, second) = tokio::join!(
+            transport.request(JsonRpcRequest::new(1u64, "first", None)),
+            transport.request(JsonRpcRequest::new(2u64, "second", None)),
+        );
+
+        assert_eq!(first.unwrap().result, Some(json!({ "id": 1 })));
+        assert_eq!(second.unwrap().result, Some(json!({ "id": 2 })));
+
+        transport.close().await.unwrap();
+    }
+
+    async fn spawn_script(script: &str) -> StdioTransport {
+        let mut cmd = Command::new("sh");
+        cmd.args(["-c", script]);
+        let mut transport =
+            StdioTransport::spawn(cmd, Arc::new(DefaultHandler), StderrCapture::default())
+                .await
+                .unwrap();
+        transport.grace = Duration::from_millis(300);
+        transport
+    }
+
+    /// Whether a process is still running (rather than gone or a zombie)
+    fn running(pid: &str) -> bool {
+        std::fs::read_to_string(format!("/proc/{}/stat", pid.trim()))
+            .is_ok_and(|stat| !stat.contains(") Z "))
+    }
+
+    #[tokio::test]
+    async fn test_close_lets_server_exit_on_stdin_eof() {
+        let dir = tempfile::tempdir().unwrap();
+        let marker = dir.path().join("clean");
+        let transport = spawn_script(&format!(
+            "trap '' TERM; cat > /dev/null; echo done > {}",
+            marker.display()
+        ))
+        .await;
+
+        transport.close().await.unwrap();
+        assert!(marker.exists());
+    }
+
+    #[tokio::test]
+    async fn test_close_escalates_to_sigterm_then_sigkill() {
+        let dir = tempfile::tempdir().unwrap();
+        let marker = dir.path().join("terminated");
+        let transport = spawn_script(&format!(
+            "trap 'echo term > {}; exit 0' TERM; while :; do sleep 0.05; done",
+            marker.display()
+        ))
+        .await;
+        transport.close().await.unwrap();
+        assert!(marker.exists());
+
+        let transport = spawn_script("trap '' TERM; while :; do sleep 0.05; done").await;
+        let started = std::time::Instant::now();
+        transport.close().await.unwrap();
+        assert!(started.elapsed() < Duration::from_secs(2));
+        let status = transport.child.lock().await.try_wait().unwrap().unwrap();
+        assert!(!status.success());
+    }
+
+    #[cfg(target_os = "linux")]
+    #[tokio::test]
+    async fn test_close_stops_the_whole_process_group() {
+        let dir = tempfile::tempdir().unwrap();
+        let pid_file = dir.path().join("grandchild.pid");
+        let transport = spawn_script(&format!(
+            "sleep 30 & echo $! > {}; cat > /dev/null",
+            pid_file.display()
+        ))
+        .await;
+        while !pid_file.exists() {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        let grandchild = std::fs::read_to_string(&pid_file).unwrap();
+        assert!(running(&grandchild));
+
+        transport.close().await.unwrap();
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert!(!running(&grandchild));
+    }
+
+    #[tokio::test]
+    async fn test_reports_early_exit_with_stderr_tail() {
+        let transport =
+            spawn_script("echo 'starting' >&2; echo 'missing API key' >&2; exit 3").await;
+        let error = transport
+            .request(JsonRpcRequest::new(1u64, "initialize", None))
+            .await
+            .unwrap_err()
+            .to_string();
+        assert!(error.contains("exit status: 3"), "{}", error);
+        assert!(
+            error.contains("stderr:\n  starting\n  missing API key"),
+            "{}",
+            error
+        );
+        transport.close().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_tees_stderr_to_log_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let log_path = dir.path().join("server.log");
+        let capture = StderrCapture {
+            server_name: "test".to_string(),
+            log_file: Some(std::fs::File::create(&log_path).unwrap()),
+        };
+        let mut cmd = Command::new("sh");
+        cmd.args(["-c", "printf 'one\\ntwo' >&2; cat > /dev/null"]);
+        let transport = StdioTransport::spawn(cmd, Arc::new(DefaultHandler), capture)
+            .await
+            .unwrap();
+
+        transport.close().await.unwrap();
+        transport
+            .stderr_reader
+            .lock()
+            .await
+            .take()
+            .unwrap()
+            .await
+            .unwrap();
+        assert_eq!(std::fs::read_to_string(&log_path).unwrap(), "one\ntwo\n");
     }
 }
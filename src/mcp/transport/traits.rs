@@ -1,12 +1,23 @@
-use crate::mcp::{JsonRpcRequest, JsonRpcResponse};
+use crate::mcp::{JsonRpcNotification, JsonRpcRequest, JsonRpcResponse};
 use anyhow::Result;
 use async_trait::async_trait;
 
 #[async_trait]
 pub trait Transport: Send + Sync {
-    /// Send a request and receive a response
-    async fn request(&mut self, req: JsonRpcRequest) -> Result<JsonRpcResponse>;
+    /// Send a request and receive its response. Several requests may be in
+    /// flight at once; responses are matched to callers by request id.
+    async fn request(&self, req: JsonRpcRequest) -> Result<JsonRpcResponse>;
+
+    /// Send a notification, which has no response
+    async fn notify(&self, notification: JsonRpcNotification) -> Result<()>;
 
     /// Close the transport
-    async fn close(&mut self) -> Result<()>;
+    async fn close(&self) -> Result<()>;
+}
+
+/// Supplies a fresh access token when the server rejects the current one
+#[async_trait]
+pub trait TokenRefresher: Send + Sync {
+    /// Return a replacement for `stale_token`
+    async fn refresh(&self, stale_token: &str) -> Result<String>;
 }
@@ -0,0 +1,488 @@
+use super::http::HttpTransportError;
+use super::{PendingRequests, TokenRefresher, Transport, TransportError};
+use crate::auth::authorization_value;
+use crate::mcp::{
+    DefaultHandler, JsonRpcMessage, JsonRpcNotification, JsonRpcRequest, JsonRpcResponse,
+    MessageHandler,
+};
+use anyhow::{anyhow, bail, Context, Result};
+use async_trait::async_trait;
+use futures_util::{SinkExt, StreamExt};
+use reqwest::header::{HeaderMap, HeaderValue, AUTHORIZATION};
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+use tokio::net::TcpStream;
+use tokio::sync::{mpsc, Mutex};
+use tokio::task::JoinHandle;
+use tokio_tungstenite::tungstenite::client::IntoClientRequest;
+use tokio_tungstenite::tungstenite::{self, Message};
+use tokio_tungstenite::{MaybeTlsStream, WebSocketStream};
+
+/// How often to ping an otherwise quiet server
+const DEFAULT_PING_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Whether a URL selects the WebSocket transport
+pub fn is_websocket_url(url: &str) -> bool {
+    url.starts_with("ws://") || url.starts_with("wss://")
+}
+
+struct WebSocketConnection {
+    outgoing: mpsc::UnboundedSender<Message>,
+    pending: PendingRequests,
+    task: JoinHandle<()>,
+}
+
+/// JSON-RPC over a WebSocket (`ws://` or `wss://`), one message per text frame
+pub struct WebSocketTransport {
+    url: String,
+    server_name: String,
+    headers: HeaderMap,
+    access_token: RwLock<Option<String>>,
+    token_refresher: Option<Arc<dyn TokenRefresher>>,
+    connect_timeout: Option<Duration>,
+    ping_interval: Duration,
+    connection: Mutex<Option<WebSocketConnection>>,
+    handler: Arc<dyn MessageHandler>,
+}
+
+impl WebSocketTransport {
+    pub fn new(url: String, server_name: String) -> Self {
+        Self {
+            url,
+            server_name,
+            headers: HeaderMap::new(),
+            access_token: RwLock::new(None),
+            token_refresher: None,
+            connect_timeout: None,
+            ping_interval: DEFAULT_PING_INTERVAL,
+            connection: Mutex::new(None),
+            handler: Arc::new(DefaultHandler),
+        }
+    }
+
+    /// Extra headers for the handshake request
+    pub fn with_headers(mut self, headers: HeaderMap) -> Self {
+        self.headers = headers;
+        self
+    }
+
+    pub fn with_token(mut self, token: Option<String>) -> Self {
+        self.access_token = RwLock::new(token);
+        self
+    }
+
+    /// Refresh the token and reconnect once when the server rejects it
+    pub fn with_token_refresher(mut self, refresher: Option<Arc<dyn TokenRefresher>>) -> Self {
+        self.token_refresher = refresher;
+        self
+    }
+
+    /// Handle notifications and requests the server sends on its own
+    pub fn with_handler(mut self, handler: Arc<dyn MessageHandler>) -> Self {
+        self.handler = handler;
+        self
+    }
+
+    pub fn with_connect_timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.connect_timeout = timeout;
+        self
+    }
+
+    /// Ping the server this often; a server that misses a pong is disconnected
+    #[cfg(test)]
+    pub fn with_ping_interval(mut self, interval: Duration) -> Self {
+        self.ping_interval = interval;
+        self
+    }
+
+    fn token(&self) -> Option<String> {
+        self.access_token.read().unwrap().clone()
+    }
+
+    async fn ensure_connected(&self) -> Result<(mpsc::UnboundedSender<Message>, PendingRequests)> {
+        // Hold the lock while connecting so concurrent callers share one socket
+        let mut conn = self.connection.lock().await;
+        if let Some(ref c) = *conn {
+            // A new socket would be a session the server never initialized
+            if c.task.is_finished() {
+                bail!(
+                    "WebSocket connection to {} closed; reconnect to start a new session",
+                    self.url
+                );
+            }
+            return Ok((c.outgoing.clone(), c.pending.clone()));
+        }
+
+        let socket = self.handshake().await?;
+        let (outgoing, rx) = mpsc::unbounded_channel();
+        let pending = PendingRequests::default();
+        let task = tokio::spawn(run_connection(
+            socket,
+            rx,
+            outgoing.clone(),
+            pending.clone(),
+            self.handler.clone(),
+            self.ping_interval,
+        ));
+
+        *conn = Some(WebSocketConnection {
+            outgoing: outgoing.clone(),
+            pending: pending.clone(),
+            task,
+        });
+        Ok((outgoing, pending))
+    }
+
+    async fn handshake(&self) -> Result<WebSocketStream<MaybeTlsStream<TcpStream>>> {
+        let mut request = self
+            .url
+            .as_str()
+            .into_client_request()
+            .with_context(|| format!("Invalid WebSocket URL: {}", self.url))?;
+        request.headers_mut().extend(self.headers.clone());
+        if let Some(token) = self.token() {
+            let value = HeaderValue::from_str(&authorization_value(&token))
+                .context("Invalid access token")?;
+            request.headers_mut().insert(AUTHORIZATION, value);
+        }
+
+        // Small JSON-RPC frames shouldn't wait on Nagle's algorithm
+        let connect = tokio_tungstenite::connect_async_with_config(request, None, true);
+        let result = match self.connect_timeout {
+            Some(timeout) => tokio::time::timeout(timeout, connect).await.map_err(|_| {
+                TransportError::Retryable {
+                    message: format!("Timed out connecting to {}", self.url),
+                    retry_after: None,
+                }
+            })?,
+            None => connect.await,
+        };
+
+        match result {
+            Ok((socket, _)) => Ok(socket),
+            Err(tungstenite::Error::Http(response)) => {
+                let status = response.status();
+                if status == reqwest::StatusCode::UNAUTHORIZED {
+                    return Err(HttpTransportError::AuthRequired {
+                        server_name: self.server_name.clone(),
+                    }
+                    .into());
+                }
+                let body = response
+                    .body()
+                    .as_deref()
+                    .map(String::from_utf8_lossy)
+                    .unwrap_or_default();
+                Err(
+                    TransportError::from_status(status, response.headers(), &body)
+                        .context("WebSocket handshake failed"),
+                )
+            }
+            Err(tungstenite::Error::Io(e)) => Err(TransportError::Retryable {
+                message: format!("Failed to connect to {}: {}", self.url, e),
+                retry_after: None,
+            }
+            .into()),
+            Err(e) => {
+                Err(anyhow::Error::new(e)
+                    .context(format!("Failed to open WebSocket to {}", self.url)))
+            }
+        }
+    }
+
+    async fn request_once(&self, req: &JsonRpcRequest) -> Result<JsonRpcResponse> {
+        let (outgoing, pending) = self.ensure_connected().await?;
+        let id = req.id.clone();
+        let rx = pending
+            .register(id.clone())
+            .map_err(|_| anyhow!("WebSocket closed before request was sent"))?;
+
+        let json = serde_json::to_string(req)?;
+        if outgoing.send(Message::text(json)).is_err() {
+            pending.cancel(&id);
+            return Err(anyhow!("WebSocket closed before request was sent"));
+        }
+
+        rx.await
+            .map_err(|_| anyhow!("WebSocket closed before response received"))
+    }
+}
+
+/// Own the socket: write queued messages, route incoming ones by request id
+/// and keep the connection alive with pings
+async fn run_connection(
+    mut socket: WebSocketStream<MaybeTlsStream<TcpStream>>,
+    mut outgoing: mpsc::UnboundedReceiver<Message>,
+    replies: mpsc::UnboundedSender<Message>,
+    pending: PendingRequests,
+    handler: Arc<dyn MessageHandler>,
+    ping_interval: Duration,
+) {
+    let mut ping =
+        tokio::time::interval_at(tokio::time::Instant::now() + ping_interval, ping_interval);
+    let mut awaiting_pong = false;
+
+    loop {
+        tokio::select! {
+            message = outgoing.recv() => {
+                let Some(message) = message else { break };
+                if let Err(e) = socket.send(message).await {
+                    tracing::debug!("Failed to write to WebSocket: {}", e);
+                    break;
+                }
+            }
+            _ = ping.tick() => {
+                if awaiting_pong {
+                    tracing::debug!("WebSocket server missed a pong; disconnecting");
+                    break;
+                }
+                if socket.send(Message::Ping(Default::default())).await.is_err() {
+                    break;
+                }
+                awaiting_pong = true;
+            }
+            frame = socket.next() => {
+                // Anything from the server shows the connection is alive
+                awaiting_pong = false;
+                let text = match frame {
+                    Some(Ok(Message::Text(text))) => text,
+                    Some(Ok(Message::Close(_))) | None => break,
+                    // Pings are answered by tungstenite itself
+                    Some(Ok(_)) => continue,
+                    Some(Err(e)) => {
+                        tracing::debug!("WebSocket read failed: {}", e);
+                        break;
+                    }
+                };
+                route_message(text.as_str(), &pending, &handler, &replies).await;
+            }
+        }
+    }
+
+    socket.close(None).await.ok();
+    pending.close();
+}
+
+/// Deliver one message: responses to their waiting callers, notifications to
+/// the handler, and server requests answered over the socket
+async fn route_message(
+    text: &str,
+    pending: &PendingRequests,
+    handler: &Arc<dyn MessageHandler>,
+    replies: &mpsc::UnboundedSender<Message>,
+) {
+    let message: JsonRpcMessage = match serde_json::from_str(text) {
+        Ok(message) => message,
+        Err(e) => {
+            tracing::debug!("Ignoring unparseable WebSocket message ({}): {}", e, text);
+            return;
+        }
+    };
+
+    match message {
+        JsonRpcMessage::Response(response) => {
+            let id = response.id.clone();
+            if !pending.complete(response) {
+                tracing::debug!("Ignoring response for unknown request id {:?}", id);
+            }
+        }
+        JsonRpcMessage::Notification(notification) => {
+            handler.on_notification(notification).await;
+        }
+        JsonRpcMessage::Request(request) => {
+            // Answer in the background so a slow handler doesn't stall responses
+            let handler = handler.clone();
+            let replies = replies.clone();
+            tokio::spawn(async move {
+                let response = handler.on_request(request).await;
+                match serde_json::to_string(&response) {
+                    Ok(json) => {
+                        replies.send(Message::text(json)).ok();
+                    }
+                    Err(e) => tracing::debug!("Failed to serialize response: {}", e),
+                }
+            });
+        }
+    }
+}
+
+#[async_trait]
+impl Transport for WebSocketTransport {
+    async fn request(&self, req: JsonRpcRequest) -> Result<JsonRpcResponse> {
+        let result = self.request_once(&req).await;
+
+        let Some(refresher) = &self.token_refresher else {
+            return result;
+        };
+        match result {
+            Err(e) if e.downcast_ref::<HttpTransportError>().is_some() => {
+                let stale = self.token().unwrap_or_default();
+                match refresher.refresh(&stale).await {
+                    Ok(token) => {
+                        *self.access_token.write().unwrap() = Some(token);
+                        self.request_once(&req).await
+                    }
+                    Err(refresh_err) => {
+                        tracing::debug!("Token refresh failed: {:#}", refresh_err);
+                        Err(e)
+                    }
+                }
+            }
+            other => other,
+        }
+    }
+
+    async fn notify(&self, notification: JsonRpcNotification) -> Result<()> {
+        let (outgoing, _) = self.ensure_connected().await?;
+        let json = serde_json::to_string(&notification)?;
+        outgoing
+            .send(Message::text(json))
+            .map_err(|_| anyhow!("WebSocket closed before notification was sent"))
+    }
+
+    async fn close(&self) -> Result<()> {
+        let mut conn = self.connection.lock().await;
+        if let Some(c) = conn.take() {
+            c.outgoing.send(Message::Close(None)).ok();
+            // Give the task a moment to send the close frame
+            let mut task = c.task;
+            if tokio::time::timeout(Duration::from_secs(1), &mut task)
+                .await
+                .is_err()
+            {
+                task.abort();
+            }
+            c.pending.close();
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::{json, Value};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use tokio::net::TcpListener;
+
+    /// Accept one WebSocket connection that checks the Authorization header,
+    /// holds back its replies until two requests are in and answers them in
+    /// reverse order. Counts the pings it receives.
+    async fn start_server() -> (String, Arc<AtomicUsize>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("ws://{}", listener.local_addr().unwrap());
+        let pings = Arc::new(AtomicUsize::new(0));
+
+        let counter = pings.clone();
+        tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            stream.set_nodelay(true).unwrap();
+            #[allow(clippy::result_large_err)]
+            let check_auth = |request: &tungstenite::handshake::server::Request, response| {
+                assert_eq!(request.headers()["authorization"], "Bearer secret");
+                Ok(response)
+            };
+            let mut socket = tokio_tungstenite::accept_hdr_async(stream, check_auth)
+                .await
+                .unwrap();
+
+            let mut ids = Vec::new();
+            while let Some(Ok(message)) = socket.next().await {
+                match message {
+                    Message::Text(text) => {
+                        let request: Value = serde_json::from_str(text.as_str()).unwrap();
+                        ids.push(request["id"].clone());
+                    }
+                    Message::Ping(_) => {
+                        counter.fetch_add(1, Ordering::SeqCst);
+                    }
+                    _ => {}
+                }
+                if ids.len() == 2 {
+                    for id in ids.drain(..).rev() {
+                        let response = json!({"jsonrpc": "2.0", "id": id, "result": {"id": id}});
+                        socket
+                            .send(Message::text(response.to_string()))
+                            .await
+                            .unwrap();
+                    }
+                }
+            }
+        });
+
+        (url, pings)
+    }
+
+    #[tokio::test]
+    async fn test_requests_matched_by_id_with_keepalive() {
+        let (url, pings) = start_server().await;
+        let transport = WebSocketTransport::new(url, "test".to_string())
+            .with_token(Some("secret".to_string()))
+            .with_ping_interval(Duration::from_millis(50));
+
+        let first = transport.request(JsonRpcRequest::new(1u64, "first", None));
+        let second = async {
+            // Let a few pings go out while the first request waits
+            tokio::time::sleep(Duration::from_millis(300)).await;
+            transport
+                .request(JsonRpcRequest::new(2u64, "second", None))
+                .await
+        };
+        let (first, second) = tokio::join!(first, second);
+
+        assert_eq!(first.unwrap().result, Some(json!({ "id": 1 })));
+        assert_eq!(second.unwrap().result, Some(json!({ "id": 2 })));
+        assert!(pings.load(Ordering::SeqCst) >= 2);
+
+        transport.close().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_no_reconnect_after_server_drops() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("ws://{}", listener.local_addr().unwrap());
+        let connections = Arc::new(AtomicUsize::new(0));
+
+        // Answer one request, then drop the connection
+        let counter = connections.clone();
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                counter.fetch_add(1, Ordering::SeqCst);
+                let mut socket = tokio_tungstenite::accept_async(stream).await.unwrap();
+                if let Some(Ok(Message::Text(text))) = socket.next().await {
+                    let request: Value = serde_json::from_str(text.as_str()).unwrap();
+                    let response = json!({"jsonrpc": "2.0", "id": request["id"], "result": {}});
+                    socket
+                        .send(Message::text(response.to_string()))
+                        .await
+                        .unwrap();
+                }
+            }
+        });
+
+        let transport = WebSocketTransport::new(url, "test".to_string());
+        let first = transport
+            .request(JsonRpcRequest::new(1u64, "initialize", None))
+            .await;
+        assert!(first.is_ok());
+
+        // The request fails instead of going to a fresh, uninitialized socket
+        let second = transport
+            .request(JsonRpcRequest::new(2u64, "tools/list", None))
+            .await;
+        let error = format!("{:#}", second.unwrap_err());
+        assert!(error.contains("closed"), "unexpected error: {}", error);
+        assert!(transport
+            .notify(JsonRpcNotification::new("notifications/initialized", None))
+            .await
+            .is_err());
+        assert_eq!(connections.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn test_is_websocket_url() {
+        assert!(is_websocket_url("ws://localhost:8080/mcp"));
+        assert!(is_websocket_url("wss://example.com/mcp"));
+        assert!(!is_websocket_url("https://example.com/mcp"));
+    }
+}
@@ -0,0 +1,164 @@
+use anyhow::{anyhow, bail, Result};
+use std::collections::HashMap;
+
+/// Characters allowed unencoded by the `+` and `#` operators (RFC 3986 reserved set)
+const RESERVED: &str = ":/?#[]@!$&'()*+,;=";
+
+/// Expansion rules for a single RFC 6570 expression operator
+struct Operator {
+    first: &'static str,
+    separator: &'static str,
+    named: bool,
+    allow_reserved: bool,
+}
+
+impl Operator {
+    fn from_char(c: Option<char>) -> Self {
+        let (first, separator, named, allow_reserved) = match c {
+            Some('+') => ("", ",", false, true),
+            Some('#') => ("#", ",", false, true),
+            Some('.') => (".", ".", false, false),
+            Some('/') => ("/", "/", false, false),
+            Some(';') => (";", ";", true, false),
+            Some('?') => ("?", "&", true, false),
+            Some('&') => ("&", "&", true, false),
+            _ => ("", ",", false, false),
+        };
+        Self {
+            first,
+            separator,
+            named,
+            allow_reserved,
+        }
+    }
+
+    /// Query-style expressions may legitimately leave variables undefined
+    fn is_optional(&self) -> bool {
+        self.first == "?" || self.first == "&"
+    }
+}
+
+fn encode(value: &str, allow_reserved: bool) -> String {
+    if !allow_reserved {
+        return urlencoding::encode(value).into_owned();
+    }
+    value
+        .chars()
+        .map(|c| {
+            if RESERVED.contains(c) {
+                c.to_string()
+            } else {
+                urlencoding::encode(&c.to_string()).into_owned()
+            }
+        })
+        .collect()
+}
+
+/// Expand an RFC 6570 URI template (levels 1-3, plus prefix modifiers)
+pub fn expand_uri_template(template: &str, vars: &HashMap<String, String>) -> Result<String> {
+    let mut result = String::new();
+    let mut rest = template;
+
+    while let Some(start) = rest.find('{') {
+        result.push_str(&rest[..start]);
+        let end = rest[start..]
+            .find('}')
+            .ok_or_else(|| anyhow!("Unterminated expression in URI template: {}", template))?;
+        let expression = &rest[start + 1..start + end];
+
+        let op_char = expression.chars().next().filter(|c| "+#./;?&".contains(*c));
+        let op = Operator::from_char(op_char);
+        let var_list = if op_char.is_some() {
+            &expression[1..]
+        } else {
+            expression
+        };
+
+        let mut parts = Vec::new();
+        for spec in var_list.split(',') {
+            let spec = spec.trim_end_matches('*');
+            let (name, max_len) = match spec.split_once(':') {
+                Some((name, len)) => (
+                    name,
+                    Some(
+                        len.parse::<usize>()
+                            .map_err(|_| anyhow!("Invalid prefix length in '{}'", spec))?,
+                    ),
+                ),
+                None => (spec, None),
+            };
+
+            let Some(value) = vars.get(name) else {
+                if op.is_optional() {
+                    continue;
+                }
+                bail!("Missing value for template variable '{}'", name);
+            };
+
+            let value: String = match max_len {
+                Some(len) => value.chars().take(len).collect(),
+                None => value.clone(),
+            };
+            let encoded = encode(&value, op.allow_reserved);
+
+            if op.named {
+                if encoded.is_empty() && op.first == ";" {
+                    parts.push(name.to_string());
+                } else {
+                    parts.push(format!("{}={}", name, encoded));
+                }
+            } else {
+                parts.push(encoded);
+            }
+        }
+
+        if !parts.is_empty() {
+            result.push_str(op.first);
+            result.push_str(&parts.join(op.separator));
+        }
+
+        rest = &rest[start + end + 1..];
+    }
+
+    result.push_str(rest);
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vars(pairs: &[(&str, &str)]) -> HashMap<String, String> {
+        pairs
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_expand_simple_and_reserved() {
+        let v = vars(&[("path", "docs/read me.md"), ("host", "example.com")]);
+        assert_eq!(
+            expand_uri_template("file:///{path}", &v).unwrap(),
+            "file:///docs%2Fread%20me.md"
+        );
+        assert_eq!(
+            expand_uri_template("file:///{+path}", &v).unwrap(),
+            "file:///docs/read%20me.md"
+        );
+        assert_eq!(
+            expand_uri_template("http://{host}{/path}", &v).unwrap(),
+            "http://example.com/docs%2Fread%20me.md"
+        );
+    }
+
+    #[test]
+    fn test_expand_query_skips_missing() {
+        let v = vars(&[("owner", "acme"), ("state", "open")]);
+        assert_eq!(
+            expand_uri_template("repo://{owner}/issues{?state,labels}", &v).unwrap(),
+            "repo://acme/issues?state=open"
+        );
+        assert!(expand_uri_template("repo://{owner}/{repo}", &v).is_err());
+    }
+}
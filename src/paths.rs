@@ -0,0 +1,42 @@
+//! Per-user locations outside the config directory, following the XDG base
+//! directory conventions
+
+use anyhow::{Context, Result};
+use std::path::PathBuf;
+
+/// `$XDG_CACHE_HOME/relay`, defaulting to `~/.cache/relay`
+pub fn cache_dir() -> Result<PathBuf> {
+    base_dir("XDG_CACHE_HOME", ".cache")
+}
+
+/// `$XDG_STATE_HOME/relay`, defaulting to `~/.local/state/relay`
+pub fn state_dir() -> Result<PathBuf> {
+    base_dir("XDG_STATE_HOME", ".local/state")
+}
+
+fn base_dir(variable: &str, default: &str) -> Result<PathBuf> {
+    let base = match std::env::var_os(variable) {
+        Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => {
+            let home = std::env::var("HOME").context("HOME environment variable not set")?;
+            PathBuf::from(home).join(default)
+        }
+    };
+    Ok(base.join("relay"))
+}
+
+/// A file name for per-server data. Server names are free-form, so anything
+/// that isn't safe in a single path component is replaced.
+pub fn server_file_name(server_name: &str, extension: &str) -> String {
+    let stem: String = server_name
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '-' || c == '_' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    format!("{}.{}", stem, extension)
+}
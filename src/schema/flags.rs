@@ -39,7 +39,6 @@ fn kebab_to_camel(s: &str) -> String {
 #[derive(Debug, Clone)]
 pub struct SchemaFlag {
     pub name: String,
-    #[allow(dead_code)]
     pub description: String,
     pub required: bool,
     pub flag_type: FlagType,
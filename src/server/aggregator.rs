@@ -0,0 +1,257 @@
+use crate::commands::{connect, session_roots};
+use crate::config::Config;
+use crate::config::ConfigStore;
+use crate::mcp::{
+    ContentItem, InitializeResult, JsonRpcRequest, JsonRpcResponse, McpClient, Root,
+    ServerCapabilities, ServerInfo, Tool, ToolCallParams, ToolCallResult, ToolsCapability,
+    INTERNAL_ERROR, INVALID_PARAMS, METHOD_NOT_FOUND, PROTOCOL_VERSION,
+};
+use anyhow::{anyhow, Result};
+use futures_util::future::join_all;
+use serde_json::{json, Value};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+use tokio::sync::OnceCell;
+
+/// Separator between the server name and tool name in re-exported tools
+pub const NAMESPACE_SEPARATOR: &str = "__";
+
+/// Fronts every configured server as a single MCP server, re-exporting their
+/// tools as `<server>__<tool>` and forwarding calls to the owning server.
+///
+/// Backend connections are opened on first use and shared by all sessions.
+pub struct Aggregator {
+    store: ConfigStore,
+    backends: BTreeMap<String, OnceCell<Arc<McpClient>>>,
+    roots: Mutex<ConfiguredRoots>,
+}
+
+/// Each server's configured roots, as of the config files' last modification
+struct ConfiguredRoots {
+    modified: Vec<Option<SystemTime>>,
+    roots: HashMap<String, Vec<Root>>,
+}
+
+impl ConfiguredRoots {
+    fn new(modified: Vec<Option<SystemTime>>, config: &Config) -> Self {
+        Self {
+            modified,
+            roots: config
+                .servers
+                .iter()
+                .map(|(name, server)| (name.clone(), session_roots(server)))
+                .collect(),
+        }
+    }
+}
+
+impl Aggregator {
+    pub fn new(store: ConfigStore) -> Result<Self> {
+        let modified = store.modified();
+        let config = store.load()?;
+        let backends = config
+            .servers
+            .keys()
+            .map(|name| (name.clone(), OnceCell::new()))
+            .collect();
+        let roots = Mutex::new(ConfiguredRoots::new(modified, &config));
+        Ok(Self {
+            store,
+            backends,
+            roots,
+        })
+    }
+
+    /// A server's configured roots, reloading the config only once its files
+    /// have changed
+    fn roots(&self, server_name: &str) -> Result<Option<Vec<Root>>> {
+        let mut roots = self.roots.lock().unwrap();
+        let modified = self.store.modified();
+        if roots.modified != modified {
+            *roots = ConfiguredRoots::new(modified, &self.store.load()?);
+        }
+        Ok(roots.roots.get(server_name).cloned())
+    }
+
+    /// Get (connecting if necessary) the client for a backend server, telling
+    /// it about any change to its configured roots
+    async fn client(&self, server_name: &str) -> Result<Arc<McpClient>> {
+        let cell = self
+            .backends
+            .get(server_name)
+            .ok_or_else(|| anyhow!("Server '{}' not found", server_name))?;
+        let client = cell
+            .get_or_try_init(|| async { connect(&self.store, server_name).await.map(Arc::new) })
+            .await?;
+        if let Some(roots) = self.roots(server_name)? {
+            client.set_roots(roots).await?;
+        }
+        Ok(client.clone())
+    }
+
+    /// Split a namespaced tool name into its server and tool parts, preferring
+    /// the longest server name when several match
+    fn resolve_tool<'a>(&self, name: &'a str) -> Option<(&str, &'a str)> {
+        self.backends
+            .keys()
+            .filter_map(|server| {
+                name.strip_prefix(server.as_str())
+                    .and_then(|rest| rest.strip_prefix(NAMESPACE_SEPARATOR))
+                    .map(|tool| (server.as_str(), tool))
+            })
+            .max_by_key(|(server, _)| server.len())
+    }
+
+    /// Answer a single request from a downstream client
+    pub async fn handle_request(&self, req: JsonRpcRequest) -> JsonRpcResponse {
+        let id = req.id.clone();
+        let result = match req.method.as_str() {
+            "initialize" => serde_json::to_value(self.initialize_result()).map_err(Into::into),
+            "ping" => Ok(json!({})),
+            "tools/list" => self
+                .list_tools()
+                .await
+                .map(|tools| json!({ "tools": tools })),
+            "tools/call" => {
+                let params: ToolCallParams =
+                    match serde_json::from_value(req.params.unwrap_or(Value::Null)) {
+                        Ok(params) => params,
+                        Err(e) => {
+                            return JsonRpcResponse::failure(
+                                id,
+                                INVALID_PARAMS,
+                                format!("Invalid tools/call params: {}", e),
+                            )
+                        }
+                    };
+                let Some((server, tool)) = self.resolve_tool(&params.name) else {
+                    return JsonRpcResponse::failure(
+                        id,
+                        INVALID_PARAMS,
+                        format!("Unknown tool: {}", params.name),
+                    );
+                };
+                let result = self.call_tool(server, tool, params.arguments).await;
+                serde_json::to_value(result).map_err(Into::into)
+            }
+            method => {
+                return JsonRpcResponse::failure(
+                    id,
+                    METHOD_NOT_FOUND,
+                    format!("Method not found: {}", method),
+                )
+            }
+        };
+
+        match result {
+            Ok(result) => JsonRpcResponse::success(id, result),
+            Err(e) => JsonRpcResponse::failure(id, INTERNAL_ERROR, format!("{:#}", e)),
+        }
+    }
+
+    fn initialize_result(&self) -> InitializeResult {
+        InitializeResult {
+            protocol_version: PROTOCOL_VERSION.to_string(),
+            capabilities: ServerCapabilities {
+                tools: Some(ToolsCapability::default()),
+                ..Default::default()
+            },
+            server_info: ServerInfo {
+                name: "relay".to_string(),
+                version: Some(env!("CARGO_PKG_VERSION").to_string()),
+            },
+        }
+    }
+
+    /// List the tools of every backend. Unreachable servers are skipped so one
+    /// broken server doesn't hide the others.
+    async fn list_tools(&self) -> Result<Vec<Tool>> {
+        let listings = join_all(self.backends.keys().map(|server| async move {
+            let tools = async { self.client(server).await?.list_tools().await }.await;
+            (server, tools)
+        }))
+        .await;
+
+        let mut all_tools = Vec::new();
+        for (server, tools) in listings {
+            match tools {
+                Ok(tools) => all_tools.extend(tools.into_iter().map(|tool| Tool {
+                    name: format!("{}{}{}", server, NAMESPACE_SEPARATOR, tool.name),
+                    ..tool
+                })),
+                Err(e) => tracing::warn!("Skipping server '{}': {:#}", server, e),
+            }
+        }
+
+        Ok(all_tools)
+    }
+
+    /// Forward a tool call. Failures are reported as tool errors so the calling
+    /// agent sees them instead of a protocol error.
+    async fn call_tool(
+        &self,
+        server: &str,
+        tool: &str,
+        arguments: std::collections::HashMap<String, Value>,
+    ) -> ToolCallResult {
+        let result = async { self.client(server).await?.call_tool(tool, arguments).await }.await;
+        result.unwrap_or_else(|e| ToolCallResult {
+            content: vec![ContentItem::Text {
+                text: format!("relay: {}: {:#}", server, e),
+            }],
+            is_error: true,
+            structured_content: None,
+        })
+    }
+
+    /// Close every open backend connection
+    pub async fn close(&self) {
+        for cell in self.backends.values() {
+            if let Some(client) = cell.get() {
+                client.close().await.ok();
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{Config, ServerConfig, TransportConfig};
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_resolve_namespaced_tool() {
+        let dir = tempdir().unwrap();
+        let store = ConfigStore::with_path(dir.path().join("config.yaml"));
+        let mut config = Config::default();
+        for name in ["linear", "linear__beta"] {
+            config.servers.insert(
+                name.to_string(),
+                ServerConfig {
+                    transport: TransportConfig::Http {
+                        url: "http://localhost:3000".to_string(),
+                        headers: Default::default(),
+                        listen: false,
+                    },
+                    env: Default::default(),
+                    connection: Default::default(),
+                    roots: Vec::new(),
+                },
+            );
+        }
+        store.save(&config).unwrap();
+
+        let aggregator = Aggregator::new(store).unwrap();
+        assert_eq!(
+            aggregator.resolve_tool("linear__list_issues"),
+            Some(("linear", "list_issues"))
+        );
+        assert_eq!(
+            aggregator.resolve_tool("linear__beta__search"),
+            Some(("linear__beta", "search"))
+        );
+        assert_eq!(aggregator.resolve_tool("github__search"), None);
+    }
+}
@@ -0,0 +1,149 @@
+use super::Aggregator;
+use crate::mcp::{JsonRpcMessage, JsonRpcResponse, RequestId, PARSE_ERROR};
+use anyhow::{Context, Result};
+use axum::extract::State;
+use axum::http::{HeaderMap, HeaderValue, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::routing::post;
+use axum::{Json, Router};
+use reqwest::Url;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Arc;
+
+/// Path the streamable HTTP endpoint is mounted on
+pub const MCP_PATH: &str = "/mcp";
+
+/// Serve the aggregator over streamable HTTP until interrupted.
+///
+/// Every request is answered with a single JSON body; relay never initiates
+/// messages of its own, so the optional GET event stream is not offered.
+pub async fn serve_http(aggregator: Arc<Aggregator>, addr: SocketAddr) -> Result<()> {
+    let state = HttpState {
+        aggregator,
+        bound_ip: addr.ip(),
+    };
+    let app = Router::new()
+        .route(
+            MCP_PATH,
+            post(handle_post)
+                .get(|| async { StatusCode::METHOD_NOT_ALLOWED })
+                .delete(|| async { StatusCode::OK }),
+        )
+        .with_state(state);
+
+    let listener = tokio::net::TcpListener::bind(addr)
+        .await
+        .with_context(|| format!("Failed to bind {}", addr))?;
+    tracing::info!("Serving MCP on http://{}{}", addr, MCP_PATH);
+
+    axum::serve(listener, app)
+        .with_graceful_shutdown(async {
+            tokio::signal::ctrl_c().await.ok();
+        })
+        .await
+        .context("HTTP server failed")
+}
+
+#[derive(Clone)]
+struct HttpState {
+    aggregator: Arc<Aggregator>,
+    bound_ip: IpAddr,
+}
+
+async fn handle_post(State(state): State<HttpState>, headers: HeaderMap, body: String) -> Response {
+    if let Some(origin) = headers.get("origin") {
+        let origin = origin.to_str().unwrap_or_default();
+        if !origin_allowed(origin, state.bound_ip) {
+            tracing::debug!("Rejecting request from origin {}", origin);
+            return StatusCode::FORBIDDEN.into_response();
+        }
+    }
+
+    let message = match serde_json::from_str::<JsonRpcMessage>(&body) {
+        Ok(message) => message,
+        Err(e) => {
+            let response = JsonRpcResponse::failure(
+                RequestId::Null,
+                PARSE_ERROR,
+                format!("Parse error: {}", e),
+            );
+            return (StatusCode::BAD_REQUEST, Json(response)).into_response();
+        }
+    };
+
+    let JsonRpcMessage::Request(request) = message else {
+        // Notifications and responses need no reply
+        return StatusCode::ACCEPTED.into_response();
+    };
+
+    let is_initialize = request.method == "initialize";
+    let mut response = Json(state.aggregator.handle_request(request).await).into_response();
+
+    // Hand out a session id on initialize and echo it back afterwards
+    let session_id = if is_initialize {
+        HeaderValue::from_str(&generate_session_id()).ok()
+    } else {
+        headers.get("mcp-session-id").cloned()
+    };
+    if let Some(session_id) = session_id {
+        response.headers_mut().insert("mcp-session-id", session_id);
+    }
+
+    response
+}
+
+/// Browsers send an Origin with every cross-site request. Only pages served
+/// from this machine may call relay, so a website can't reach the servers
+/// behind it through the user's browser (e.g. via DNS rebinding).
+fn origin_allowed(origin: &str, bound_ip: IpAddr) -> bool {
+    let Some(host) = Url::parse(origin)
+        .ok()
+        .and_then(|url| url.host_str().map(str::to_owned))
+    else {
+        return false;
+    };
+    if host.eq_ignore_ascii_case("localhost") {
+        return true;
+    }
+    match host
+        .trim_start_matches('[')
+        .trim_end_matches(']')
+        .parse::<IpAddr>()
+    {
+        Ok(ip) => ip.is_loopback() || ip == bound_ip,
+        Err(_) => false,
+    }
+}
+
+/// 128 random bits from the OS, so session ids can't be guessed
+fn generate_session_id() -> String {
+    let mut bytes = [0u8; 16];
+    getrandom::fill(&mut bytes).expect("OS random number generator unavailable");
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_origin_allowed() {
+        let loopback: IpAddr = "127.0.0.1".parse().unwrap();
+        assert!(origin_allowed("http://localhost:3000", loopback));
+        assert!(origin_allowed("http://127.0.0.1:8080", loopback));
+        assert!(origin_allowed("http://[::1]", loopback));
+        assert!(!origin_allowed("https://evil.example", loopback));
+        assert!(!origin_allowed("null", loopback));
+
+        let lan: IpAddr = "192.168.1.20".parse().unwrap();
+        assert!(origin_allowed("http://192.168.1.20:8080", lan));
+        assert!(!origin_allowed("http://192.168.1.21:8080", lan));
+    }
+
+    #[test]
+    fn test_session_ids_are_random() {
+        let id = generate_session_id();
+        assert_eq!(id.len(), 32);
+        assert_ne!(id, generate_session_id());
+    }
+}
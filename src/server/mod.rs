@@ -0,0 +1,7 @@
+mod aggregator;
+mod http;
+mod stdio;
+
+pub use aggregator::*;
+pub use http::*;
+pub use stdio::*;
@@ -0,0 +1,60 @@
+use super::Aggregator;
+use crate::mcp::{JsonRpcMessage, JsonRpcResponse, RequestId, PARSE_ERROR};
+use anyhow::{Context, Result};
+use std::sync::Arc;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader, Stdout};
+use tokio::sync::Mutex;
+use tokio::task::JoinSet;
+
+/// Serve the aggregator over stdin/stdout until stdin is closed.
+///
+/// Requests are answered concurrently, so a slow tool call doesn't hold up
+/// the rest of the session.
+pub async fn serve_stdio(aggregator: Arc<Aggregator>) -> Result<()> {
+    let stdout = Arc::new(Mutex::new(tokio::io::stdout()));
+    let mut lines = BufReader::new(tokio::io::stdin()).lines();
+    let mut tasks = JoinSet::new();
+
+    while let Some(line) = lines.next_line().await.context("Failed to read stdin")? {
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        match serde_json::from_str::<JsonRpcMessage>(&line) {
+            Ok(JsonRpcMessage::Request(request)) => {
+                let aggregator = aggregator.clone();
+                let stdout = stdout.clone();
+                tasks.spawn(async move {
+                    let response = aggregator.handle_request(request).await;
+                    if let Err(e) = write_response(&stdout, &response).await {
+                        tracing::debug!("Failed to write response: {:#}", e);
+                    }
+                });
+            }
+            Ok(message) => {
+                tracing::debug!("Ignoring client message: {:?}", message);
+            }
+            Err(e) => {
+                let response = JsonRpcResponse::failure(
+                    RequestId::Null,
+                    PARSE_ERROR,
+                    format!("Parse error: {}", e),
+                );
+                write_response(&stdout, &response).await?;
+            }
+        }
+    }
+
+    while tasks.join_next().await.is_some() {}
+    Ok(())
+}
+
+async fn write_response(stdout: &Mutex<Stdout>, response: &JsonRpcResponse) -> Result<()> {
+    let mut line = serde_json::to_string(response)?;
+    line.push('\n');
+
+    let mut stdout = stdout.lock().await;
+    stdout.write_all(line.as_bytes()).await?;
+    stdout.flush().await?;
+    Ok(())
+}
@@ -1,13 +1,13 @@
-use assert_cmd::Command;
+use assert_cmd::cargo::cargo_bin_cmd;
 
 #[test]
 fn test_cli_help() {
-    let mut cmd = Command::cargo_bin("relay").unwrap();
+    let mut cmd = cargo_bin_cmd!("relay");
     cmd.arg("--help").assert().success();
 }
 
 #[test]
 fn test_cli_version() {
-    let mut cmd = Command::cargo_bin("relay").unwrap();
+    let mut cmd = cargo_bin_cmd!("relay");
     cmd.arg("--version").assert().success();
 }
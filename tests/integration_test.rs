@@ -1,20 +1,27 @@
+use assert_cmd::cargo::cargo_bin_cmd;
 use assert_cmd::Command;
 use predicates::prelude::*;
+use std::os::unix::fs::PermissionsExt;
+use std::time::Duration;
 use tempfile::tempdir;
 
 fn relay_cmd(config_path: &str) -> Command {
-    let mut cmd = Command::cargo_bin("relay").unwrap();
+    let mut cmd = cargo_bin_cmd!("relay");
     cmd.env("RELAY_CONFIG", config_path);
+    // Keep each test's cached tool lists and server logs beside its config
+    let config_dir = std::path::Path::new(config_path).parent().unwrap();
+    cmd.env("XDG_CACHE_HOME", config_dir.join("cache"));
+    cmd.env("XDG_STATE_HOME", config_dir.join("state"));
     cmd
 }
 
-#[test]
-fn test_full_workflow() {
-    let dir = tempdir().unwrap();
-    let config_path = dir.path().join("config.yaml");
-    let config_str = config_path.to_str().unwrap();
+/// Build the mock server and register it as `mock` in the given config
+fn add_mock_server(config_path: &str) {
+    add_mock_server_with_args(config_path, "");
+}
 
-    // Build mock server first
+/// Like `add_mock_server`, passing extra arguments to the mock server
+fn add_mock_server_with_args(config_path: &str, args: &str) {
     Command::new("cargo")
         .args(["build", "--bin", "mock-server"])
         .assert()
@@ -23,19 +30,30 @@ fn test_full_workflow() {
     let mock_server_path = std::env::current_dir()
         .unwrap()
         .join("target/debug/mock-server");
+    let command = format!("{} {}", mock_server_path.to_str().unwrap(), args);
 
-    // Add mock server
-    relay_cmd(config_str)
+    relay_cmd(config_path)
         .args([
             "add",
             "mock",
             "--transport",
             "stdio",
             "--cmd",
-            mock_server_path.to_str().unwrap(),
+            command.trim_end(),
+            "--log-stderr",
         ])
         .assert()
         .success();
+}
+
+#[test]
+fn test_full_workflow() {
+    let dir = tempdir().unwrap();
+    let config_path = dir.path().join("config.yaml");
+    let config_str = config_path.to_str().unwrap();
+
+    // Build and add mock server
+    add_mock_server(config_str);
 
     // List servers
     relay_cmd(config_str)
@@ -109,3 +127,1753 @@ fn test_full_workflow() {
         .success()
         .stdout(predicate::str::contains(r#""type": "text""#));
 }
+
+#[test]
+fn test_resources_workflow() {
+    let dir = tempdir().unwrap();
+    let config_path = dir.path().join("config.yaml");
+    let config_str = config_path.to_str().unwrap();
+
+    add_mock_server(config_str);
+
+    // List resources and templates
+    relay_cmd(config_str)
+        .args(["resources", "mock"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("mock://greeting"))
+        .stdout(predicate::str::contains("mock://greeting/{name}"));
+
+    // Read a text resource
+    relay_cmd(config_str)
+        .args(["read", "mock://greeting", "--server", "mock"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Hello from mock"));
+
+    // Expand a resource template
+    relay_cmd(config_str)
+        .args([
+            "read",
+            "mock://greeting/{name}",
+            "--server",
+            "mock",
+            "--var",
+            "name=Ada",
+        ])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Hello, Ada!"));
+
+    // Missing template variables are reported
+    relay_cmd(config_str)
+        .args(["read", "mock://greeting/{name}", "--server", "mock"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("name"));
+
+    // Write a binary blob to a file
+    let blob_path = dir.path().join("logo.png");
+    relay_cmd(config_str)
+        .args([
+            "read",
+            "mock://logo.png",
+            "--server",
+            "mock",
+            "--output",
+            blob_path.to_str().unwrap(),
+        ])
+        .assert()
+        .success();
+    assert_eq!(std::fs::read(&blob_path).unwrap(), b"relay");
+
+    // JSON output returns raw contents
+    relay_cmd(config_str)
+        .args([
+            "--format",
+            "json",
+            "read",
+            "mock://logo.png",
+            "--server",
+            "mock",
+        ])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(r#""blob": "cmVsYXk=""#));
+}
+
+#[test]
+fn test_resources_without_templates() {
+    let dir = tempdir().unwrap();
+    let config_path = dir.path().join("config.yaml");
+    let config_str = config_path.to_str().unwrap();
+    add_mock_server_with_args(config_str, "--no-templates");
+
+    // A server that doesn't know resources/templates/list has no templates
+    relay_cmd(config_str)
+        .args(["resources", "mock"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("mock://greeting"))
+        .stdout(predicate::str::contains("0 template(s)"));
+}
+
+#[test]
+fn test_prompts_workflow() {
+    let dir = tempdir().unwrap();
+    let config_path = dir.path().join("config.yaml");
+    let config_str = config_path.to_str().unwrap();
+
+    add_mock_server(config_str);
+
+    // List prompts with their arguments
+    relay_cmd(config_str)
+        .args(["prompts", "mock"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("greet"))
+        .stdout(predicate::str::contains("--style"));
+
+    // Render a prompt with flags
+    relay_cmd(config_str)
+        .args([
+            "prompt", "greet", "--server", "mock", "--name", "Ada", "--style", "formal",
+        ])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("[user]"))
+        .stdout(predicate::str::contains("Write a formal greeting for Ada"))
+        .stdout(predicate::str::contains("[assistant]"));
+
+    // Required arguments are enforced
+    relay_cmd(config_str)
+        .args(["prompt", "greet", "--server", "mock"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("--name"));
+
+    // JSON output returns the raw messages
+    relay_cmd(config_str)
+        .args([
+            "--format", "json", "prompt", "greet", "--server", "mock", "--name", "Ada",
+        ])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(r#""role": "assistant""#));
+}
+
+#[test]
+fn test_interleaved_server_messages() {
+    let dir = tempdir().unwrap();
+    let config_path = dir.path().join("config.yaml");
+    let config_str = config_path.to_str().unwrap();
+
+    // The mock sends notifications and a ping before every response
+    add_mock_server_with_args(config_str, "--interleave");
+
+    relay_cmd(config_str)
+        .args(["tools", "mock"])
+        .timeout(Duration::from_secs(10))
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("echo"));
+
+    relay_cmd(config_str)
+        .args([
+            "run",
+            "echo",
+            "--server",
+            "mock",
+            "--message",
+            "interleaved",
+        ])
+        .timeout(Duration::from_secs(10))
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Echo: interleaved"));
+}
+
+#[test]
+fn test_serve_aggregates_servers() {
+    let dir = tempdir().unwrap();
+    let config_path = dir.path().join("config.yaml");
+    let config_str = config_path.to_str().unwrap();
+
+    add_mock_server(config_str);
+
+    let input = [
+        r#"{"jsonrpc":"2.0","id":1,"method":"initialize","params":{}}"#,
+        r#"{"jsonrpc":"2.0","method":"notifications/initialized"}"#,
+        r#"{"jsonrpc":"2.0","id":2,"method":"tools/list"}"#,
+        r#"{"jsonrpc":"2.0","id":3,"method":"tools/call","params":{"name":"mock__echo","arguments":{"message":"hi"}}}"#,
+        r#"{"jsonrpc":"2.0","id":4,"method":"tools/call","params":{"name":"nope__echo"}}"#,
+    ]
+    .join("\n");
+
+    relay_cmd(config_str)
+        .arg("serve")
+        .write_stdin(input + "\n")
+        .timeout(Duration::from_secs(10))
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(r#""name":"relay""#))
+        .stdout(predicate::str::contains("mock__echo"))
+        .stdout(predicate::str::contains("Echo: hi"))
+        .stdout(predicate::str::contains("Unknown tool: nope__echo"));
+}
+
+#[test]
+fn test_project_config_overrides_user_config() {
+    let dir = tempdir().unwrap();
+    let config_path = dir.path().join("config.yaml");
+    let config_str = config_path.to_str().unwrap();
+
+    add_mock_server(config_str);
+
+    let project_dir = dir.path().join("repo");
+    let nested_dir = project_dir.join("src");
+    std::fs::create_dir_all(&nested_dir).unwrap();
+    std::fs::write(
+        project_dir.join(".relay.yaml"),
+        "servers:\n  docs:\n    transport: http\n    url: http://localhost:9/mcp\ndefault_server: docs\n",
+    )
+    .unwrap();
+
+    // Nothing from the project file applies until it is trusted
+    relay_cmd(config_str)
+        .current_dir(&nested_dir)
+        .arg("list")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("mock"))
+        .stdout(predicate::str::contains("docs").not())
+        .stderr(predicate::str::contains("isn't trusted yet"));
+    relay_cmd(config_str)
+        .current_dir(&nested_dir)
+        .arg("trust")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Trusted"));
+
+    relay_cmd(config_str)
+        .current_dir(&nested_dir)
+        .arg("list")
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("trusted").not())
+        .stdout(predicate::str::contains("mock"))
+        .stdout(predicate::str::contains("docs"))
+        .stdout(predicate::str::contains(".relay.yaml"))
+        .stdout(predicate::str::contains("config.yaml"));
+
+    // Project servers are read-only from relay's point of view
+    relay_cmd(config_str)
+        .current_dir(&nested_dir)
+        .args(["remove", "docs"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(".relay.yaml"));
+
+    // Outside the project only the user config applies
+    relay_cmd(config_str)
+        .current_dir(dir.path())
+        .arg("list")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("docs").not());
+
+    // Editing the file withdraws the trust
+    std::fs::write(
+        project_dir.join(".relay.yaml"),
+        "servers:\n  docs:\n    transport: stdio\n    command: touch pwned\n",
+    )
+    .unwrap();
+    relay_cmd(config_str)
+        .current_dir(&nested_dir)
+        .arg("list")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("docs").not())
+        .stderr(predicate::str::contains("isn't trusted yet"));
+}
+
+#[test]
+fn test_import_and_export() {
+    let dir = tempdir().unwrap();
+    let config_path = dir.path().join("config.yaml");
+    let config_str = config_path.to_str().unwrap();
+
+    add_mock_server(config_str);
+
+    let import_path = dir.path().join("claude_desktop_config.json");
+    std::fs::write(
+        &import_path,
+        r#"{"mcpServers": {
+            "mock": {"command": "other"},
+            "files": {"command": "npx", "args": ["-y", "server-files", "/tmp"], "env": {"DEBUG": "1"}}
+        }}"#,
+    )
+    .unwrap();
+
+    // Existing names are reported, not overwritten
+    relay_cmd(config_str)
+        .args(["import", import_path.to_str().unwrap()])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Imported server:"))
+        .stdout(predicate::str::contains("files"))
+        .stdout(predicate::str::contains("already exists"));
+
+    relay_cmd(config_str)
+        .args(["export", "--as", "vscode"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(r#""type": "stdio""#))
+        .stdout(predicate::str::contains(r#""server-files""#))
+        .stdout(predicate::str::contains(r#""DEBUG": "1""#))
+        .stdout(predicate::str::contains("mock-server"));
+}
+
+#[test]
+fn test_stdio_command_with_spaces() {
+    let dir = tempdir().unwrap();
+    let config_path = dir.path().join("config.yaml");
+    let config_str = config_path.to_str().unwrap();
+
+    Command::new("cargo")
+        .args(["build", "--bin", "mock-server"])
+        .assert()
+        .success();
+
+    // Copy the mock server somewhere whose path needs quoting
+    let tool_dir = dir.path().join("My Tools");
+    std::fs::create_dir_all(&tool_dir).unwrap();
+    let mock_path = tool_dir.join("mock-server");
+    std::fs::copy(
+        std::env::current_dir()
+            .unwrap()
+            .join("target/debug/mock-server"),
+        &mock_path,
+    )
+    .unwrap();
+
+    relay_cmd(config_str)
+        .args([
+            "add",
+            "quoted",
+            "--transport",
+            "stdio",
+            "--cmd",
+            &format!("'{}'", mock_path.display()),
+            "--cwd",
+            tool_dir.to_str().unwrap(),
+            "--clear-env",
+        ])
+        .assert()
+        .success();
+
+    relay_cmd(config_str)
+        .args(["add", "argv", "--transport", "stdio", "--cmd"])
+        .arg(&mock_path)
+        .args(["--arg", "--interleave"])
+        .assert()
+        .success();
+
+    for server in ["quoted", "argv"] {
+        relay_cmd(config_str)
+            .args(["tools", server])
+            .timeout(Duration::from_secs(10))
+            .assert()
+            .success()
+            .stdout(predicate::str::contains("echo"));
+    }
+
+    let config = std::fs::read_to_string(&config_path).unwrap();
+    assert!(config.contains("inherit_env: false"));
+    assert!(config.contains("- --interleave"));
+}
+
+#[test]
+fn test_clear_env_keeps_path() {
+    let dir = tempdir().unwrap();
+    let config_path = dir.path().join("config.yaml");
+    let config_str = config_path.to_str().unwrap();
+
+    // The shell looks the bare command up on the PATH relay passed through
+    relay_cmd(config_str)
+        .args(["add", "bare", "--transport", "stdio"])
+        .args(["--cmd", "sh -c 'exec mock-server'", "--clear-env"])
+        .assert()
+        .success();
+
+    let bin_dir = std::path::Path::new(env!("CARGO_BIN_EXE_mock-server"))
+        .parent()
+        .unwrap()
+        .to_path_buf();
+    let path = std::env::join_paths(
+        std::iter::once(bin_dir).chain(std::env::split_paths(&std::env::var_os("PATH").unwrap())),
+    )
+    .unwrap();
+    relay_cmd(config_str)
+        .args(["tools", "bare"])
+        .env("PATH", path)
+        .timeout(Duration::from_secs(10))
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("echo"));
+}
+
+#[test]
+fn test_reports_stdio_server_crash() {
+    let dir = tempdir().unwrap();
+    let config_path = dir.path().join("config.yaml");
+    let config_str = config_path.to_str().unwrap();
+
+    relay_cmd(config_str)
+        .args([
+            "add",
+            "broken",
+            "--transport",
+            "stdio",
+            "--cmd",
+            "sh",
+            "--arg",
+            "-c",
+        ])
+        .args(["--arg", "echo 'SEARCH_API_KEY is not set' >&2; exit 2"])
+        .assert()
+        .success();
+
+    relay_cmd(config_str)
+        .args(["tools", "broken"])
+        .timeout(Duration::from_secs(10))
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "Server exited unexpectedly (exit status: 2)",
+        ))
+        .stderr(predicate::str::contains(
+            "stderr:\n  SEARCH_API_KEY is not set",
+        ));
+}
+
+#[test]
+fn test_stdio_stderr_is_logged_not_shown() {
+    let dir = tempdir().unwrap();
+    let config_path = dir.path().join("config.yaml");
+    let config_str = config_path.to_str().unwrap();
+    add_mock_server(config_str);
+
+    relay_cmd(config_str)
+        .args(["logs", "mock"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("No logs for 'mock' yet"));
+
+    relay_cmd(config_str)
+        .args(["tools", "mock"])
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("mock-server: ready").not());
+    relay_cmd(config_str)
+        .args(["--verbose", "tools", "mock"])
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("mock stderr: mock-server: ready"));
+
+    let output = relay_cmd(config_str)
+        .args(["--format", "json", "logs", "mock", "-n", "3"])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let logs: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    let lines = logs["lines"].as_array().unwrap();
+    assert_eq!(lines.len(), 3);
+    assert_eq!(lines[2], "mock-server: ready");
+    assert!(lines[1].as_str().unwrap().starts_with("--- started"));
+    let log = std::fs::metadata(dir.path().join("state/relay/logs/mock.log")).unwrap();
+    assert_eq!(log.permissions().mode() & 0o777, 0o600);
+}
+
+/// Serve a minimal HTTP MCP server that only answers requests carrying
+/// `X-API-Key: secret`. Returns the server's URL.
+async fn start_api_key_server() -> String {
+    use axum::http::{HeaderMap, StatusCode};
+    use axum::response::IntoResponse;
+    use axum::Json;
+    use serde_json::{json, Value};
+
+    async fn handle(headers: HeaderMap, Json(request): Json<Value>) -> axum::response::Response {
+        if headers.get("x-api-key").and_then(|v| v.to_str().ok()) != Some("secret") {
+            return StatusCode::UNAUTHORIZED.into_response();
+        }
+        let result = match request["method"].as_str() {
+            Some("initialize") => json!({
+                "protocolVersion": "2024-11-05",
+                "capabilities": {"tools": {}},
+                "serverInfo": {"name": "api-key-server"}
+            }),
+            Some("tools/list") => json!({
+                "tools": [{"name": "keyed_tool", "inputSchema": {"type": "object"}}]
+            }),
+            _ => json!({}),
+        };
+        Json(json!({"jsonrpc": "2.0", "id": request["id"], "result": result})).into_response()
+    }
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let app = axum::Router::new().route("/mcp", axum::routing::post(handle));
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+    format!("http://{}/mcp", addr)
+}
+
+#[tokio::test]
+async fn test_custom_http_headers() {
+    let url = start_api_key_server().await;
+
+    tokio::task::spawn_blocking(move || {
+        let dir = tempdir().unwrap();
+        let config_path = dir.path().join("config.yaml");
+        let config_str = config_path.to_str().unwrap();
+
+        relay_cmd(config_str)
+            .args(["add", "keyed", "--transport", "http", "--url", &url])
+            .args(["--header", "X-API-Key=${env:RELAY_TEST_API_KEY}"])
+            .assert()
+            .success();
+
+        relay_cmd(config_str)
+            .args(["tools", "keyed"])
+            .env("RELAY_TEST_API_KEY", "secret")
+            .timeout(Duration::from_secs(10))
+            .assert()
+            .success()
+            .stdout(predicate::str::contains("keyed_tool"));
+
+        relay_cmd(config_str)
+            .args(["tools", "keyed"])
+            .env("RELAY_TEST_API_KEY", "wrong")
+            .timeout(Duration::from_secs(10))
+            .assert()
+            .failure()
+            .stderr(predicate::str::contains("Authentication required"));
+    })
+    .await
+    .unwrap();
+}
+
+#[test]
+fn test_request_timeout() {
+    let dir = tempdir().unwrap();
+    let config_path = dir.path().join("config.yaml");
+    let config_str = config_path.to_str().unwrap();
+
+    add_mock_server(config_str);
+
+    relay_cmd(config_str)
+        .args(["--timeout", "300ms", "run", "sleep", "--server", "mock"])
+        .args(["--seconds", "5"])
+        .timeout(Duration::from_secs(10))
+        .assert()
+        .code(124)
+        .stderr(predicate::str::contains("timed out after 300ms"));
+    // The server was told to stop working on the call
+    assert!(server_log(config_str).contains("(\"Timed out after 300ms\")"));
+
+    relay_cmd(config_str)
+        .args(["--timeout", "5s", "run", "sleep", "--server", "mock"])
+        .args(["--seconds", "0.1"])
+        .timeout(Duration::from_secs(10))
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Slept"));
+}
+
+/// The mock server's stderr, as logged by relay
+fn server_log(config_path: &str) -> String {
+    let dir = std::path::Path::new(config_path).parent().unwrap();
+    std::fs::read_to_string(dir.join("state/relay/logs/mock.log")).unwrap_or_default()
+}
+
+/// Whether the mock server's log comes to contain `needle`. The daemon
+/// writes its sessions' stderr in the background, so it can lag the reply.
+fn server_log_shows(config_path: &str, needle: &str) -> bool {
+    for _ in 0..50 {
+        if server_log(config_path).contains(needle) {
+            return true;
+        }
+        std::thread::sleep(Duration::from_millis(100));
+    }
+    false
+}
+
+/// The `type: progress` events relay wrote to stderr in JSON mode
+fn progress_events(stderr: &[u8]) -> Vec<serde_json::Value> {
+    String::from_utf8_lossy(stderr)
+        .lines()
+        .filter_map(|line| serde_json::from_str::<serde_json::Value>(line).ok())
+        .filter(|event| event["type"] == "progress")
+        .collect()
+}
+
+#[test]
+fn test_progress_and_interrupt() {
+    use std::io::BufRead;
+
+    let dir = tempdir().unwrap();
+    let config_path = dir.path().join("config.yaml");
+    let config_str = config_path.to_str().unwrap();
+    add_mock_server(config_str);
+
+    // Progress goes to stderr as NDJSON, leaving stdout a single document
+    let output = relay_cmd(config_str)
+        .args(["--format", "json", "run", "sleep", "-s", "mock"])
+        .args(["--seconds", "0.5"])
+        .timeout(Duration::from_secs(10))
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let result: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(result["content"][0]["text"], "Slept 0.5s");
+    let events = progress_events(&output.stderr);
+    assert!(events.len() >= 2, "{:?}", events);
+    assert_eq!(events[0]["total"], 0.5);
+    assert_eq!(events[0]["message"], "Sleeping");
+
+    // Ctrl-C cancels the call on the server and exits with 130
+    let dir_path = dir.path();
+    let mut child = std::process::Command::new(env!("CARGO_BIN_EXE_relay"))
+        .env("RELAY_CONFIG", config_str)
+        .env("XDG_CACHE_HOME", dir_path.join("cache"))
+        .env("XDG_STATE_HOME", dir_path.join("state"))
+        .args([
+            "--format",
+            "json",
+            "run",
+            "sleep",
+            "-s",
+            "mock",
+            "--seconds",
+            "30",
+        ])
+        .stdout(std::process::Stdio::null())
+        .stderr(std::process::Stdio::piped())
+        .spawn()
+        .unwrap();
+    let mut stderr = std::io::BufReader::new(child.stderr.take().unwrap());
+    let mut line = String::new();
+    stderr.read_line(&mut line).unwrap();
+    assert!(line.contains("\"progress\""), "{}", line);
+
+    unsafe { libc::kill(child.id() as i32, libc::SIGINT) };
+    let started = std::time::Instant::now();
+    let status = child.wait().unwrap();
+    assert_eq!(status.code(), Some(130));
+    assert!(started.elapsed() < Duration::from_secs(10));
+    assert!(server_log(config_str).contains("(\"Interrupted\")"));
+}
+
+#[test]
+fn test_sampling_command() {
+    let dir = tempdir().unwrap();
+    let config_path = dir.path().join("config.yaml");
+    let config_str = config_path.to_str().unwrap();
+    add_mock_server(config_str);
+
+    // Without a sampling command relay doesn't advertise sampling
+    relay_cmd(config_str)
+        .args(["run", "ask", "-s", "mock", "--question", "2+2?"])
+        .assert()
+        .failure()
+        .stdout(predicate::str::contains("Client does not support sampling"));
+
+    // A stub model that records what it was asked
+    let request_path = dir.path().join("sampling-request.json");
+    let stub = dir.path().join("stub-model.sh");
+    let script = r#"cat > REQUEST
+printf '{"role":"assistant","content":{"type":"text","text":"4"},"model":"stub-%s"}' "$RELAY_SERVER"
+"#;
+    std::fs::write(
+        &stub,
+        script.replace("REQUEST", request_path.to_str().unwrap()),
+    )
+    .unwrap();
+    let mut config = std::fs::read_to_string(&config_path).unwrap();
+    config.push_str(&format!("sampling:\n  command: sh {}\n", stub.display()));
+    std::fs::write(&config_path, config).unwrap();
+
+    // There is no terminal to ask on, so the request is declined
+    relay_cmd(config_str)
+        .args(["run", "ask", "-s", "mock", "--question", "2+2?"])
+        .assert()
+        .failure()
+        .stdout(predicate::str::contains("User rejected sampling request"));
+    assert!(!request_path.exists());
+
+    relay_cmd(config_str)
+        .args(["--auto-approve-sampling", "run", "ask", "-s", "mock"])
+        .args(["--question", "2+2?"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Model said: 4"));
+    let request: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string(&request_path).unwrap()).unwrap();
+    assert_eq!(request["messages"][0]["content"]["text"], "2+2?");
+    assert_eq!(request["maxTokens"], 100);
+
+    // Every request is logged with its outcome, readable only by the user
+    let log_path = dir.path().join("state/relay/sampling.log");
+    let mode = std::fs::metadata(&log_path).unwrap().permissions().mode();
+    assert_eq!(mode & 0o777, 0o600);
+    let log = std::fs::read_to_string(&log_path).unwrap();
+    let entries: Vec<serde_json::Value> = log
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    assert_eq!(entries.len(), 2);
+    assert_eq!(entries[0]["approved"], false);
+    assert_eq!(entries[1]["approved"], true);
+    assert_eq!(entries[1]["server"], "mock");
+    assert_eq!(entries[1]["result"]["model"], "stub-mock");
+}
+
+#[test]
+fn test_server_log_records() {
+    use std::io::BufRead;
+
+    let dir = tempdir().unwrap();
+    let config_path = dir.path().join("config.yaml");
+    let config_str = config_path.to_str().unwrap();
+    add_mock_server(config_str);
+
+    // Servers send nothing until relay sets a level
+    relay_cmd(config_str)
+        .args(["run", "echo", "-s", "mock", "--message", "hi"])
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("tool called").not());
+
+    relay_cmd(config_str)
+        .args(["--server-log-level", "debug", "run", "echo", "-s", "mock"])
+        .args(["--message", "hi"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Echo: hi"))
+        .stderr(predicate::str::contains(
+            "mock info [mock]: log level set to debug",
+        ))
+        .stderr(predicate::str::contains(
+            r#"{"detail":"debug records enabled"}"#,
+        ))
+        .stderr(predicate::str::contains(
+            "mock warning [mock]: tool called: echo",
+        ));
+
+    // The server filters by level, and RUST_LOG filters what relay shows
+    relay_cmd(config_str)
+        .args(["--server-log-level", "warning", "run", "echo", "-s", "mock"])
+        .args(["--message", "hi"])
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("log level set").not())
+        .stderr(predicate::str::contains("tool called: echo"));
+    relay_cmd(config_str)
+        .env("RUST_LOG", "server_log=error")
+        .args(["--server-log-level", "debug", "run", "echo", "-s", "mock"])
+        .args(["--message", "hi"])
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("mock").not());
+
+    // --follow streams records as they arrive until interrupted
+    let dir_path = dir.path();
+    let mut child = std::process::Command::new(env!("CARGO_BIN_EXE_relay"))
+        .env("RELAY_CONFIG", config_str)
+        .env("XDG_CACHE_HOME", dir_path.join("cache"))
+        .env("XDG_STATE_HOME", dir_path.join("state"))
+        .args(["--format", "json", "--server-log-level", "info"])
+        .args(["logs", "mock", "--follow"])
+        .stdout(std::process::Stdio::piped())
+        .stderr(std::process::Stdio::null())
+        .spawn()
+        .unwrap();
+    let mut stdout = std::io::BufReader::new(child.stdout.take().unwrap());
+    let mut line = String::new();
+    stdout.read_line(&mut line).unwrap();
+    let record: serde_json::Value = serde_json::from_str(&line).unwrap();
+    assert_eq!(record["type"], "log");
+    assert_eq!(record["server"], "mock");
+    assert_eq!(record["level"], "info");
+    assert_eq!(record["logger"], "mock");
+    assert_eq!(record["data"], "log level set to info");
+
+    unsafe { libc::kill(child.id() as i32, libc::SIGINT) };
+    assert!(child.wait().unwrap().success());
+}
+
+#[test]
+fn test_elicitation() {
+    let dir = tempdir().unwrap();
+    let config_path = dir.path().join("config.yaml");
+    let config_str = config_path.to_str().unwrap();
+    add_mock_server(config_str);
+
+    // There is no terminal to ask on, so the request is declined
+    relay_cmd(config_str)
+        .args(["run", "confirm", "-s", "mock"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("User chose decline: null"));
+
+    // Typed values from --elicit, with the schema's defaults filled in
+    relay_cmd(config_str)
+        .args(["run", "confirm", "-s", "mock"])
+        .args(["--elicit", "project=relay", "--elicit", "count=3"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            r#"User chose accept: {"count":3,"dryRun":true,"project":"relay"}"#,
+        ));
+
+    // Values that don't fit the schema are declined
+    relay_cmd(config_str)
+        .args(["run", "confirm", "-s", "mock"])
+        .args(["--elicit", "project=relay", "--elicit", "count=many"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("User chose decline"));
+}
+
+#[test]
+fn test_roots() {
+    let dir = tempdir().unwrap();
+    let config_path = dir.path().join("config.yaml");
+    let config_str = config_path.to_str().unwrap();
+    add_mock_server(config_str);
+    let project = dir.path().canonicalize().unwrap().join("project");
+    std::fs::create_dir_all(project.join("docs")).unwrap();
+
+    // By default the only root is the current directory
+    relay_cmd(config_str)
+        .args(["run", "roots", "-s", "mock"])
+        .current_dir(&project)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(format!(
+            "file://{}",
+            project.display()
+        )));
+
+    // Configured roots are relative to the config file
+    let config = std::fs::read_to_string(&config_path).unwrap().replace(
+        "    transport: stdio\n",
+        "    transport: stdio\n    roots:\n    - project/docs\n    - /tmp\n",
+    );
+    std::fs::write(&config_path, config).unwrap();
+    relay_cmd(config_str)
+        .args(["run", "roots", "-s", "mock"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(format!(
+            "file://{}/docs\nfile:///tmp",
+            project.display()
+        )));
+}
+
+#[test]
+fn test_daemon_notifies_roots_changes() {
+    let dir = tempdir().unwrap();
+    let config_path = dir.path().join("config.yaml");
+    let config_str = config_path.to_str().unwrap();
+    add_mock_server(config_str);
+    let first = dir.path().canonicalize().unwrap().join("first");
+    let second = dir.path().canonicalize().unwrap().join("second");
+    std::fs::create_dir_all(&first).unwrap();
+    std::fs::create_dir_all(&second).unwrap();
+
+    relay_cmd(config_str)
+        .args(["daemon", "start"])
+        .assert()
+        .success();
+    let _guard = DaemonGuard(config_str);
+
+    relay_cmd(config_str)
+        .args(["run", "roots", "-s", "mock"])
+        .current_dir(&first)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(format!(
+            "file://{}",
+            first.display()
+        )));
+    assert!(!server_log(config_str).contains("mock-server: roots changed"));
+
+    // A caller in another directory reuses the session with its own roots
+    relay_cmd(config_str)
+        .args(["run", "roots", "-s", "mock"])
+        .current_dir(&second)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(format!(
+            "file://{}",
+            second.display()
+        )));
+    assert!(server_log_shows(config_str, "mock-server: roots changed"));
+    assert_eq!(
+        daemon_status(config_str)["sessions"]
+            .as_array()
+            .unwrap()
+            .len(),
+        1
+    );
+}
+
+/// Serve an HTTP MCP server that answers the first `failures` requests with
+/// 503 and a `Retry-After` header. Returns the server's URL.
+async fn start_flaky_server(failures: usize) -> String {
+    use axum::http::StatusCode;
+    use axum::response::IntoResponse;
+    use axum::Json;
+    use serde_json::{json, Value};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    let count = Arc::new(AtomicUsize::new(0));
+    let handle = move |Json(request): Json<Value>| {
+        let count = count.clone();
+        async move {
+            if count.fetch_add(1, Ordering::SeqCst) < failures {
+                return (
+                    StatusCode::SERVICE_UNAVAILABLE,
+                    [("retry-after", "0")],
+                    "busy",
+                )
+                    .into_response();
+            }
+            let result = match request["method"].as_str() {
+                Some("initialize") => json!({
+                    "protocolVersion": "2024-11-05",
+                    "capabilities": {"tools": {}},
+                    "serverInfo": {"name": "flaky-server"}
+                }),
+                _ => json!({"tools": [{"name": "flaky_tool", "inputSchema": {"type": "object"}}]}),
+            };
+            Json(json!({"jsonrpc": "2.0", "id": request["id"], "result": result})).into_response()
+        }
+    };
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let app = axum::Router::new().route("/mcp", axum::routing::post(handle));
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+    format!("http://{}/mcp", addr)
+}
+
+#[tokio::test]
+async fn test_retries_transient_http_errors() {
+    let recovering = start_flaky_server(2).await;
+    let failing = start_flaky_server(usize::MAX).await;
+
+    tokio::task::spawn_blocking(move || {
+        let dir = tempdir().unwrap();
+        let config_path = dir.path().join("config.yaml");
+        let config_str = config_path.to_str().unwrap();
+
+        for (name, url) in [("recovering", &recovering), ("failing", &failing)] {
+            relay_cmd(config_str)
+                .args(["add", name, "--transport", "http", "--url", url])
+                .assert()
+                .success();
+        }
+
+        relay_cmd(config_str)
+            .args(["tools", "recovering"])
+            .timeout(Duration::from_secs(10))
+            .assert()
+            .success()
+            .stdout(predicate::str::contains("flaky_tool"));
+
+        relay_cmd(config_str)
+            .args(["--retries", "1", "tools", "failing"])
+            .timeout(Duration::from_secs(10))
+            .assert()
+            .failure()
+            .stderr(predicate::str::contains("failed after 2 attempts"));
+    })
+    .await
+    .unwrap();
+}
+
+/// Encode a server-sent event with randomly chosen but valid syntax: line
+/// endings, optional space after the colon, comments and multi-line data
+fn encode_sse_event(rng: &mut fastrand::Rng, event: Option<&str>, data: &str) -> String {
+    let newline = ["\n", "\r\n", "\r"][rng.usize(..3)];
+    let space = if rng.bool() { " " } else { "" };
+    let mut encoded = String::new();
+    if rng.bool() {
+        encoded.push_str(&format!(": keep-alive{}", newline));
+    }
+    if let Some(event) = event {
+        encoded.push_str(&format!("event:{}{}{}", space, event, newline));
+    }
+    for line in data.lines() {
+        encoded.push_str(&format!("data:{}{}{}", space, line, newline));
+    }
+    encoded.push_str(newline);
+    encoded
+}
+
+/// Serve a legacy HTTP+SSE MCP server at `/mcp`: POSTs to `/mcp` are
+/// rejected with `post_status`, a GET opens the event stream and requests are
+/// POSTed to the advertised endpoint. Events are encoded and split into
+/// chunks at random, driven by `seed`. Returns the server's URL.
+async fn start_legacy_sse_server(seed: u64, post_status: axum::http::StatusCode) -> String {
+    use axum::body::{Body, Bytes};
+    use axum::http::StatusCode;
+    use axum::routing::{get, post};
+    use axum::Json;
+    use serde_json::{json, Value};
+    use std::sync::{Arc, Mutex};
+    use tokio::sync::mpsc;
+
+    struct Session {
+        rng: fastrand::Rng,
+        events: Option<mpsc::UnboundedSender<Bytes>>,
+    }
+
+    impl Session {
+        /// Queue an event in randomly sized pieces
+        fn send(&mut self, event: Option<&str>, data: &str) {
+            let encoded = encode_sse_event(&mut self.rng, event, data).into_bytes();
+            let Some(tx) = &self.events else { return };
+            let mut rest = encoded.as_slice();
+            while !rest.is_empty() {
+                let (chunk, tail) = rest.split_at(self.rng.usize(1..=rest.len()));
+                tx.send(Bytes::copy_from_slice(chunk)).ok();
+                rest = tail;
+            }
+        }
+    }
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let session = Arc::new(Mutex::new(Session {
+        rng: fastrand::Rng::with_seed(seed),
+        events: None,
+    }));
+
+    let open_stream = {
+        let session = session.clone();
+        move || {
+            let (tx, rx) = mpsc::unbounded_channel();
+            let mut session = session.lock().unwrap();
+            session.events = Some(tx);
+            let endpoint = if session.rng.bool() {
+                "/mcp/messages?sessionId=1&v=2".to_string()
+            } else {
+                format!("http://{}/mcp/messages?sessionId=1&v=2", addr)
+            };
+            session.send(Some("endpoint"), &endpoint);
+
+            let events = futures_util::stream::unfold(rx, |mut rx| async move {
+                let chunk = rx.recv().await?;
+                Some((Ok::<_, std::io::Error>(chunk), rx))
+            });
+            async move {
+                (
+                    [("content-type", "text/event-stream")],
+                    Body::from_stream(events),
+                )
+            }
+        }
+    };
+
+    let handle_message = move |Json(request): Json<Value>| {
+        let session = session.clone();
+        async move {
+            let result = match request["method"].as_str() {
+                Some("initialize") => json!({
+                    "protocolVersion": "2024-11-05",
+                    "capabilities": {"tools": {}},
+                    "serverInfo": {"name": "legacy-server"}
+                }),
+                _ => json!({"tools": [{
+                    "name": "legacy_tool",
+                    "description": "Multi-line\ndescription",
+                    "inputSchema": {"type": "object"}
+                }]}),
+            };
+            if request.get("id").is_some() && request.get("method").is_some() {
+                let response = json!({"jsonrpc": "2.0", "id": request["id"], "result": result});
+                let mut session = session.lock().unwrap();
+                let event = session.rng.bool().then_some("message");
+                // Pretty-printing spreads the message over several data lines
+                session.send(event, &serde_json::to_string_pretty(&response).unwrap());
+            }
+            StatusCode::ACCEPTED
+        }
+    };
+
+    let app = axum::Router::new()
+        .route(
+            "/mcp",
+            get(open_stream).post(move || async move { post_status }),
+        )
+        .route("/mcp/messages", post(handle_message));
+
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+    format!("http://{}/mcp", addr)
+}
+
+#[tokio::test]
+async fn test_falls_back_to_legacy_sse() {
+    let url = start_legacy_sse_server(0, axum::http::StatusCode::METHOD_NOT_ALLOWED).await;
+
+    tokio::task::spawn_blocking(move || {
+        let dir = tempdir().unwrap();
+        let config_path = dir.path().join("config.yaml");
+        let config_str = config_path.to_str().unwrap();
+
+        relay_cmd(config_str)
+            .args(["add", "legacy", "--transport", "http", "--url", &url])
+            .assert()
+            .success();
+
+        relay_cmd(config_str)
+            .args(["tools", "legacy"])
+            .timeout(Duration::from_secs(10))
+            .assert()
+            .success()
+            .stdout(predicate::str::contains("legacy_tool"));
+
+        // The detected transport is remembered
+        let config = std::fs::read_to_string(&config_path).unwrap();
+        assert!(config.contains("transport: sse"));
+
+        relay_cmd(config_str)
+            .args(["tools", "legacy"])
+            .timeout(Duration::from_secs(10))
+            .assert()
+            .success()
+            .stdout(predicate::str::contains("legacy_tool"));
+    })
+    .await
+    .unwrap();
+}
+
+#[tokio::test]
+async fn test_forbidden_keeps_streamable_http() {
+    // The server would answer over legacy SSE, but only with a valid key
+    let url = start_legacy_sse_server(0, axum::http::StatusCode::FORBIDDEN).await;
+
+    tokio::task::spawn_blocking(move || {
+        let dir = tempdir().unwrap();
+        let config_path = dir.path().join("config.yaml");
+        let config_str = config_path.to_str().unwrap();
+
+        relay_cmd(config_str)
+            .args(["add", "locked", "--transport", "http", "--url", &url])
+            .assert()
+            .success();
+        let before = std::fs::read_to_string(&config_path).unwrap();
+
+        // A bad key is the server's answer, not a sign it only speaks SSE
+        relay_cmd(config_str)
+            .args(["tools", "locked"])
+            .timeout(Duration::from_secs(10))
+            .assert()
+            .failure()
+            .stderr(predicate::str::contains("403"));
+        assert_eq!(std::fs::read_to_string(&config_path).unwrap(), before);
+    })
+    .await
+    .unwrap();
+}
+
+/// Serve a streamable HTTP MCP server that answers `tools/list` with an event
+/// stream: it sends a `ping` request, then drops the stream before the
+/// response, which is only delivered when the client resumes with a GET and
+/// `Last-Event-ID`. Returns the URL and a log of what the client did.
+async fn start_streaming_server() -> (String, std::sync::Arc<std::sync::Mutex<Vec<String>>>) {
+    use axum::http::{HeaderMap, StatusCode};
+    use axum::response::IntoResponse;
+    use axum::routing::post;
+    use axum::Json;
+    use serde_json::{json, Value};
+    use std::sync::{Arc, Mutex};
+
+    let log: Arc<Mutex<Vec<String>>> = Arc::default();
+
+    let handle_post = {
+        let log = log.clone();
+        move |Json(message): Json<Value>| {
+            let log = log.clone();
+            async move {
+                let sse =
+                    |body: String| ([("content-type", "text/event-stream")], body).into_response();
+                match message["method"].as_str() {
+                    Some("initialize") => (
+                        [("mcp-session-id", "session-1")],
+                        Json(json!({
+                            "jsonrpc": "2.0",
+                            "id": message["id"],
+                            "result": {
+                                "protocolVersion": "2024-11-05",
+                                "capabilities": {"tools": {}},
+                                "serverInfo": {"name": "streaming-server"}
+                            }
+                        })),
+                    )
+                        .into_response(),
+                    Some("tools/list") => {
+                        let ping = json!({"jsonrpc": "2.0", "id": "srv-1", "method": "ping"});
+                        sse(format!("id: 1\ndata: {}\n\n", ping))
+                    }
+                    Some(_) => StatusCode::ACCEPTED.into_response(),
+                    None => {
+                        log.lock()
+                            .unwrap()
+                            .push(format!("answered {}", message["id"]));
+                        StatusCode::ACCEPTED.into_response()
+                    }
+                }
+            }
+        }
+    };
+
+    let handle_get = {
+        let log = log.clone();
+        move |headers: HeaderMap| {
+            let log = log.clone();
+            async move {
+                let last_event_id = headers
+                    .get("last-event-id")
+                    .and_then(|v| v.to_str().ok())
+                    .unwrap_or_default();
+                log.lock()
+                    .unwrap()
+                    .push(format!("resumed after {}", last_event_id));
+                let response = json!({
+                    "jsonrpc": "2.0",
+                    "id": 2,
+                    "result": {"tools": [{"name": "streamed_tool", "inputSchema": {"type": "object"}}]}
+                });
+                (
+                    [("content-type", "text/event-stream")],
+                    format!("id: 2\ndata: {}\n\n", response),
+                )
+            }
+        }
+    };
+
+    let handle_delete = {
+        let log = log.clone();
+        move |headers: HeaderMap| {
+            let log = log.clone();
+            async move {
+                let session = headers
+                    .get("mcp-session-id")
+                    .and_then(|v| v.to_str().ok())
+                    .unwrap_or_default();
+                log.lock().unwrap().push(format!("deleted {}", session));
+                StatusCode::OK
+            }
+        }
+    };
+
+    let app = axum::Router::new().route(
+        "/mcp",
+        post(handle_post).get(handle_get).delete(handle_delete),
+    );
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+    (format!("http://{}/mcp", addr), log)
+}
+
+#[tokio::test]
+async fn test_streamable_http_resumes_and_ends_session() {
+    let (url, log) = start_streaming_server().await;
+
+    tokio::task::spawn_blocking(move || {
+        let dir = tempdir().unwrap();
+        let config_path = dir.path().join("config.yaml");
+        let config_str = config_path.to_str().unwrap();
+
+        relay_cmd(config_str)
+            .args(["add", "streaming", "--transport", "http", "--url", &url])
+            .assert()
+            .success();
+
+        relay_cmd(config_str)
+            .args(["tools", "streaming"])
+            .timeout(Duration::from_secs(10))
+            .assert()
+            .success()
+            .stdout(predicate::str::contains("streamed_tool"));
+    })
+    .await
+    .unwrap();
+
+    assert_eq!(
+        *log.lock().unwrap(),
+        ["answered \"srv-1\"", "resumed after 1", "deleted session-1"]
+    );
+}
+
+#[tokio::test]
+async fn test_legacy_sse_encodings() {
+    let mut urls = Vec::new();
+    for seed in 1..=8 {
+        urls.push(start_legacy_sse_server(seed, axum::http::StatusCode::METHOD_NOT_ALLOWED).await);
+    }
+
+    tokio::task::spawn_blocking(move || {
+        let dir = tempdir().unwrap();
+        let config_path = dir.path().join("config.yaml");
+        let config_str = config_path.to_str().unwrap();
+
+        for (i, url) in urls.iter().enumerate() {
+            let name = format!("legacy{}", i);
+            relay_cmd(config_str)
+                .args(["add", &name, "--transport", "sse", "--url", url])
+                .assert()
+                .success();
+
+            relay_cmd(config_str)
+                .args(["tools", &name])
+                .timeout(Duration::from_secs(10))
+                .assert()
+                .success()
+                .stdout(predicate::str::contains("legacy_tool"));
+        }
+    })
+    .await
+    .unwrap();
+}
+
+/// Serve an MCP server over WebSocket with an `echo` tool. The handshake
+/// requires `X-API-Key: secret`, and before answering a tool call the server
+/// pings the client and waits for its reply. Returns the server's URL.
+async fn start_websocket_server() -> String {
+    use axum::extract::ws::{Message, WebSocket, WebSocketUpgrade};
+    use axum::http::{HeaderMap, StatusCode};
+    use axum::response::IntoResponse;
+    use serde_json::{json, Value};
+
+    async fn serve(mut socket: WebSocket) {
+        while let Some(Ok(Message::Text(text))) = socket.recv().await {
+            let request: Value = serde_json::from_str(text.as_str()).unwrap();
+            let result = match request["method"].as_str() {
+                Some("initialize") => json!({
+                    "protocolVersion": "2024-11-05",
+                    "capabilities": {"tools": {}},
+                    "serverInfo": {"name": "websocket-server"}
+                }),
+                Some("tools/list") => json!({"tools": [{
+                    "name": "echo",
+                    "inputSchema": {
+                        "type": "object",
+                        "properties": {"message": {"type": "string"}}
+                    }
+                }]}),
+                Some("tools/call") => {
+                    let ping = json!({"jsonrpc": "2.0", "id": "srv-1", "method": "ping"});
+                    socket.send(Message::text(ping.to_string())).await.unwrap();
+                    let Some(Ok(Message::Text(reply))) = socket.recv().await else {
+                        return;
+                    };
+                    let reply: Value = serde_json::from_str(reply.as_str()).unwrap();
+                    assert_eq!(reply["id"], "srv-1");
+
+                    let message = request["params"]["arguments"]["message"].clone();
+                    json!({"content": [{"type": "text", "text": format!("ws: {}", message.as_str().unwrap())}]})
+                }
+                _ => continue,
+            };
+            let response = json!({"jsonrpc": "2.0", "id": request["id"], "result": result});
+            socket
+                .send(Message::text(response.to_string()))
+                .await
+                .unwrap();
+        }
+    }
+
+    async fn upgrade(headers: HeaderMap, ws: WebSocketUpgrade) -> axum::response::Response {
+        if headers.get("x-api-key").and_then(|v| v.to_str().ok()) != Some("secret") {
+            return StatusCode::UNAUTHORIZED.into_response();
+        }
+        ws.on_upgrade(serve)
+    }
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let app = axum::Router::new().route("/mcp", axum::routing::get(upgrade));
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+    format!("ws://{}/mcp", addr)
+}
+
+#[tokio::test]
+async fn test_websocket_transport() {
+    let url = start_websocket_server().await;
+
+    tokio::task::spawn_blocking(move || {
+        let dir = tempdir().unwrap();
+        let config_path = dir.path().join("config.yaml");
+        let config_str = config_path.to_str().unwrap();
+
+        relay_cmd(config_str)
+            .args(["add", "ws", "--transport", "http", "--url", &url])
+            .args(["--header", "X-API-Key=secret"])
+            .assert()
+            .success();
+
+        relay_cmd(config_str)
+            .args(["list"])
+            .assert()
+            .success()
+            .stdout(predicate::str::contains("websocket"));
+
+        relay_cmd(config_str)
+            .args([
+                "run",
+                "echo",
+                "--server",
+                "ws",
+                "--message",
+                "over the wire",
+            ])
+            .timeout(Duration::from_secs(10))
+            .assert()
+            .success()
+            .stdout(predicate::str::contains("ws: over the wire"));
+
+        relay_cmd(config_str)
+            .args(["add", "ws-nokey", "--transport", "http", "--url", &url])
+            .assert()
+            .success();
+
+        relay_cmd(config_str)
+            .args(["tools", "ws-nokey"])
+            .timeout(Duration::from_secs(10))
+            .assert()
+            .failure()
+            .stderr(predicate::str::contains("Authentication required"));
+    })
+    .await
+    .unwrap();
+}
+
+/// Bridge each accepted connection to a fresh mock server process, the way a
+/// long-lived local MCP daemon would serve its clients
+async fn bridge_to_mock_server<S>(stream: S)
+where
+    S: tokio::io::AsyncRead + tokio::io::AsyncWrite + Unpin,
+{
+    let mut child = tokio::process::Command::new(env!("CARGO_BIN_EXE_mock-server"))
+        .stdin(std::process::Stdio::piped())
+        .stdout(std::process::Stdio::piped())
+        .spawn()
+        .unwrap();
+    let mut stdio = tokio::io::join(child.stdout.take().unwrap(), child.stdin.take().unwrap());
+    let mut stream = stream;
+    tokio::io::copy_bidirectional(&mut stream, &mut stdio)
+        .await
+        .ok();
+    child.kill().await.ok();
+}
+
+#[tokio::test]
+async fn test_socket_transports() {
+    let dir = tempdir().unwrap();
+    let socket_path = dir.path().join("mcp.sock");
+
+    let unix = tokio::net::UnixListener::bind(&socket_path).unwrap();
+    tokio::spawn(async move {
+        while let Ok((stream, _)) = unix.accept().await {
+            tokio::spawn(bridge_to_mock_server(stream));
+        }
+    });
+    let tcp = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = tcp.local_addr().unwrap().to_string();
+    tokio::spawn(async move {
+        while let Ok((stream, _)) = tcp.accept().await {
+            tokio::spawn(bridge_to_mock_server(stream));
+        }
+    });
+
+    tokio::task::spawn_blocking(move || {
+        let config_path = dir.path().join("config.yaml");
+        let config_str = config_path.to_str().unwrap();
+
+        relay_cmd(config_str)
+            .args(["add", "local-unix", "--transport", "unix", "--socket"])
+            .arg(&socket_path)
+            .assert()
+            .success();
+        relay_cmd(config_str)
+            .args([
+                "add",
+                "local-tcp",
+                "--transport",
+                "tcp",
+                "--address",
+                &address,
+            ])
+            .assert()
+            .success();
+        relay_cmd(config_str)
+            .args(["add", "bad", "--transport", "tcp", "--address", "localhost"])
+            .assert()
+            .failure()
+            .stderr(predicate::str::contains("expected host:port"));
+
+        for server in ["local-unix", "local-tcp"] {
+            relay_cmd(config_str)
+                .args(["run", "echo", "--server", server, "--message", "via socket"])
+                .timeout(Duration::from_secs(10))
+                .assert()
+                .success()
+                .stdout(predicate::str::contains("via socket"));
+        }
+
+        let config = std::fs::read_to_string(&config_path).unwrap();
+        assert!(config.contains("transport: unix"));
+        assert!(config.contains("transport: tcp"));
+    })
+    .await
+    .unwrap();
+}
+
+/// Stops a test's daemon even if the test fails part way
+struct DaemonGuard<'a>(&'a str);
+
+impl Drop for DaemonGuard<'_> {
+    fn drop(&mut self) {
+        relay_cmd(self.0).args(["daemon", "stop"]).output().ok();
+    }
+}
+
+fn daemon_status(config_path: &str) -> serde_json::Value {
+    let output = relay_cmd(config_path)
+        .args(["--format", "json", "daemon", "status"])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    serde_json::from_slice(&output.stdout).unwrap()
+}
+
+#[test]
+fn test_daemon_relays_server_log_records() {
+    let dir = tempdir().unwrap();
+    let config_path = dir.path().join("config.yaml");
+    let config_str = config_path.to_str().unwrap();
+    add_mock_server(config_str);
+
+    relay_cmd(config_str)
+        .args(["daemon", "start"])
+        .assert()
+        .success();
+    let _guard = DaemonGuard(config_str);
+
+    // Records sent while the call runs are passed on
+    relay_cmd(config_str)
+        .args(["--server-log-level", "debug", "run", "echo", "-s", "mock"])
+        .args(["--message", "hi"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Echo: hi"))
+        .stderr(predicate::str::contains(
+            "mock warning [mock]: tool called: echo",
+        ));
+
+    // Each level gets its own session, and callers without one see nothing
+    relay_cmd(config_str)
+        .args(["--server-log-level", "warning", "run", "echo", "-s", "mock"])
+        .args(["--message", "hi"])
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("log level set").not())
+        .stderr(predicate::str::contains("tool called: echo"));
+    relay_cmd(config_str)
+        .args(["run", "echo", "-s", "mock", "--message", "hi"])
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("tool called").not());
+    assert_eq!(
+        daemon_status(config_str)["sessions"]
+            .as_array()
+            .unwrap()
+            .len(),
+        3
+    );
+}
+
+#[test]
+fn test_daemon_sessions_without_prompts() {
+    let dir = tempdir().unwrap();
+    let config_path = dir.path().join("config.yaml");
+    let config_str = config_path.to_str().unwrap();
+    add_mock_server(config_str);
+
+    relay_cmd(config_str)
+        .args(["daemon", "start"])
+        .assert()
+        .success();
+    let _guard = DaemonGuard(config_str);
+
+    // Nobody can answer at the daemon, so its session doesn't offer to
+    relay_cmd(config_str)
+        .args(["run", "confirm", "-s", "mock"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "Client does not support elicitation",
+        ));
+
+    // Callers with answers of their own connect directly
+    relay_cmd(config_str)
+        .args(["run", "confirm", "-s", "mock", "--elicit", "project=relay"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("User chose accept"));
+    assert_eq!(
+        daemon_status(config_str)["sessions"]
+            .as_array()
+            .unwrap()
+            .len(),
+        1
+    );
+}
+
+#[test]
+fn test_daemon_keeps_sessions_warm() {
+    let dir = tempdir().unwrap();
+    let config_path = dir.path().join("config.yaml");
+    let config_str = config_path.to_str().unwrap();
+    add_mock_server(config_str);
+
+    assert_eq!(daemon_status(config_str)["running"], false);
+
+    relay_cmd(config_str)
+        .args(["daemon", "start", "--idle-timeout", "2s"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Daemon started"));
+    let _guard = DaemonGuard(config_str);
+    relay_cmd(config_str)
+        .args(["daemon", "start"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("already running"));
+
+    // Both commands share one session
+    relay_cmd(config_str)
+        .args(["tools", "mock"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("echo"));
+    relay_cmd(config_str)
+        .args(["run", "echo", "-s", "mock", "--message", "via daemon"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Echo: via daemon"));
+
+    // Progress is relayed from the daemon's session, and timeouts cancel there
+    let output = relay_cmd(config_str)
+        .args(["--format", "json", "run", "sleep", "-s", "mock"])
+        .args(["--seconds", "0.3"])
+        .timeout(Duration::from_secs(10))
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    assert!(!progress_events(&output.stderr).is_empty());
+    relay_cmd(config_str)
+        .args(["--timeout", "300ms", "run", "sleep", "-s", "mock"])
+        .args(["--seconds", "5"])
+        .timeout(Duration::from_secs(10))
+        .assert()
+        .code(124);
+    assert!(server_log_shows(config_str, "(\"Timed out after 300ms\")"));
+
+    let status = daemon_status(config_str);
+    assert_eq!(status["running"], true);
+    let sessions = status["sessions"].as_array().unwrap();
+    assert_eq!(sessions.len(), 1);
+    assert_eq!(sessions[0]["server"], "mock");
+    assert_eq!(sessions[0]["server_info"]["name"], "mock-server");
+    assert!(sessions[0]["requests"].as_u64().unwrap() >= 4);
+
+    // Idle sessions are closed, but the daemon keeps running
+    std::thread::sleep(Duration::from_secs(3));
+    let status = daemon_status(config_str);
+    assert_eq!(status["running"], true);
+    assert_eq!(status["sessions"].as_array().unwrap().len(), 0);
+
+    relay_cmd(config_str)
+        .args(["daemon", "stop"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Daemon stopped"));
+    assert_eq!(daemon_status(config_str)["running"], false);
+    assert!(!dir.path().join("daemon.sock").exists());
+
+    // Without the daemon, commands connect directly again
+    relay_cmd(config_str)
+        .args(["run", "echo", "-s", "mock", "--message", "direct"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Echo: direct"));
+}
+
+#[test]
+fn test_tool_list_cache() {
+    let dir = tempdir().unwrap();
+    let config_path = dir.path().join("config.yaml");
+    let config_str = config_path.to_str().unwrap();
+    add_mock_server_with_args(config_str, "--list-changed");
+    let entry_path = dir.path().join("cache/relay/tools/mock.json");
+
+    relay_cmd(config_str)
+        .args(["tools", "mock"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("echo"));
+    let mut entry: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string(&entry_path).unwrap()).unwrap();
+    assert_eq!(entry["server_version"], "1.0.0");
+
+    // Later commands read the cached list rather than asking the server
+    entry["tools"][0]["description"] = "Served from the cache".into();
+    std::fs::write(&entry_path, entry.to_string()).unwrap();
+    relay_cmd(config_str)
+        .args(["describe", "echo", "-s", "mock"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Served from the cache"));
+    relay_cmd(config_str)
+        .args(["--no-cache", "describe", "echo", "-s", "mock"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Echo back a message"));
+
+    // The mock server reports a changed tool list during each call
+    relay_cmd(config_str)
+        .args(["run", "echo", "-s", "mock", "--message", "hi"])
+        .assert()
+        .success();
+    assert!(!entry_path.exists());
+
+    relay_cmd(config_str)
+        .args(["tools", "mock"])
+        .assert()
+        .success();
+    relay_cmd(config_str)
+        .args(["cache", "clear"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Cleared 1 cached tool list(s)"));
+    assert!(!entry_path.exists());
+}
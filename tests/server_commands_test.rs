@@ -1,9 +1,10 @@
+use assert_cmd::cargo::cargo_bin_cmd;
 use assert_cmd::Command;
 use predicates::prelude::*;
 use tempfile::tempdir;
 
 fn relay_cmd(config_path: &str) -> Command {
-    let mut cmd = Command::cargo_bin("relay").unwrap();
+    let mut cmd = cargo_bin_cmd!("relay");
     cmd.env("RELAY_CONFIG", config_path);
     cmd
 }